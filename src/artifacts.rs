@@ -0,0 +1,218 @@
+// Artifact dependencies (`[dependencies.foo] artifact = "bin"`). The cargo we link against
+// predates them and treats them as ordinary dependencies, so the manifest is read directly
+// and the artifacts are built with a compilation of their own.
+
+use anyhow::{bail, Context as AnyhowContext, Result};
+use cargo::{
+  core::{
+    compiler::{CompileMode, Unit},
+    Workspace,
+  },
+  ops::{self, CompileFilter, CompileOptions, FilterRule, LibRule, Packages},
+  util::{config::Config, Filesystem},
+};
+use std::{
+  collections::HashMap,
+  fs,
+  path::{Path, PathBuf},
+};
+use toml::Value;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ArtifactKind {
+  // All bins, or just the named one for `bin:<name>`.
+  Bin(Option<String>),
+  Cdylib,
+}
+
+#[derive(Debug)]
+struct ArtifactDep {
+  // The name the dependency is declared under, which names the env vars.
+  name: String,
+  package: String,
+  kinds: Vec<ArtifactKind>,
+  target: Option<String>,
+}
+
+fn parse_kind(name: &str, kind: &str) -> Result<ArtifactKind> {
+  Ok(match kind.split_once(':') {
+    Some(("bin", bin)) => ArtifactKind::Bin(Some(bin.to_string())),
+    None if kind == "bin" => ArtifactKind::Bin(None),
+    None if kind == "cdylib" => ArtifactKind::Cdylib,
+    _ => bail!(
+      "Unsupported artifact `{}` for dependency `{}`: only `bin`, `bin:<name>` and \
+       `cdylib` are supported",
+      kind,
+      name
+    ),
+  })
+}
+
+fn parse_deps(table: &Value, deps: &mut Vec<ArtifactDep>) -> Result<()> {
+  let table = match table.as_table() {
+    Some(table) => table,
+    None => return Ok(()),
+  };
+  for (name, dep) in table {
+    let kinds = match dep.get("artifact") {
+      Some(Value::String(kind)) => vec![parse_kind(name, kind)?],
+      Some(Value::Array(kinds)) => kinds
+        .iter()
+        .filter_map(Value::as_str)
+        .map(|kind| parse_kind(name, kind))
+        .collect::<Result<_>>()?,
+      _ => continue,
+    };
+    let package = dep.get("package").and_then(Value::as_str).unwrap_or(name);
+    deps.push(ArtifactDep {
+      name: name.clone(),
+      package: package.to_string(),
+      kinds,
+      target: dep.get("target").and_then(Value::as_str).map(str::to_string),
+    });
+  }
+  Ok(())
+}
+
+// Dev-dependencies only count for the units cargo resolves them for.
+fn artifact_deps(manifest_path: &Path, include_dev: bool) -> Result<Vec<ArtifactDep>> {
+  let manifest = fs::read_to_string(manifest_path)
+    .with_context(|| format!("Could not read {}", manifest_path.display()))?
+    .parse::<Value>()
+    .with_context(|| format!("Could not parse {}", manifest_path.display()))?;
+  let mut deps = Vec::new();
+  if let Some(table) = manifest.get("dependencies") {
+    parse_deps(table, &mut deps)?;
+  }
+  if include_dev {
+    if let Some(table) = manifest.get("dev-dependencies") {
+      parse_deps(table, &mut deps)?;
+    }
+  }
+  Ok(deps)
+}
+
+fn env_name(name: &str) -> String {
+  name.to_uppercase().replace('-', "_")
+}
+
+/// Whether `unit` has artifact dependencies, without building them.
+pub(crate) fn has_artifact_deps(unit: &Unit) -> bool {
+  let include_dev = unit.mode.is_any_test() || unit.target.is_example();
+  artifact_deps(unit.pkg.manifest_path(), include_dev).map_or(false, |deps| !deps.is_empty())
+}
+
+/// Builds the artifact dependencies of `unit` and returns the `CARGO_BIN_*` and
+/// `CARGO_CDYLIB_*` variables pointing at them. `requested_target` is the unit's own
+/// target, which artifacts without `target = ".."` are built for.
+pub(crate) fn artifact_env(
+  ws: &Workspace,
+  unit: &Unit,
+  requested_target: Option<&str>,
+  jobs: Option<u32>,
+) -> Result<HashMap<String, String>> {
+  let include_dev = unit.mode.is_any_test() || unit.target.is_example();
+  let deps = artifact_deps(unit.pkg.manifest_path(), include_dev)?;
+  let mut env = HashMap::new();
+  for dep in &deps {
+    let target = match dep.target.as_deref() {
+      None | Some("target") => requested_target,
+      Some(triple) => Some(triple),
+    };
+    let outputs = build(ws, dep, target, jobs).with_context(|| {
+      format!(
+        "Could not build the artifacts of dependency `{}` of {}",
+        dep.name,
+        unit.pkg.name()
+      )
+    })?;
+
+    let dep_name = env_name(&dep.name);
+    for (prefix, target_name, path) in outputs {
+      if let Some(dir) = path.parent() {
+        env.insert(
+          format!("CARGO_{}_DIR_{}", prefix, dep_name),
+          format!("{}", dir.display()),
+        );
+      }
+      let path = format!("{}", path.display());
+      // Bins are also reachable without the name suffix when named like the dependency,
+      // and a package has at most one cdylib.
+      if prefix == "CDYLIB" || target_name == dep.name {
+        env.insert(format!("CARGO_{}_FILE_{}", prefix, dep_name), path.clone());
+      }
+      env.insert(
+        format!("CARGO_{}_FILE_{}_{}", prefix, dep_name, target_name),
+        path,
+      );
+    }
+  }
+  Ok(env)
+}
+
+// Compiles the selected targets of `dep` in a workspace whose target directory is
+// `<target>/artifact`, which keeps clear of the build lock the caller's context holds.
+// Returns the env var prefix, target name and path of every artifact.
+fn build(
+  ws: &Workspace,
+  dep: &ArtifactDep,
+  target: Option<&str>,
+  jobs: Option<u32>,
+) -> Result<Vec<(&'static str, String, PathBuf)>> {
+  let config: &Config = ws.config();
+  let mut artifact_ws = Workspace::new(ws.root_manifest(), config)?;
+  let target_dir = ws.target_dir().join("artifact");
+  artifact_ws.set_target_dir(Filesystem::new(target_dir.into_path_unlocked()));
+
+  let mut compile_opts = CompileOptions::new(config, CompileMode::Build)?;
+  compile_opts.spec = Packages::Packages(vec![dep.package.clone()]);
+  if let Some(jobs) = jobs {
+    compile_opts.build_config.jobs = jobs;
+  }
+  if let Some(target) = target {
+    compile_opts.build_config.requested_kinds =
+      crate::cargo_compat::requested_kinds(config, target)?;
+  }
+  let bins = dep
+    .kinds
+    .iter()
+    .filter_map(|kind| match kind {
+      ArtifactKind::Bin(Some(bin)) => Some(bin.clone()),
+      _ => None,
+    })
+    .collect::<Vec<_>>();
+  let all_bins = dep.kinds.contains(&ArtifactKind::Bin(None));
+  compile_opts.filter = CompileFilter::Only {
+    all_targets: false,
+    lib: if dep.kinds.contains(&ArtifactKind::Cdylib) {
+      LibRule::True
+    } else {
+      LibRule::False
+    },
+    bins: if all_bins {
+      FilterRule::All
+    } else {
+      FilterRule::Just(bins)
+    },
+    examples: FilterRule::Just(vec![]),
+    tests: FilterRule::Just(vec![]),
+    benches: FilterRule::Just(vec![]),
+  };
+
+  let compilation = ops::compile(&artifact_ws, &compile_opts)?;
+  let bins = compilation.binaries.iter().map(|output| {
+    (
+      "BIN",
+      output.unit.target.name().to_string(),
+      output.path.clone(),
+    )
+  });
+  let cdylibs = compilation.cdylibs.iter().map(|output| {
+    (
+      "CDYLIB",
+      output.unit.target.crate_name(),
+      output.path.clone(),
+    )
+  });
+  Ok(bins.chain(cdylibs).collect())
+}
@@ -0,0 +1,75 @@
+// `cargo flags`: prints the rustc command line for a source file, for tools that can't
+// link against cargo.
+//
+//   cargo flags --file src/lib.rs [--features a,b] [--all-features]
+//     [--no-default-features] [--manifest-path Cargo.toml] [--target <triple>]
+//     [--format json|args]
+//
+// With `--format json` (the default), prints `{"args": [...], "env": {...}}`, where `args`
+// starts with `rustc`. With `--format args`, prints one argument per line.
+
+use anyhow::{bail, Context, Result};
+use generate_rustc_flags::FlagsBuilder;
+use serde::Serialize;
+use std::{collections::BTreeMap, env};
+
+#[derive(Serialize)]
+struct Output<'a> {
+  args: Vec<String>,
+  env: BTreeMap<&'a str, &'a str>,
+}
+
+fn run() -> Result<()> {
+  let mut args = env::args().skip(1).peekable();
+  // Cargo runs `cargo-flags flags ...` for `cargo flags ...`.
+  if args.peek().map(String::as_str) == Some("flags") {
+    args.next();
+  }
+
+  let mut builder = FlagsBuilder::new();
+  let mut file = None;
+  let mut format = "json".to_string();
+  while let Some(arg) = args.next() {
+    let mut value = || args.next().with_context(|| format!("{} needs a value", arg));
+    match arg.as_str() {
+      "--file" => file = Some(value()?),
+      "--features" => builder = builder.features(vec![value()?]),
+      "--all-features" => builder = builder.all_features(),
+      "--no-default-features" => builder = builder.no_default_features(),
+      "--manifest-path" => builder = builder.manifest_path(value()?),
+      "--target" => builder = builder.target(value()?),
+      "--format" => format = value()?,
+      _ => bail!("Unknown argument {}", arg),
+    }
+  }
+
+  let file = file.context("Missing --file <path>")?;
+  let flags = builder.generate(file)?;
+  match format.as_str() {
+    "json" => {
+      let output = Output {
+        args: flags.to_args(),
+        env: flags
+          .env
+          .iter()
+          .map(|(k, v)| (k.as_str(), v.as_str()))
+          .collect(),
+      };
+      println!("{}", serde_json::to_string(&output)?);
+    }
+    "args" => {
+      for arg in flags.to_args() {
+        println!("{}", arg);
+      }
+    }
+    _ => bail!("Unknown format {}, expected json or args", format),
+  }
+  Ok(())
+}
+
+fn main() {
+  if let Err(err) = run() {
+    eprintln!("error: {:?}", err);
+    std::process::exit(1);
+  }
+}
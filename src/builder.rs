@@ -0,0 +1,159 @@
+// A builder over `FlagsOptions`, so that options can be added without breaking callers.
+
+use crate::{
+  CompileMode, FallbackMode, FlagsOptions, Incremental, RustcFlags, TargetKind, TargetSelector,
+};
+use anyhow::Result;
+use cargo::core::resolver::CliFeatures;
+use std::path::{Path, PathBuf};
+
+/// Builds [`FlagsOptions`] step by step and generates flags with them, e.g.
+/// `FlagsBuilder::new().features(vec!["serde"]).no_default_features().generate(path)`.
+pub struct FlagsBuilder {
+  options: FlagsOptions,
+  features: Vec<String>,
+  all_features: bool,
+  default_features: bool,
+}
+
+impl Default for FlagsBuilder {
+  fn default() -> Self {
+    FlagsBuilder {
+      options: FlagsOptions::default(),
+      features: Vec::new(),
+      all_features: false,
+      default_features: true,
+    }
+  }
+}
+
+impl FlagsBuilder {
+  pub fn new() -> Self {
+    FlagsBuilder::default()
+  }
+
+  /// See [`FlagsOptions::manifest_path`].
+  pub fn manifest_path(mut self, manifest_path: impl Into<PathBuf>) -> Self {
+    self.options.manifest_path = Some(manifest_path.into());
+    self
+  }
+
+  /// Enables features, each written as on the cargo command line (`foo`, `dep/feat`,
+  /// or a comma/space separated list). Can be called repeatedly.
+  pub fn features<I, S>(mut self, features: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    self.features.extend(features.into_iter().map(Into::into));
+    self
+  }
+
+  pub fn all_features(mut self) -> Self {
+    self.all_features = true;
+    self
+  }
+
+  pub fn no_default_features(mut self) -> Self {
+    self.default_features = false;
+    self
+  }
+
+  /// See [`FlagsOptions::target`].
+  pub fn target(mut self, target: impl Into<String>) -> Self {
+    self.options.target = Some(target.into());
+    self
+  }
+
+  /// See [`FlagsOptions::profile`].
+  pub fn profile(mut self, profile: impl Into<String>) -> Self {
+    self.options.profile = Some(profile.into());
+    self
+  }
+
+  /// See [`FlagsOptions::target_dir`].
+  pub fn target_dir(mut self, target_dir: impl Into<PathBuf>) -> Self {
+    self.options.target_dir = Some(target_dir.into());
+    self
+  }
+
+  /// Keeps this crate's artifacts in `target/<tool>` rather than `target`. See
+  /// [`FlagsOptions::isolated_target_dir`].
+  pub fn isolated_target_dir(mut self, tool: impl Into<String>) -> Self {
+    self.options.isolated_target_dir = Some(tool.into());
+    self
+  }
+
+  /// See [`FlagsOptions::mode`].
+  pub fn mode(mut self, mode: CompileMode) -> Self {
+    self.options.mode = mode;
+    self
+  }
+
+  /// See [`FlagsOptions::test`].
+  pub fn test(mut self, test: bool) -> Self {
+    self.options.test = test;
+    self
+  }
+
+  /// Only consider the package's lib target.
+  pub fn lib_only(mut self, lib_only: bool) -> Self {
+    self.options.lib_only = lib_only;
+    self
+  }
+
+  /// See [`FlagsOptions::target_selector`].
+  pub fn select(mut self, selector: TargetSelector) -> Self {
+    self.options.target_selector = Some(selector);
+    self
+  }
+
+  /// See [`FlagsOptions::target_priority`].
+  pub fn target_priority(mut self, priority: impl IntoIterator<Item = TargetKind>) -> Self {
+    self.options.target_priority = priority.into_iter().collect();
+    self
+  }
+
+  /// See [`FlagsOptions::package_id_spec`].
+  pub fn package(mut self, spec: impl Into<String>) -> Self {
+    self.options.package_id_spec = Some(spec.into());
+    self
+  }
+
+  /// See [`FlagsOptions::package`].
+  pub fn member(mut self, package: impl Into<String>) -> Self {
+    self.options.package = Some(package.into());
+    self
+  }
+
+  /// See [`FlagsOptions::incremental`].
+  pub fn incremental(mut self, incremental: Incremental) -> Self {
+    self.options.incremental = incremental;
+    self
+  }
+
+  pub fn fallback(mut self, fallback: FallbackMode) -> Self {
+    self.options.fallback = fallback;
+    self
+  }
+
+  /// The options built so far, for setting the ones without a builder method.
+  pub fn options_mut(&mut self) -> &mut FlagsOptions {
+    &mut self.options
+  }
+
+  pub fn build(self) -> Result<FlagsOptions> {
+    let features =
+      CliFeatures::from_command_line(&self.features, self.all_features, self.default_features)?;
+    Ok(FlagsOptions {
+      features,
+      ..self.options
+    })
+  }
+
+  /// Shorthand for [`FlagsBuilder::build`] followed by [`crate::generate`].
+  pub fn generate(self, source_path: impl AsRef<Path>) -> Result<RustcFlags> {
+    let options = self.build()?;
+    crate::generate(source_path, &options)
+  }
+}
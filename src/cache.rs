@@ -0,0 +1,168 @@
+//! A persistent cache of generated flags, for tools that run once per crate (like a
+//! `RUSTC_WORKSPACE_WRAPPER`) and so can't keep a [`crate::FlagsContext`] around.
+//!
+//! [`FlagCache`] stores each [`RustcFlags`] as JSON under `<target dir>/.rustc-flags/`,
+//! in a file named after a hash of what the flags were generated from:
+//!
+//! - the source file, and every [`FlagsOptions`] field (file overrides included, and
+//!   callbacks as whether they're set);
+//! - the `Cargo.lock`, and every `Cargo.toml` from the package's directory up to the
+//!   workspace root (the first directory with a lockfile);
+//! - the toolchain, as `rustc -vV` describes it, and `RUSTFLAGS`.
+//!
+//! A change to any of those gives a different key, so the old entry is just never read
+//! again. Like `FlagsContext`, it can't see changes to build script inputs, to
+//! `.cargo/config.toml` or to path dependencies outside the workspace; those need
+//! [`FlagCache::clear`].
+
+use crate::{
+  canonical_source_path, fnv1a, generate, make_config, manifest_path_for, toolchain,
+  use_fallback, FlagsOptions, RustcFlags,
+};
+use anyhow::{Context as AnyhowContext, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+  env, fs,
+  path::{Path, PathBuf},
+  process,
+};
+
+// The key is stored alongside, so that a hash collision reads as a miss.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+  key: String,
+  flags: RustcFlags,
+}
+
+/// Generates flags like [`crate::generate`], reusing the ones an earlier call (in any
+/// process) stored for the same key.
+pub struct FlagCache {
+  options: FlagsOptions,
+}
+
+// A file's path and the hash of its contents, as a line of the key.
+fn file_line(path: &Path) -> Result<String> {
+  let contents = fs::read(path).with_context(|| format!("Could not read {}", path.display()))?;
+  Ok(format!("{} {:016x}", path.display(), fnv1a(&contents)))
+}
+
+// The workspace root, taken to be the first directory above the manifest with a lockfile,
+// and the files from the manifest up to it whose changes invalidate the flags.
+fn workspace_files(manifest_path: &Path) -> (PathBuf, Vec<PathBuf>) {
+  let package_dir = manifest_path.parent().unwrap_or(manifest_path);
+  let mut files = vec![manifest_path.to_path_buf()];
+  for dir in package_dir.ancestors() {
+    let manifest = dir.join("Cargo.toml");
+    if dir != package_dir && manifest.exists() {
+      files.push(manifest);
+    }
+    let lockfile = dir.join("Cargo.lock");
+    if lockfile.exists() {
+      files.push(lockfile);
+      return (dir.to_path_buf(), files);
+    }
+  }
+  (package_dir.to_path_buf(), files)
+}
+
+impl FlagCache {
+  pub fn new(options: FlagsOptions) -> Self {
+    FlagCache { options }
+  }
+
+  pub fn options(&self) -> &FlagsOptions {
+    &self.options
+  }
+
+  /// The stored flags for `source_path` if there are any for the current key, else newly
+  /// generated ones, which are stored. Files outside a package aren't cached, as their
+  /// fallback flags are cheap anyway.
+  pub fn generate(&self, source_path: impl AsRef<Path>) -> Result<RustcFlags> {
+    let options = &self.options;
+    let source_path = &canonical_source_path(source_path.as_ref(), &options.file_overrides)?;
+    if use_fallback(options, source_path) {
+      return generate(source_path, options);
+    }
+
+    let manifest_path = manifest_path_for(options, source_path)?;
+    let (ws_root, files) = workspace_files(&manifest_path);
+    let key = self.key(source_path, &files)?;
+    let dir = self.dir(&ws_root)?;
+    let path = dir.join(format!("{:016x}.json", fnv1a(key.as_bytes())));
+    if let Some(flags) = read_entry(&path, &key) {
+      return Ok(flags);
+    }
+
+    let flags = generate(source_path, options)?;
+    let entry = Entry { key, flags };
+    write_entry(&dir, &path, &entry)?;
+    Ok(entry.flags)
+  }
+
+  /// Removes every stored flag set of the workspace `near` (a file or directory in it)
+  /// belongs to.
+  pub fn clear(&self, near: impl AsRef<Path>) -> Result<()> {
+    let manifest_path = manifest_path_for(&self.options, near.as_ref())?;
+    let (ws_root, _) = workspace_files(&manifest_path);
+    let dir = self.dir(&ws_root)?;
+    if dir.exists() {
+      fs::remove_dir_all(&dir).with_context(|| format!("Could not remove {}", dir.display()))?;
+    }
+    Ok(())
+  }
+
+  fn key(&self, source_path: &Path, files: &[PathBuf]) -> Result<String> {
+    let options = format!("{:?}", self.options);
+    let dir = source_path.parent().unwrap_or(source_path);
+    let mut lines = vec![
+      format!("schema {}", RustcFlags::SCHEMA_VERSION),
+      format!("source {}", source_path.display()),
+      format!("options {:016x}", fnv1a(options.as_bytes())),
+      toolchain::probe_version(self.options.toolchain.as_deref(), dir)?,
+      format!(
+        "rustflags {:?} {:?}",
+        env::var_os("CARGO_ENCODED_RUSTFLAGS"),
+        env::var_os("RUSTFLAGS")
+      ),
+    ];
+    for file in files {
+      lines.push(file_line(file)?);
+    }
+    Ok(lines.join("\n"))
+  }
+
+  // `<target dir>/.rustc-flags`, with the target directory cargo's config says, else the
+  // workspace's `target`.
+  fn dir(&self, ws_root: &Path) -> Result<PathBuf> {
+    let config = make_config(&self.options)?;
+    let mut target_dir = match config.target_dir()? {
+      Some(target_dir) => target_dir.into_path_unlocked(),
+      None => ws_root.join("target"),
+    };
+    if let Some(name) = &self.options.isolated_target_dir {
+      target_dir = target_dir.join(name);
+    }
+    Ok(target_dir.join(".rustc-flags"))
+  }
+}
+
+// The flags stored at `path` under `key`. Unreadable entries, e.g. from another version
+// of this crate, are misses.
+fn read_entry(path: &Path, key: &str) -> Option<RustcFlags> {
+  let entry: Entry = serde_json::from_slice(&fs::read(path).ok()?).ok()?;
+  if entry.key != key {
+    return None;
+  }
+  Some(entry.flags)
+}
+
+// Writes `path` in `dir` through a temporary file, so that concurrent readers never see
+// half an entry.
+fn write_entry(dir: &Path, path: &Path, entry: &Entry) -> Result<()> {
+  fs::create_dir_all(dir).with_context(|| format!("Could not create {}", dir.display()))?;
+  let temp = path.with_extension(format!("{}.tmp", process::id()));
+  fs::write(&temp, serde_json::to_vec(entry)?)
+    .with_context(|| format!("Could not write {}", temp.display()))?;
+  fs::rename(&temp, path).with_context(|| format!("Could not write {}", path.display()))?;
+  Ok(())
+}
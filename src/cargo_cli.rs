@@ -0,0 +1,583 @@
+//! A backend that runs the `cargo` binary and rebuilds the flags from its JSON output,
+//! instead of calling into the cargo this crate links against. The linked cargo pins one
+//! release's internals, while these outputs stay stable across releases, so this keeps
+//! working after toolchain updates that the linked cargo can't follow.
+//!
+//! [`generate`] runs three commands next to the manifest:
+//!
+//! - `cargo metadata`, for the package information behind the `CARGO_PKG_*` variables;
+//! - `cargo check --unit-graph -Z unstable-options`, for the units and their dependencies
+//!   (with `RUSTC_BOOTSTRAP=1`, since the unit graph is unstable);
+//! - `cargo check --message-format=json`, which compiles the dependencies, runs the build
+//!   scripts and reports their artifacts and outputs.
+//!
+//! The flags cover the crate name and types, edition, emitted outputs, the profile's
+//! codegen options, `-C metadata`, features, target, sysroot, externs, and the build
+//! script's cfgs, link paths, libraries and environment. Lints, check-cfgs and anything
+//! else cargo invents along the way are not reconstructed. Of `options`, only the
+//! manifest path, features, target, profile, mode and sysroot settings apply.
+
+use crate::{
+  toolchain, ExternDep, FlagsOptions, FlagsOrigin, PackageEnv, ProfileInfo, RustcFlag,
+  RustcFlags,
+};
+use anyhow::{bail, Context as AnyhowContext, Result};
+use cargo::core::compiler::{CompileMode, CrateType};
+use serde::Deserialize;
+use std::{
+  collections::{BTreeMap, HashMap},
+  env,
+  path::{Path, PathBuf},
+  process::Command,
+};
+
+#[derive(Deserialize)]
+struct UnitGraphJson {
+  units: Vec<UnitJson>,
+  roots: Vec<usize>,
+}
+
+#[derive(Deserialize)]
+struct UnitJson {
+  pkg_id: String,
+  target: TargetJson,
+  profile: ProfileJson,
+  platform: Option<String>,
+  mode: String,
+  features: Vec<String>,
+  dependencies: Vec<UnitDepJson>,
+}
+
+#[derive(Deserialize, PartialEq)]
+struct TargetJson {
+  kind: Vec<String>,
+  crate_types: Vec<String>,
+  name: String,
+  src_path: PathBuf,
+  edition: String,
+}
+
+#[derive(Deserialize)]
+struct ProfileJson {
+  name: String,
+  opt_level: String,
+  lto: String,
+  codegen_units: Option<u32>,
+  debuginfo: Option<u32>,
+  debug_assertions: bool,
+  overflow_checks: bool,
+  panic: String,
+  incremental: bool,
+}
+
+#[derive(Deserialize)]
+struct UnitDepJson {
+  index: usize,
+  extern_crate_name: String,
+}
+
+#[derive(Deserialize)]
+struct ArtifactJson {
+  package_id: String,
+  target: TargetJson,
+  filenames: Vec<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct BuildScriptJson {
+  package_id: String,
+  linked_libs: Vec<String>,
+  linked_paths: Vec<String>,
+  cfgs: Vec<String>,
+  env: Vec<(String, String)>,
+  out_dir: PathBuf,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "reason")]
+enum MessageJson {
+  #[serde(rename = "compiler-artifact")]
+  CompilerArtifact(ArtifactJson),
+  #[serde(rename = "build-script-executed")]
+  BuildScriptExecuted(BuildScriptJson),
+  #[serde(other)]
+  Other,
+}
+
+#[derive(Deserialize)]
+struct MetadataJson {
+  packages: Vec<PackageJson>,
+}
+
+#[derive(Deserialize)]
+struct PackageJson {
+  id: String,
+  name: String,
+  version: String,
+  #[serde(default)]
+  authors: Vec<String>,
+  description: Option<String>,
+  homepage: Option<String>,
+  repository: Option<String>,
+  license: Option<String>,
+  license_file: Option<String>,
+  readme: Option<String>,
+  rust_version: Option<String>,
+  manifest_path: PathBuf,
+  links: Option<String>,
+}
+
+fn cargo_path() -> PathBuf {
+  env::var_os("CARGO")
+    .map(PathBuf::from)
+    .unwrap_or_else(|| PathBuf::from("cargo"))
+}
+
+// `cargo <subcommand>` on the manifest, with the options that select what's compiled.
+fn cargo_command(subcommand: &str, manifest_path: &Path, options: &FlagsOptions) -> Command {
+  let mut command = Command::new(cargo_path());
+  command.arg(subcommand).arg("--manifest-path").arg(manifest_path);
+  if let Some(dir) = manifest_path.parent() {
+    command.current_dir(dir);
+  }
+  if subcommand != "metadata" {
+    command.args(crate::feature_args(&options.features));
+    if let Some(target) = &options.target {
+      command.arg("--target").arg(target);
+    }
+    if let Some(profile) = &options.profile {
+      command.arg("--profile").arg(profile);
+    }
+  }
+  command
+}
+
+fn run(mut command: Command) -> Result<Vec<u8>> {
+  let output = command
+    .output()
+    .with_context(|| format!("Could not run {:?}", command))?;
+  if !output.status.success() {
+    bail!(
+      "{:?} failed:\n{}",
+      command,
+      String::from_utf8_lossy(&output.stderr).trim_end()
+    );
+  }
+  Ok(output.stdout)
+}
+
+fn crate_name(target: &TargetJson) -> String {
+  target.name.replace('-', "_")
+}
+
+// The unit whose target's root is `source_path` or, failing that, the one whose root's
+// directory is the innermost containing it, preferring the root units cargo was asked for.
+fn find_unit<'a>(graph: &'a UnitGraphJson, source_path: &Path) -> Result<&'a UnitJson> {
+  let candidates = graph
+    .roots
+    .iter()
+    .map(|index| &graph.units[*index])
+    .chain(graph.units.iter())
+    .filter(|unit| unit.mode != "run-custom-build");
+  let mut best: Option<(&UnitJson, usize)> = None;
+  for unit in candidates {
+    if unit.target.src_path == source_path {
+      return Ok(unit);
+    }
+    if let Some(dir) = unit.target.src_path.parent() {
+      let depth = dir.components().count();
+      if source_path.starts_with(dir) && best.map_or(true, |(_, best)| depth > best) {
+        best = Some((unit, depth));
+      }
+    }
+  }
+  match best {
+    Some((unit, _)) => Ok(unit),
+    None => bail!("No unit of the unit graph contains {}", source_path.display()),
+  }
+}
+
+// The file dependents extern: an `.rmeta` for check builds, else the `.rlib` or, for
+// proc-macros, the dynamic library.
+fn extern_path(filenames: &[PathBuf], check: bool) -> Option<&PathBuf> {
+  let extension = |path: &PathBuf| {
+    path
+      .extension()
+      .and_then(|extension| extension.to_str())
+      .map(str::to_string)
+  };
+  let preference: &[&str] = if check {
+    &["rmeta", "rlib", "so", "dylib", "dll"]
+  } else {
+    &["rlib", "so", "dylib", "dll", "rmeta"]
+  };
+  preference.iter().find_map(|wanted| {
+    filenames
+      .iter()
+      .find(|path| extension(path).as_deref() == Some(*wanted))
+  })
+}
+
+// Cargo names a unit's outputs `lib<crate>-<metadata>.<ext>` (or `<crate>-<metadata>`),
+// where the hash is the unit's `-C metadata`.
+fn metadata_hash(path: &Path, crate_name: &str) -> Option<String> {
+  let stem = path.file_stem()?.to_str()?;
+  let stem = stem.strip_prefix("lib").unwrap_or(stem);
+  let hash = stem.strip_prefix(crate_name)?.strip_prefix('-')?;
+  Some(hash.to_string())
+}
+
+fn codegen_flags(profile: &ProfileJson) -> Vec<RustcFlag> {
+  let on_off = |enabled: bool| if enabled { "on" } else { "off" };
+  let mut flags = Vec::new();
+  if profile.opt_level != "0" {
+    flags.push(RustcFlag::codegen("opt-level", profile.opt_level.clone()));
+  }
+  if profile.panic != "unwind" {
+    flags.push(RustcFlag::codegen("panic", profile.panic.clone()));
+  }
+  match profile.lto.as_str() {
+    "false" | "off" => {}
+    "true" => flags.push(RustcFlag::codegen("lto", "fat")),
+    lto => flags.push(RustcFlag::codegen("lto", lto)),
+  }
+  if let Some(codegen_units) = profile.codegen_units {
+    flags.push(RustcFlag::codegen("codegen-units", codegen_units.to_string()));
+  }
+  if let Some(debuginfo) = profile.debuginfo.filter(|debuginfo| *debuginfo > 0) {
+    flags.push(RustcFlag::codegen("debuginfo", debuginfo.to_string()));
+  }
+  // As in cargo, only when they differ from rustc's defaults for the opt level.
+  if profile.debug_assertions != (profile.opt_level == "0") {
+    flags.push(RustcFlag::codegen(
+      "debug-assertions",
+      on_off(profile.debug_assertions),
+    ));
+  }
+  if profile.overflow_checks != profile.debug_assertions {
+    flags.push(RustcFlag::codegen(
+      "overflow-checks",
+      on_off(profile.overflow_checks),
+    ));
+  }
+  flags
+}
+
+fn package_env(package: &PackageJson) -> Result<PackageEnv> {
+  let version = semver::Version::parse(&package.version)
+    .with_context(|| format!("Invalid version of {}", package.name))?;
+  Ok(PackageEnv {
+    name: package.name.clone(),
+    version: package.version.clone(),
+    version_major: version.major,
+    version_minor: version.minor,
+    version_patch: version.patch,
+    version_pre: version.pre.as_str().to_string(),
+    version_build: version.build.as_str().to_string(),
+    authors: package.authors.clone(),
+    description: package.description.clone(),
+    homepage: package.homepage.clone(),
+    repository: package.repository.clone(),
+    license: package.license.clone(),
+    license_file: package.license_file.clone(),
+    readme: package.readme.clone(),
+    rust_version: package.rust_version.clone(),
+    manifest_dir: package
+      .manifest_path
+      .parent()
+      .unwrap_or_else(|| Path::new(""))
+      .to_path_buf(),
+    links: package.links.clone(),
+    extra: BTreeMap::new(),
+  })
+}
+
+/// Like [`crate::generate`], but from the output of the `cargo` binary on `PATH` (or
+/// `CARGO`). See the [module docs](self) for what's covered.
+pub fn generate(source_path: impl AsRef<Path>, options: &FlagsOptions) -> Result<RustcFlags> {
+  let source_path = &crate::canonical_source_path(source_path.as_ref(), &options.file_overrides)?;
+  let manifest_path = crate::manifest_path_for(options, source_path)?;
+  let sysroot = crate::resolve_sysroot(options, source_path)?;
+  let check = matches!(options.mode, CompileMode::Check { .. });
+  let subcommand = if check { "check" } else { "build" };
+
+  let mut metadata_command = cargo_command("metadata", &manifest_path, options);
+  metadata_command.args(&["--format-version", "1"]);
+  let metadata: MetadataJson = serde_json::from_slice(&run(metadata_command)?)
+    .context("Could not parse the output of `cargo metadata`")?;
+
+  let mut graph_command = cargo_command(subcommand, &manifest_path, options);
+  graph_command
+    .args(&["--unit-graph", "-Z", "unstable-options"])
+    .env("RUSTC_BOOTSTRAP", "1");
+  let graph: UnitGraphJson = serde_json::from_slice(&run(graph_command)?)
+    .context("Could not parse the unit graph")?;
+  let unit = find_unit(&graph, source_path)?;
+
+  // Errors in the crate itself don't matter, only that its dependencies were built, so
+  // the exit status is only reported when something turns out to be missing.
+  let mut build_command = cargo_command(subcommand, &manifest_path, options);
+  build_command.arg("--message-format=json");
+  let build_output = build_command
+    .output()
+    .with_context(|| format!("Could not run {:?}", build_command))?;
+  let mut artifacts = Vec::new();
+  let mut scripts = HashMap::new();
+  for line in build_output.stdout.split(|b| *b == b'\n') {
+    match serde_json::from_slice::<MessageJson>(line) {
+      Ok(MessageJson::CompilerArtifact(artifact)) => artifacts.push(artifact),
+      // With separate host and target runs, the first is as good as any.
+      Ok(MessageJson::BuildScriptExecuted(script)) => {
+        scripts.entry(script.package_id.clone()).or_insert(script);
+      }
+      _ => {}
+    }
+  }
+  let find_artifact = |unit: &UnitJson| {
+    artifacts
+      .iter()
+      .find(|artifact| artifact.package_id == unit.pkg_id && artifact.target == unit.target)
+      .map(|artifact| artifact.filenames.as_slice())
+  };
+  let missing = |what: String| -> anyhow::Error {
+    anyhow::anyhow!(
+      "Cargo didn't build {}:\n{}",
+      what,
+      String::from_utf8_lossy(&build_output.stderr).trim_end()
+    )
+  };
+
+  let name = crate_name(&unit.target);
+  let mut flags = vec![RustcFlag::CrateName(name.clone())];
+  flags.push(RustcFlag::Edition(unit.target.edition.clone()));
+  flags.push(RustcFlag::Input(unit.target.src_path.clone()));
+  if unit.mode == "test" {
+    flags.push(RustcFlag::Raw("--test".into()));
+  } else {
+    for crate_type in &unit.target.crate_types {
+      flags.push(RustcFlag::CrateType(CrateType::from(crate_type)));
+    }
+  }
+  let emit = if check { "metadata" } else { "link" };
+  flags.push(RustcFlag::Emit(vec!["dep-info".into(), emit.into()]));
+  flags.extend(codegen_flags(&unit.profile));
+
+  let own_output = find_artifact(unit).and_then(|filenames| filenames.first());
+  if let Some(output) = own_output {
+    if let Some(hash) = metadata_hash(output, &name) {
+      flags.push(RustcFlag::codegen("metadata", hash));
+    }
+    if let Some(dir) = output.parent() {
+      flags.push(RustcFlag::Raw("--out-dir".into()));
+      flags.push(RustcFlag::Raw(format!("{}", dir.display())));
+    }
+  }
+
+  let mut features = unit.features.clone();
+  features.sort();
+  for feature in features {
+    flags.push(RustcFlag::Cfg(format!("feature=\"{}\"", feature)));
+  }
+  if let Some(platform) = &unit.platform {
+    flags.push(RustcFlag::Target(platform.clone()));
+  }
+  flags.push(RustcFlag::Sysroot(sysroot.clone()));
+  flags.extend(crate::sysroot_remap_flags(options, &sysroot));
+
+  let mut search_dirs = Vec::new();
+  let mut externs = Vec::new();
+  let mut extern_flags = Vec::new();
+  for dep in &unit.dependencies {
+    let dep_unit = &graph.units[dep.index];
+    if dep_unit.mode == "run-custom-build" {
+      continue;
+    }
+    let filenames = find_artifact(dep_unit)
+      .ok_or_else(|| missing(format!("dependency `{}`", dep.extern_crate_name)))?;
+    let path = extern_path(filenames, check).cloned();
+    if let Some(dir) = path.as_ref().and_then(|path| path.parent()) {
+      if !search_dirs.contains(&dir.to_path_buf()) {
+        search_dirs.push(dir.to_path_buf());
+      }
+    }
+    let dep_name = crate_name(&dep_unit.target);
+    externs.push(ExternDep {
+      rename: Some(dep.extern_crate_name.clone()).filter(|rename| *rename != dep_name),
+      name: dep_name,
+      rmeta_path: path.clone(),
+      is_proc_macro: dep_unit.target.kind.iter().any(|kind| kind == "proc-macro"),
+      pkg_id: dep_unit.pkg_id.clone(),
+    });
+    extern_flags.push(RustcFlag::Extern {
+      name: dep.extern_crate_name.clone(),
+      path,
+      modifiers: Vec::new(),
+    });
+  }
+  for dir in search_dirs {
+    flags.push(RustcFlag::LibSearch {
+      kind: Some("dependency".into()),
+      path: dir,
+    });
+  }
+  flags.extend(extern_flags);
+
+  let package = metadata
+    .packages
+    .iter()
+    .find(|package| package.id == unit.pkg_id)
+    .with_context(|| format!("`cargo metadata` has no package {}", unit.pkg_id))?;
+  let package_env = package_env(package)?;
+  let mut env = package_env.vars();
+  env.insert("CARGO_CRATE_NAME".into(), name);
+  env.insert("CARGO".into(), format!("{}", cargo_path().display()));
+  if unit.target.kind.iter().any(|kind| kind == "bin") {
+    env.insert("CARGO_BIN_NAME".into(), unit.target.name.clone());
+  }
+  if graph.roots.iter().any(|index| graph.units[*index].pkg_id == unit.pkg_id) {
+    env.insert("CARGO_PRIMARY_PACKAGE".into(), "1".into());
+  }
+
+  let has_build_script = unit
+    .dependencies
+    .iter()
+    .any(|dep| graph.units[dep.index].mode == "run-custom-build");
+  if has_build_script {
+    let script = scripts
+      .get(&unit.pkg_id)
+      .ok_or_else(|| missing(format!("the build script of {}", package.name)))?;
+    for cfg in &script.cfgs {
+      flags.push(RustcFlag::Cfg(cfg.clone()));
+    }
+    for path in &script.linked_paths {
+      flags.extend(RustcFlag::parse_args(vec!["-L".into(), path.into()]));
+    }
+    for lib in &script.linked_libs {
+      flags.push(RustcFlag::Raw("-l".into()));
+      flags.push(RustcFlag::Raw(lib.clone()));
+    }
+    env.extend(script.env.iter().cloned());
+    env.insert("OUT_DIR".into(), format!("{}", script.out_dir.display()));
+  }
+  flags.extend(crate::message_format_flags(options));
+  flags.extend(options.extra_args.iter().cloned().map(RustcFlag::Raw));
+
+  let profile = &unit.profile;
+  Ok(RustcFlags {
+    schema_version: RustcFlags::SCHEMA_VERSION,
+    flags,
+    crate_root: unit.target.src_path.clone(),
+    env,
+    origin: FlagsOrigin::Cargo,
+    package_id: Some(unit.pkg_id.clone()),
+    profile: Some(ProfileInfo {
+      name: profile.name.clone(),
+      opt_level: profile.opt_level.clone(),
+      debuginfo: profile.debuginfo,
+      lto: profile.lto.clone(),
+      codegen_units: profile.codegen_units,
+      debug_assertions: profile.debug_assertions,
+      overflow_checks: profile.overflow_checks,
+      panic: profile.panic.clone(),
+      incremental: profile.incremental,
+    }),
+    externs,
+    rustc: toolchain::rustc_path(),
+    wrappers: env::var_os("RUSTC_WRAPPER")
+      .filter(|wrapper| !wrapper.is_empty())
+      .map(PathBuf::from)
+      .into_iter()
+      .collect(),
+    inputs: None,
+    file_overrides: crate::crate_file_overrides(options, &unit.target.src_path),
+    package_env: Some(package_env),
+    timings: None,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn profile(opt_level: &str, debug_assertions: bool, overflow_checks: bool) -> ProfileJson {
+    ProfileJson {
+      name: "dev".into(),
+      opt_level: opt_level.into(),
+      lto: "false".into(),
+      codegen_units: None,
+      debuginfo: None,
+      debug_assertions,
+      overflow_checks,
+      panic: "unwind".into(),
+      incremental: false,
+    }
+  }
+
+  #[test]
+  fn metadata_hashes_come_from_the_file_name() {
+    let hash = |path: &str, name: &str| metadata_hash(Path::new(path), name);
+    assert_eq!(hash("deps/libfoo_bar-0123abcd.rmeta", "foo_bar").as_deref(), Some("0123abcd"));
+    assert_eq!(hash("deps/app-0123abcd", "app").as_deref(), Some("0123abcd"));
+    assert_eq!(hash("deps/libfoo.rlib", "foo"), None);
+    assert_eq!(hash("deps/libother-0123abcd.rlib", "foo"), None);
+  }
+
+  #[test]
+  fn externs_prefer_metadata_only_for_checks() {
+    let filenames = [PathBuf::from("libfoo.rlib"), PathBuf::from("libfoo.rmeta")];
+    assert_eq!(extern_path(&filenames, true), Some(&filenames[1]));
+    assert_eq!(extern_path(&filenames, false), Some(&filenames[0]));
+    let proc_macro = [PathBuf::from("libderive.so")];
+    assert_eq!(extern_path(&proc_macro, true), Some(&proc_macro[0]));
+    assert_eq!(extern_path(&[PathBuf::from("foo.d")], true), None);
+  }
+
+  #[test]
+  fn codegen_flags_differ_from_rustcs_defaults() {
+    let render = |profile: &ProfileJson| crate::flags::render(&codegen_flags(profile));
+    assert_eq!(render(&profile("0", true, true)), Vec::<String>::new());
+    assert_eq!(render(&profile("3", false, false)), ["-C", "opt-level=3"]);
+    assert_eq!(
+      render(&profile("0", false, true)),
+      ["-C", "debug-assertions=off", "-C", "overflow-checks=on"]
+    );
+
+    let mut release = profile("3", false, false);
+    release.lto = "true".into();
+    release.panic = "abort".into();
+    release.codegen_units = Some(1);
+    assert_eq!(
+      render(&release),
+      ["-C", "opt-level=3", "-C", "panic=abort", "-C", "lto=fat", "-C", "codegen-units=1"]
+    );
+  }
+
+  #[test]
+  fn units_are_found_by_their_innermost_root() {
+    let graph: UnitGraphJson = serde_json::from_str(
+      r#"{
+        "roots": [1],
+        "units": [
+          {"pkg_id": "dep", "target": {"kind": ["lib"], "crate_types": ["lib"], "name": "dep",
+            "src_path": "/ws/dep/src/lib.rs", "edition": "2018"}, "profile": {"name": "dev",
+            "opt_level": "0", "lto": "false", "codegen_units": null, "debuginfo": 2,
+            "debug_assertions": true, "overflow_checks": true, "panic": "unwind",
+            "incremental": false}, "platform": null, "mode": "check", "features": [],
+            "dependencies": []},
+          {"pkg_id": "app", "target": {"kind": ["lib"], "crate_types": ["lib"], "name": "app",
+            "src_path": "/ws/src/lib.rs", "edition": "2018"}, "profile": {"name": "dev",
+            "opt_level": "0", "lto": "false", "codegen_units": null, "debuginfo": 2,
+            "debug_assertions": true, "overflow_checks": true, "panic": "unwind",
+            "incremental": false}, "platform": null, "mode": "check", "features": [],
+            "dependencies": [{"index": 0, "extern_crate_name": "dep"}]}
+        ]
+      }"#,
+    )
+    .unwrap();
+    let pkg_id = |path: &str| find_unit(&graph, Path::new(path)).unwrap().pkg_id.clone();
+    assert_eq!(pkg_id("/ws/src/lib.rs"), "app");
+    assert_eq!(pkg_id("/ws/src/util.rs"), "app");
+    assert_eq!(pkg_id("/ws/dep/src/util.rs"), "dep");
+    assert!(find_unit(&graph, Path::new("/elsewhere/lib.rs")).is_err());
+  }
+}
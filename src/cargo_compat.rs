@@ -0,0 +1,371 @@
+// Calls into cargo's internal (non-`ops`) APIs go through this module: config
+// construction, context preparation, build-script compilation and extern args. These are
+// the signatures that churn between cargo releases (e.g. `Context` became `BuildRunner`
+// and `Config` became `GlobalContext`), so keeping them together keeps that work in one
+// place. Nothing here is part of the public API.
+
+use anyhow::{bail, Context as AnyhowContext, Result};
+use cargo::{
+  core::{
+    compiler::{
+      build_map, compile, custom_build::BuildOutput, extern_args as cargo_extern_args,
+      fingerprint, lto, BuildContext, BuildPlan, CompileKind, CompileMode, Context,
+      DefaultExecutor, Executor, FileFlavor, Freshness, JobQueue, Metadata, Unit,
+    },
+    shell::Verbosity,
+    Package, PackageId, Shell, SourceId, Target, Workspace,
+  },
+  util::{config::Config, toml::TomlManifest},
+};
+use cargo_util::{paths, ProcessBuilder};
+use fs2::FileExt;
+use std::cell::RefCell;
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+
+pub(crate) struct ConfigureArgs<'a> {
+  pub frozen: bool,
+  pub locked: bool,
+  pub offline: bool,
+  pub unstable_flags: &'a [String],
+  pub config_overrides: &'a [String],
+  pub target_dir: Option<PathBuf>,
+}
+
+// `cargo_home` overrides `CARGO_HOME` (and `~/.cargo`), and `shell_output` replaces
+// stderr as where cargo's messages go.
+pub(crate) fn new_config(
+  allow_nightly_features: bool,
+  cargo_home: Option<&Path>,
+  shell_output: Option<Box<dyn Write>>,
+) -> Result<Config> {
+  let mut config = match cargo_home {
+    Some(cargo_home) => {
+      Config::new(Shell::new(), env::current_dir()?, cargo_home.to_path_buf())
+    }
+    None => Config::default()?,
+  };
+  if let Some(output) = shell_output {
+    // A shell over a writer defaults to verbose.
+    let mut shell = Shell::from_write(output);
+    shell.set_verbosity(Verbosity::Normal);
+    *config.shell() = shell;
+  }
+  if allow_nightly_features {
+    config.nightly_features_allowed = true;
+  }
+  Ok(config)
+}
+
+pub(crate) fn configure(config: &mut Config, args: ConfigureArgs) -> Result<()> {
+  config.configure(
+    0,
+    false,
+    None,
+    args.frozen,
+    args.locked,
+    args.offline,
+    &args.target_dir,
+    args.unstable_flags,
+    args.config_overrides,
+  )?;
+  Ok(())
+}
+
+// The compile kinds for `--target <triple>` (or a path to a target spec JSON).
+pub(crate) fn requested_kinds(config: &Config, target: &str) -> Result<Vec<CompileKind>> {
+  CompileKind::from_requested_targets(config, &[target.to_string()])
+}
+
+pub(crate) fn cargo_version() -> String {
+  cargo::version().to_string()
+}
+
+pub(crate) fn nightly_features_allowed(config: &Config) -> bool {
+  config.nightly_features_allowed
+}
+
+// Errors if another process holds the build lock of any layout `bcx` would use. The
+// lock is released right away; cargo takes it again (and waits, if it lost a race) when
+// the layouts are created.
+fn ensure_build_dirs_unlocked(bcx: &BuildContext) -> Result<()> {
+  let target_dir = bcx.ws.target_dir().into_path_unlocked();
+  let profile_dir = bcx.profiles.get_dir_name();
+  let mut dirs = vec![target_dir.join(profile_dir.as_str())];
+  for kind in &bcx.build_config.requested_kinds {
+    if let CompileKind::Target(target) = kind {
+      dirs.push(target_dir.join(target.short_name()).join(profile_dir.as_str()));
+    }
+  }
+
+  for dir in dirs {
+    let lock_path = dir.join(".cargo-lock");
+    let file = match OpenOptions::new().read(true).write(true).open(&lock_path) {
+      Ok(file) => file,
+      // Nobody has built here yet, so nobody can be holding the lock.
+      Err(_) => continue,
+    };
+    if file.try_lock_exclusive().is_err() {
+      bail!(
+        "The build directory {} is locked by another cargo process",
+        dir.display()
+      );
+    }
+    file.unlock()?;
+  }
+  Ok(())
+}
+
+// Creates the compilation context and runs every preparation step needed before
+// querying it for unit dependencies, output paths, or build scripts. The context holds
+// the build directory locks until it's dropped.
+pub(crate) fn prepare_context<'a, 'cfg>(
+  bcx: &'a BuildContext<'a, 'cfg>,
+  wait_for_lock: bool,
+) -> Result<Context<'a, 'cfg>> {
+  if !wait_for_lock {
+    ensure_build_dirs_unlocked(bcx)?;
+  }
+  let mut cx = Context::new(bcx)?;
+  cx.lto = lto::generate(bcx)?;
+
+  // Both create directories under the target directory, which is the usual failure in
+  // sandboxes and read-only checkouts.
+  let target_dir = bcx.ws.target_dir();
+  let target_dir_context = || {
+    format!(
+      "Could not set up the target directory {}. Check that it is writable, or point \
+       CARGO_TARGET_DIR (or `build.target-dir` in .cargo/config.toml) somewhere that is",
+      target_dir.as_path_unlocked().display()
+    )
+  };
+  cx.prepare_units().with_context(target_dir_context)?;
+  cx.prepare().with_context(target_dir_context)?;
+  build_map(&mut cx)?;
+  Ok(cx)
+}
+
+// Runs rustc like `DefaultExecutor`, but keeps what it prints. When `quiet`, the output
+// only shows up in the error of a failed invocation; otherwise cargo gets it as usual.
+// Build scripts themselves don't go through an executor: cargo already captures their
+// output in `output` and `stderr` files, and includes it in its error when they fail.
+struct CapturingExecutor {
+  quiet: bool,
+}
+
+impl Executor for CapturingExecutor {
+  fn exec(
+    &self,
+    cmd: &ProcessBuilder,
+    id: PackageId,
+    target: &Target,
+    mode: CompileMode,
+    on_stdout_line: &mut dyn FnMut(&str) -> Result<()>,
+    on_stderr_line: &mut dyn FnMut(&str) -> Result<()>,
+  ) -> Result<()> {
+    // Both callbacks append to it.
+    let output = RefCell::new(Vec::new());
+    let quiet = self.quiet;
+    let result = DefaultExecutor.exec(
+      cmd,
+      id,
+      target,
+      mode,
+      &mut |line| {
+        output.borrow_mut().push(line.to_string());
+        if quiet {
+          Ok(())
+        } else {
+          on_stdout_line(line)
+        }
+      },
+      &mut |line| {
+        // Cargo waits on the `artifact` notifications to start pipelined dependents.
+        let artifact = line.starts_with("{\"artifact\"");
+        if !artifact {
+          let line = rendered_diagnostic(line).unwrap_or_else(|| line.to_string());
+          output.borrow_mut().push(line);
+        }
+        if quiet && !artifact {
+          Ok(())
+        } else {
+          on_stderr_line(line)
+        }
+      },
+    );
+    result.with_context(|| {
+      format!(
+        "Output of compiling {} `{}` of {}:\n{}",
+        target.kind().description(),
+        target.name(),
+        id,
+        output.borrow().join("\n").trim_end()
+      )
+    })
+  }
+}
+
+// The `rendered` text of a diagnostic that rustc printed as JSON (cargo always asks for
+// `--error-format=json`), or `None` for other lines.
+fn rendered_diagnostic(line: &str) -> Option<String> {
+  if !line.starts_with('{') {
+    return None;
+  }
+  let start = line.find("\"rendered\":\"")? + "\"rendered\":\"".len();
+  let mut rendered = String::new();
+  let mut chars = line[start..].chars();
+  loop {
+    match chars.next()? {
+      '"' => return Some(rendered.trim_end().to_string()),
+      '\\' => match chars.next()? {
+        'n' => rendered.push('\n'),
+        't' => rendered.push('\t'),
+        'r' => rendered.push('\r'),
+        'u' => {
+          let code = chars.by_ref().take(4).collect::<String>();
+          rendered.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+        }
+        c => rendered.push(c),
+      },
+      c => rendered.push(c),
+    }
+  }
+}
+
+// Compiles (and for build script runs, runs) `units` and everything they depend on,
+// sharing one job queue. See `CapturingExecutor` for `quiet`.
+pub(crate) fn compile_units(cx: &mut Context, units: &[Unit], quiet: bool) -> Result<()> {
+  let mut queue = JobQueue::new(cx.bcx);
+  let mut plan = BuildPlan::new();
+  let exec = Arc::new(CapturingExecutor { quiet }) as Arc<dyn Executor>;
+  for unit in units {
+    compile(cx, &mut queue, &mut plan, unit, &exec, false)?;
+  }
+  queue.execute(cx, &mut plan)?;
+  Ok(())
+}
+
+// Loads the recorded output of the build script that `run_unit` runs into
+// `cx.build_script_outputs`, as cargo does for a fresh unit, if its fingerprint says it's
+// up to date (e.g. because `cargo check` ran it with the same inputs). Returns false if
+// the script has to be (compiled and) run instead.
+pub(crate) fn load_fresh_build_script(
+  cx: &mut Context,
+  run_unit: &Unit,
+  meta: Metadata,
+) -> Result<bool> {
+  let run_dir = cx.files().build_script_run_dir(run_unit);
+  let output_file = run_dir.join("output");
+  if !output_file.exists() {
+    return Ok(false);
+  }
+  let job = fingerprint::prepare_target(cx, run_unit, false)?;
+  if !matches!(job.freshness(), Freshness::Fresh) {
+    return Ok(false);
+  }
+
+  // Paths in the output are rewritten from the `OUT_DIR` the script ran with, in case
+  // the target directory moved since.
+  let out_dir = cx.files().build_script_out_dir(run_unit);
+  let prev_out_dir = fs::read(run_dir.join("root-output"))
+    .ok()
+    .and_then(|bytes| paths::bytes2path(&bytes).ok())
+    .unwrap_or_else(|| out_dir.clone());
+  let output = BuildOutput::parse_file(
+    &output_file,
+    run_unit.pkg.library().map(|lib| lib.crate_name()),
+    &run_unit.pkg.to_string(),
+    &prev_out_dir,
+    &out_dir,
+    cx.bcx.config.nightly_features_allowed,
+  )?;
+  cx.build_script_outputs
+    .lock()
+    .unwrap()
+    .insert(run_unit.pkg.package_id(), meta, output);
+  Ok(true)
+}
+
+// The `-C` options for the LTO mode cargo picked for `unit` over the whole unit graph, as
+// cargo's `lto_args` passes them.
+pub(crate) fn lto_codegen_options(cx: &Context, unit: &Unit) -> Vec<String> {
+  let options: &[&str] = match &cx.lto[unit] {
+    lto::Lto::Run(None) => &["lto"],
+    lto::Lto::Run(Some(name)) => return vec![format!("lto={}", name)],
+    lto::Lto::Off => &["lto=off", "embed-bitcode=no"],
+    lto::Lto::ObjectAndBitcode => &[],
+    lto::Lto::OnlyBitcode => &["linker-plugin-lto"],
+    lto::Lto::OnlyObject => &["embed-bitcode=no"],
+  };
+  options.iter().map(|option| option.to_string()).collect()
+}
+
+// Returns the `--extern` arguments for `unit`, and whether any of them use modifiers
+// (like `noprelude:`) that require `-Z unstable-options`.
+pub(crate) fn extern_args(cx: &Context, unit: &Unit) -> Result<(Vec<OsString>, bool)> {
+  let mut unstable_opts = false;
+  let args = cargo_extern_args(cx, unit, &mut unstable_opts)?;
+  Ok((args, unstable_opts))
+}
+
+// The artifact other crates link `unit` through (the `.rlib`, or the `.rmeta` for check
+// builds), as `extern_args` would pass it.
+pub(crate) fn linkable_output(cx: &Context, unit: &Unit) -> Result<Option<PathBuf>> {
+  let outputs = cx.outputs(unit)?;
+  let output = outputs
+    .iter()
+    .find(|output| output.flavor == FileFlavor::Linkable)
+    .or_else(|| outputs.iter().find(|output| output.flavor == FileFlavor::Rmeta));
+  Ok(output.map(|output| output.path.clone()))
+}
+
+// With `-Z build-std`, the sysroot cargo assembles for `kind` from the std crates it
+// builds, which target units are compiled against.
+pub(crate) fn build_std_sysroot(cx: &Context, kind: CompileKind) -> Option<PathBuf> {
+  cx.files().layout(kind).sysroot().map(Path::to_path_buf)
+}
+
+// The `.d` file rustc writes next to `unit`'s artifacts, which exists once cargo has
+// compiled the unit.
+pub(crate) fn dep_info_path(cx: &Context, unit: &Unit) -> PathBuf {
+  cx.files()
+    .out_dir(unit)
+    .join(format!("{}.d", cx.files().file_stem(unit)))
+}
+
+// Builds a single-package workspace from manifest contents held in memory, rather than
+// from the file at `manifest_path`.
+pub(crate) fn ephemeral_workspace<'cfg>(
+  manifest_path: &Path,
+  contents: &str,
+  config: &'cfg Config,
+) -> Result<Workspace<'cfg>> {
+  let package_root = match manifest_path.parent() {
+    Some(package_root) => package_root,
+    None => bail!("{} is not a manifest file", manifest_path.display()),
+  };
+  let toml_manifest: TomlManifest = toml::from_str(contents)?;
+  let source_id = SourceId::for_path(package_root)?;
+  let (manifest, _) =
+    TomlManifest::to_real_manifest(&Rc::new(toml_manifest), source_id, package_root, config)?;
+  let package = Package::new(manifest, manifest_path);
+  Ok(Workspace::ephemeral(package, config, None, true)?)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn renders_json_diagnostics() {
+    let line = r#"{"message":"x","rendered":"error: \"x\"\n  --> a.rs\té\n"}"#;
+    assert_eq!(rendered_diagnostic(line).unwrap(), "error: \"x\"\n  --> a.rs\té");
+    assert_eq!(rendered_diagnostic("warning: plain text"), None);
+    assert_eq!(rendered_diagnostic(r#"{"artifact":"/a.rmeta"}"#), None);
+  }
+}
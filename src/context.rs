@@ -0,0 +1,119 @@
+// A long-lived handle for generating flags repeatedly, e.g. from an editor.
+
+use crate::{
+  canonical_source_path, fallback, generate_in_workspace, load_workspace, make_config,
+  manifest_path_for, resolve_sysroot, use_fallback, validate_output_paths, FlagsOptions,
+  RustcFlags,
+};
+use anyhow::Result;
+use cargo::util::config::Config;
+use std::{
+  collections::HashMap,
+  env, fs,
+  path::{Path, PathBuf},
+  time::SystemTime,
+};
+
+/// Generates flags like [`crate::generate`], but keeps the cargo `Config` and sysroot
+/// across calls and remembers the flags generated for each source file.
+///
+/// Remembered flags are dropped when a manifest or lockfile of a workspace they came from
+/// changes, or on [`FlagsContext::invalidate`]. Changes that cargo can't see through
+/// those files, like a build script's inputs or `.cargo/config.toml`, need an explicit
+/// `invalidate`. The workspace and unit graph borrow the `Config`, so they are still
+/// rebuilt for each file that isn't remembered.
+pub struct FlagsContext {
+  options: FlagsOptions,
+  config: Config,
+  sysroot: PathBuf,
+  flags: HashMap<PathBuf, RustcFlags>,
+  // The files the remembered flags depend on, with their modification times when the
+  // flags were generated.
+  stamps: HashMap<PathBuf, Option<SystemTime>>,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+  fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+impl FlagsContext {
+  pub fn new(options: FlagsOptions) -> Result<Self> {
+    validate_output_paths(&options)?;
+    // Every workspace is assumed to use the same toolchain, the one of the manifest's
+    // directory if there is one.
+    let near = match &options.manifest_path {
+      Some(manifest_path) => manifest_path.clone(),
+      None => env::current_dir()?,
+    };
+    let sysroot = resolve_sysroot(&options, &near)?;
+    let config = make_config(&options)?;
+    Ok(FlagsContext {
+      options,
+      config,
+      sysroot,
+      flags: HashMap::new(),
+      stamps: HashMap::new(),
+    })
+  }
+
+  pub fn options(&self) -> &FlagsOptions {
+    &self.options
+  }
+
+  /// Forgets every remembered flag set and reloads the cargo `Config`.
+  pub fn invalidate(&mut self) -> Result<()> {
+    self.flags.clear();
+    self.stamps.clear();
+    self.config = make_config(&self.options)?;
+    Ok(())
+  }
+
+  fn is_stale(&self) -> bool {
+    self
+      .stamps
+      .iter()
+      .any(|(path, stamp)| modified(path) != *stamp)
+  }
+
+  pub fn generate(&mut self, source_path: impl AsRef<Path>) -> Result<RustcFlags> {
+    let source_path = canonical_source_path(source_path.as_ref(), &self.options.file_overrides)?;
+    if self.is_stale() {
+      self.invalidate()?;
+    }
+    if let Some(flags) = self.flags.get(&source_path) {
+      return Ok(flags.clone());
+    }
+
+    let flags = if use_fallback(&self.options, &source_path) {
+      fallback::generate(&source_path, &self.options, &self.sysroot)?
+    } else {
+      let manifest_path = manifest_path_for(&self.options, &source_path)?;
+      let workspace = load_workspace(&self.options, &manifest_path, &self.config)?;
+      let flags = generate_in_workspace(
+        &workspace,
+        &source_path,
+        &self.options,
+        &self.options.features,
+        self.sysroot.clone(),
+      )?;
+
+      let lockfile = workspace.root().join("Cargo.lock");
+      let watched = workspace
+        .members()
+        .map(|member| member.manifest_path().to_path_buf())
+        .chain(vec![
+          manifest_path,
+          workspace.root_manifest().to_path_buf(),
+          lockfile,
+        ]);
+      for path in watched {
+        let stamp = modified(&path);
+        self.stamps.entry(path).or_insert(stamp);
+      }
+      flags
+    };
+
+    self.flags.insert(source_path, flags.clone());
+    Ok(flags)
+  }
+}
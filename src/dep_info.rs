@@ -0,0 +1,114 @@
+// Parsing the Makefile-style `.d` files written by `--emit=dep-info`.
+
+use anyhow::{Context as AnyhowContext, Result};
+use std::{fs, path::Path, path::PathBuf};
+
+// Splits a rule's prerequisites on whitespace, honoring `\ ` escapes for spaces in paths.
+fn split_paths(prerequisites: &str) -> Vec<String> {
+  let mut paths = Vec::new();
+  let mut current = String::new();
+  let mut chars = prerequisites.chars().peekable();
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' if chars.peek() == Some(&' ') => current.push(chars.next().unwrap()),
+      c if c.is_whitespace() => {
+        if !current.is_empty() {
+          paths.push(std::mem::take(&mut current));
+        }
+      }
+      c => current.push(c),
+    }
+  }
+  if !current.is_empty() {
+    paths.push(current);
+  }
+  paths
+}
+
+// The variables listed in the `# env-dep:NAME[=VALUE]` comments of a dep-info file.
+pub(crate) fn parse_env_deps(path: &Path) -> Result<Vec<String>> {
+  let contents =
+    fs::read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?;
+  Ok(
+    contents
+      .lines()
+      .filter_map(|line| line.strip_prefix("# env-dep:"))
+      .map(|dep| dep.split('=').next().unwrap().to_string())
+      .collect(),
+  )
+}
+
+/// Reads a dep-info file produced by rustc and returns every source file it lists, in
+/// order and without duplicates.
+pub fn parse_dep_info(path: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+  let path = path.as_ref();
+  let contents =
+    fs::read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?;
+
+  let mut files = Vec::new();
+  for line in contents.lines() {
+    // rustc also writes `# env-dep:` comments, which aren't files.
+    if line.starts_with('#') {
+      continue;
+    }
+    // The target may itself be a Windows path like `C:\...`, so split on `": "`.
+    let prerequisites = match line.split_once(": ") {
+      Some((_, prerequisites)) => prerequisites,
+      None => continue,
+    };
+    for file in split_paths(prerequisites) {
+      let file = PathBuf::from(file);
+      if !files.contains(&file) {
+        files.push(file);
+      }
+    }
+  }
+  Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Writes `contents` to a dep-info file of its own under the system temp directory.
+  fn dep_file(name: &str, contents: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("dep-info-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    fs::write(&path, contents).unwrap();
+    path
+  }
+
+  #[test]
+  fn splits_on_unescaped_whitespace() {
+    assert_eq!(
+      split_paths(" src/lib.rs  src/my\\ mod.rs\tsrc/a\\b.rs "),
+      vec!["src/lib.rs", "src/my mod.rs", "src/a\\b.rs"]
+    );
+  }
+
+  #[test]
+  fn lists_each_file_once() {
+    let path = dep_file(
+      "files.d",
+      "C:\\target\\foo.rmeta: src/lib.rs src/util.rs\n\
+       C:\\target\\foo.d: src/lib.rs src/util.rs\n\
+       \n\
+       src/lib.rs:\n\
+       # env-dep:CARGO_PKG_NAME=foo\n",
+    );
+    assert_eq!(
+      parse_dep_info(path).unwrap(),
+      vec![PathBuf::from("src/lib.rs"), PathBuf::from("src/util.rs")]
+    );
+  }
+
+  #[test]
+  fn lists_env_deps_without_values() {
+    let path = dep_file(
+      "env.d",
+      "foo.d: src/lib.rs\n# env-dep:CARGO_PKG_NAME=foo\n# env-dep:MY_UNSET_VAR\n",
+    );
+    assert_eq!(parse_env_deps(&path).unwrap(), vec!["CARGO_PKG_NAME", "MY_UNSET_VAR"]);
+  }
+}
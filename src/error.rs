@@ -0,0 +1,73 @@
+// Errors callers may want to recover from. They travel inside `anyhow::Error`, either as
+// the error itself or as context on cargo's error, so callers find them with
+// `err.downcast_ref::<GenerateFlagsError>()`.
+
+use std::{fmt, path::PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum GenerateFlagsError {
+  /// No unit compiles the file. `package` is the package ID spec the search was
+  /// restricted to, and `hint` explains why the file may be missing from the package.
+  UnitNotFound {
+    path: PathBuf,
+    package: Option<String>,
+    hint: Option<String>,
+  },
+  /// Several targets compile the file and [`crate::FlagsOptions::target_priority`] prefers
+  /// none; retry with [`crate::FlagsOptions::target_selector`] set to one of `candidates`,
+  /// which are described like ``bin `foo` ``, or use [`crate::generate_candidates`].
+  AmbiguousUnit {
+    path: PathBuf,
+    candidates: Vec<String>,
+  },
+  /// The file isn't part of the target picked with `target_selector`.
+  NotInSelectedTarget { path: PathBuf, selector: String },
+  /// Compiling or running the package's build script failed. The error's source is
+  /// cargo's, including the script's output.
+  BuildScriptFailed { package: String },
+  /// Cargo couldn't load the workspace, e.g. because of an invalid manifest. The error's
+  /// source is cargo's.
+  WorkspaceLoad { manifest_path: PathBuf },
+}
+
+impl fmt::Display for GenerateFlagsError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      GenerateFlagsError::UnitNotFound {
+        path,
+        package,
+        hint,
+      } => {
+        write!(f, "Could not find unit for path {}", path.display())?;
+        if let Some(package) = package {
+          write!(f, " in package {}", package)?;
+        }
+        if let Some(hint) = hint {
+          write!(f, ": {}", hint)?;
+        }
+        Ok(())
+      }
+      GenerateFlagsError::AmbiguousUnit { path, candidates } => write!(
+        f,
+        "{} belongs to several targets ({}); pick one with `FlagsOptions::target_selector`",
+        path.display(),
+        candidates.join(", ")
+      ),
+      GenerateFlagsError::NotInSelectedTarget { path, selector } => write!(
+        f,
+        "{} is not part of the selected target {}",
+        path.display(),
+        selector
+      ),
+      GenerateFlagsError::BuildScriptFailed { package } => {
+        write!(f, "The build script for {} failed", package)
+      }
+      GenerateFlagsError::WorkspaceLoad { manifest_path } => {
+        write!(f, "Could not load the workspace at {}", manifest_path.display())
+      }
+    }
+  }
+}
+
+impl std::error::Error for GenerateFlagsError {}
@@ -0,0 +1,148 @@
+//! Exporting a workspace as a `rust-project.json`, which rust-analyzer reads for projects
+//! it doesn't query cargo about, e.g. ones built by a custom driver.
+//!
+//! Every lib and proc-macro unit cargo would compile becomes a crate, dependencies
+//! included, along with the workspace's own bins, tests and so on (as `options` selects
+//! them). Build scripts are run for their environment as with [`crate::generate`].
+
+use crate::{cargo_compat, unit_flags, units, FlagsOptions, RustcFlag};
+use anyhow::{Context as AnyhowContext, Result};
+use cargo::core::compiler::{CompileKind, Unit};
+use std::{
+  collections::{BTreeMap, HashMap},
+  path::{Path, PathBuf},
+};
+
+/// The contents of a `rust-project.json`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RustProject {
+  pub sysroot: PathBuf,
+  /// The std sources under the sysroot, if the `rust-src` component is installed.
+  pub sysroot_src: Option<PathBuf>,
+  /// Dependencies come before the crates that depend on them.
+  pub crates: Vec<ProjectCrate>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ProjectCrate {
+  pub display_name: String,
+  pub root_module: PathBuf,
+  pub edition: String,
+  pub deps: Vec<ProjectDep>,
+  pub is_workspace_member: bool,
+  /// Like the values of `--cfg`, e.g. `feature="std"`.
+  pub cfg: Vec<String>,
+  /// The target triple, or `None` for the host.
+  pub target: Option<String>,
+  pub env: BTreeMap<String, String>,
+  pub is_proc_macro: bool,
+  /// The compiled proc-macro, once cargo has built it.
+  pub proc_macro_dylib_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ProjectDep {
+  /// Index of the dependency in [`RustProject::crates`].
+  #[cfg_attr(feature = "serde", serde(rename = "crate"))]
+  pub krate: usize,
+  /// The name the dependency is externed under.
+  pub name: String,
+}
+
+#[cfg(all(feature = "serde", feature = "serde_json"))]
+impl RustProject {
+  /// The project as the JSON rust-analyzer reads.
+  pub fn to_json(&self) -> String {
+    serde_json::to_string_pretty(self).unwrap()
+  }
+}
+
+/// Builds the `rust-project.json` for the workspace at `manifest_path`.
+pub fn rust_project(
+  manifest_path: impl AsRef<Path>,
+  options: &FlagsOptions,
+) -> Result<RustProject> {
+  let manifest_path = crate::canonical_manifest_path(manifest_path.as_ref())?;
+  crate::validate_output_paths(options)?;
+  let sysroot = crate::resolve_sysroot(options, &manifest_path)?;
+
+  let crates = crate::with_context(options, &manifest_path, &manifest_path, |cx, all_units| {
+    // Build scripts only contribute their environment, which is part of the crate's.
+    let crate_units = all_units
+      .iter()
+      .filter(|unit| !unit.target.is_custom_build())
+      .collect::<Vec<&Unit>>();
+    let index = crate_units
+      .iter()
+      .enumerate()
+      .map(|(i, unit)| (*unit, i))
+      .collect::<HashMap<_, _>>();
+
+    unit_flags::run_prerequisites(cx, &crate_units, all_units, options)?;
+
+    let mut crates = Vec::new();
+    for unit in &crate_units {
+      let flags = unit_flags::prepared_unit_flags(cx, unit, all_units, options, &sysroot)
+        .with_context(|| {
+          format!(
+            "Could not generate flags for {} `{}`",
+            unit.target.kind().description(),
+            unit.target.name()
+          )
+        })?;
+
+      let mut edition = unit.target.edition().to_string();
+      let mut cfg = Vec::new();
+      for flag in &flags.flags {
+        match flag {
+          RustcFlag::Edition(flag_edition) => edition = flag_edition.clone(),
+          RustcFlag::Cfg(value) => cfg.push(value.clone()),
+          _ => {}
+        }
+      }
+
+      let is_proc_macro = unit.target.proc_macro();
+      let proc_macro_dylib_path = if is_proc_macro {
+        cargo_compat::linkable_output(cx, unit)?.filter(|path| path.exists())
+      } else {
+        None
+      };
+
+      crates.push(ProjectCrate {
+        display_name: unit.target.crate_name(),
+        root_module: units::crate_root(unit)?.to_path_buf(),
+        edition,
+        deps: cx
+          .unit_deps(unit)
+          .iter()
+          .filter_map(|dep| {
+            Some(ProjectDep {
+              krate: *index.get(&dep.unit)?,
+              name: dep.extern_crate_name.to_string(),
+            })
+          })
+          .collect(),
+        is_workspace_member: cx.bcx.ws.is_member(&unit.pkg),
+        cfg,
+        target: match unit.kind {
+          CompileKind::Host => None,
+          CompileKind::Target(target) => Some(target.rustc_target().to_string()),
+        },
+        env: flags.env.into_iter().collect(),
+        is_proc_macro,
+        proc_macro_dylib_path,
+      });
+    }
+    Ok(crates)
+  })?;
+
+  let sysroot_src = sysroot.join("lib/rustlib/src/rust/library");
+  Ok(RustProject {
+    sysroot_src: Some(sysroot_src).filter(|src| src.is_dir()),
+    sysroot,
+    crates,
+  })
+}
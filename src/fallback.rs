@@ -0,0 +1,99 @@
+// Flags for a standalone `.rs` file that doesn't belong to any cargo package.
+
+use crate::{toolchain, FlagsOptions, FlagsOrigin, Incremental, RustcFlag, RustcFlags};
+use anyhow::{Context as AnyhowContext, Result};
+use cargo::core::{compiler::CrateType, Edition};
+use regex::Regex;
+use std::{
+  collections::BTreeMap,
+  env, fs,
+  path::{Path, PathBuf},
+};
+
+// Mirrors how rustc derives a crate name from a file stem: anything that isn't a valid
+// identifier character becomes `_`.
+fn sanitize_crate_name(stem: &str) -> String {
+  let mut name = stem
+    .chars()
+    .map(|c| if c.is_alphanumeric() || c == '_' { c } else { '_' })
+    .collect::<String>();
+  if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+    name.insert(0, '_');
+  }
+  name
+}
+
+pub(crate) fn generate(
+  source_path: &Path,
+  options: &FlagsOptions,
+  sysroot: &Path,
+) -> Result<RustcFlags> {
+  let contents = match crate::crate_file_overrides(options, source_path).remove(source_path) {
+    Some(contents) => contents,
+    None => fs::read_to_string(source_path)
+      .with_context(|| format!("Could not read {}", source_path.display()))?,
+  };
+
+  // A cheap check rather than a parse: good enough to tell scripts from scratch libraries.
+  // `#![no_main]` binaries have no `main` but are still executables.
+  let main_re = Regex::new(r"(?m)^\s*(pub\s+)?fn\s+main\s*\(").unwrap();
+  let no_main_re = Regex::new(r"(?m)^\s*#!\[\s*no_main\s*\]").unwrap();
+  let is_bin = main_re.is_match(&contents) || no_main_re.is_match(&contents);
+  let crate_type = if is_bin {
+    CrateType::Bin
+  } else {
+    CrateType::Lib
+  };
+
+  let stem = source_path
+    .file_stem()
+    .and_then(|stem| stem.to_str())
+    .unwrap_or("main");
+
+  let edition = options
+    .fallback_edition
+    .clone()
+    .unwrap_or_else(|| Edition::LATEST_STABLE.to_string());
+
+  let mut flags = vec![
+    RustcFlag::CrateName(sanitize_crate_name(stem)),
+    RustcFlag::CrateType(crate_type),
+    RustcFlag::Sysroot(sysroot.to_path_buf()),
+    RustcFlag::Input(source_path.to_path_buf()),
+    RustcFlag::Edition(edition),
+    crate::output_flag(options, options.mode, is_bin),
+  ];
+  if let Some(target) = &options.target {
+    flags.push(RustcFlag::Target(target.clone()));
+  }
+  if let Incremental::Dir(dir) = &options.incremental {
+    flags.push(RustcFlag::codegen("incremental", format!("{}", dir.display())));
+  }
+  flags.extend(crate::message_format_flags(options));
+  flags.extend(crate::sysroot_remap_flags(options, sysroot));
+  for (from, to) in &options.remap_path_prefixes {
+    flags.push(crate::remap_path_prefix(from, to));
+  }
+  flags.extend(options.extra_args.iter().cloned().map(RustcFlag::Raw));
+
+  Ok(RustcFlags {
+    schema_version: RustcFlags::SCHEMA_VERSION,
+    flags,
+    crate_root: source_path.to_path_buf(),
+    env: BTreeMap::new(),
+    origin: FlagsOrigin::Fallback,
+    package_id: None,
+    profile: None,
+    externs: Vec::new(),
+    rustc: toolchain::rustc_path(),
+    wrappers: env::var_os("RUSTC_WRAPPER")
+      .filter(|wrapper| !wrapper.is_empty())
+      .map(PathBuf::from)
+      .into_iter()
+      .collect(),
+    inputs: None,
+    file_overrides: crate::crate_file_overrides(options, source_path),
+    package_env: None,
+    timings: None,
+  })
+}
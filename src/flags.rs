@@ -0,0 +1,441 @@
+use cargo::core::compiler::CrateType;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LintLevel {
+  Allow,
+  Warn,
+  Deny,
+  Forbid,
+}
+
+impl LintLevel {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      LintLevel::Allow => "allow",
+      LintLevel::Warn => "warn",
+      LintLevel::Deny => "deny",
+      LintLevel::Forbid => "forbid",
+    }
+  }
+}
+
+/// A single rustc argument (or option/value pair) in typed form.
+///
+/// Every flag generated by this crate is built as a `RustcFlag` first, and the string
+/// form is only ever produced by [`RustcFlag::render`] (or [`RustcFlag::render_os`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RustcFlag {
+  CrateName(String),
+  #[cfg_attr(feature = "serde", serde(with = "crate_type_serde"))]
+  CrateType(CrateType),
+  Edition(String),
+  Cfg(String),
+  Extern {
+    name: String,
+    path: Option<PathBuf>,
+    modifiers: Vec<String>,
+  },
+  LibSearch {
+    kind: Option<String>,
+    path: PathBuf,
+  },
+  Codegen {
+    key: String,
+    value: Option<String>,
+  },
+  Unstable {
+    key: String,
+    value: Option<String>,
+  },
+  Emit(Vec<String>),
+  Lint {
+    level: LintLevel,
+    name: String,
+  },
+  Sysroot(PathBuf),
+  Target(String),
+  Input(PathBuf),
+  Raw(String),
+}
+
+// Cargo's `CrateType` only serializes, so go through its string form both ways.
+#[cfg(feature = "serde")]
+mod crate_type_serde {
+  use cargo::core::compiler::CrateType;
+  use serde::{Deserialize, Deserializer, Serializer};
+
+  pub fn serialize<S: Serializer>(ty: &CrateType, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(ty.as_str())
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<CrateType, D::Error> {
+    let ty = String::deserialize(deserializer)?;
+    Ok(CrateType::from(&ty))
+  }
+}
+
+fn key_value(key: &str, value: &Option<String>) -> String {
+  match value {
+    Some(value) => format!("{}={}", key, value),
+    None => key.to_string(),
+  }
+}
+
+impl RustcFlag {
+  pub fn render(&self) -> Vec<String> {
+    match self {
+      RustcFlag::CrateName(name) => vec!["--crate-name".into(), name.clone()],
+      RustcFlag::CrateType(ty) => vec!["--crate-type".into(), ty.as_str().to_string()],
+      RustcFlag::Edition(edition) => vec![format!("--edition={}", edition)],
+      RustcFlag::Cfg(cfg) => vec!["--cfg".into(), cfg.clone()],
+      RustcFlag::Extern {
+        name,
+        path,
+        modifiers,
+      } => {
+        let mut value = String::new();
+        if !modifiers.is_empty() {
+          value.push_str(&modifiers.join(","));
+          value.push(':');
+        }
+        value.push_str(name);
+        if let Some(path) = path {
+          value.push('=');
+          value.push_str(&format!("{}", path.display()));
+        }
+        vec!["--extern".into(), value]
+      }
+      RustcFlag::LibSearch { kind, path } => {
+        let value = match kind {
+          Some(kind) => format!("{}={}", kind, path.display()),
+          None => format!("{}", path.display()),
+        };
+        vec!["-L".into(), value]
+      }
+      RustcFlag::Codegen { key, value } => vec!["-C".into(), key_value(key, value)],
+      RustcFlag::Unstable { key, value } => vec!["-Z".into(), key_value(key, value)],
+      RustcFlag::Emit(kinds) => vec![format!("--emit={}", kinds.join(","))],
+      RustcFlag::Lint { level, name } => vec![format!("--{}={}", level.as_str(), name)],
+      RustcFlag::Sysroot(path) => vec!["--sysroot".into(), format!("{}", path.display())],
+      RustcFlag::Target(triple) => vec!["--target".into(), triple.clone()],
+      RustcFlag::Input(path) => vec![format!("{}", path.display())],
+      RustcFlag::Raw(arg) => vec![arg.clone()],
+    }
+  }
+
+  pub fn codegen(key: impl Into<String>, value: impl Into<String>) -> Self {
+    RustcFlag::Codegen {
+      key: key.into(),
+      value: Some(value.into()),
+    }
+  }
+
+  /// Like [`RustcFlag::render`], but with paths as they are rather than as UTF-8 strings,
+  /// so that paths that aren't valid UTF-8 survive.
+  pub fn render_os(&self) -> Vec<OsString> {
+    let joined = |prefix: String, path: &Path| {
+      let mut value = OsString::from(prefix);
+      value.push(path);
+      value
+    };
+    match self {
+      RustcFlag::Extern {
+        name,
+        path: Some(path),
+        modifiers,
+      } => {
+        let mut spec = String::new();
+        if !modifiers.is_empty() {
+          spec.push_str(&modifiers.join(","));
+          spec.push(':');
+        }
+        vec!["--extern".into(), joined(format!("{}{}=", spec, name), path)]
+      }
+      RustcFlag::LibSearch { kind, path } => {
+        let kind = kind.as_ref().map_or_else(String::new, |kind| format!("{}=", kind));
+        vec!["-L".into(), joined(kind, path)]
+      }
+      RustcFlag::Sysroot(path) => vec!["--sysroot".into(), path.into()],
+      RustcFlag::Input(path) => vec![path.into()],
+      _ => self.render().into_iter().map(OsString::from).collect(),
+    }
+  }
+
+  // Parses the value of an `--extern` argument, i.e. `[modifiers:]name[=path]`.
+  fn parse_extern(value: &OsStr) -> Self {
+    let (spec, path) = match split_path_arg(value) {
+      Some((spec, path)) => (spec, Some(path)),
+      None => (value.to_string_lossy().into_owned(), None),
+    };
+    let (modifiers, name) = match spec.split_once(':') {
+      Some((modifiers, name)) => (
+        modifiers.split(',').map(|s| s.to_string()).collect(),
+        name,
+      ),
+      None => (Vec::new(), spec.as_str()),
+    };
+    RustcFlag::Extern {
+      name: name.to_string(),
+      path,
+      modifiers,
+    }
+  }
+
+  /// Converts arguments produced by cargo (e.g. by `extern_args`) into typed flags.
+  /// Anything not recognized is kept as `Raw`. The paths of `--extern` and `-L` are kept
+  /// as they are; other arguments that aren't UTF-8 have their invalid parts replaced.
+  pub fn parse_args(args: impl IntoIterator<Item = OsString>) -> Vec<RustcFlag> {
+    let mut flags = Vec::new();
+    let mut iter = args.into_iter().peekable();
+    while let Some(arg) = iter.next() {
+      let flag = match arg.to_str() {
+        Some("--extern") if iter.peek().is_some() => {
+          RustcFlag::parse_extern(&iter.next().unwrap())
+        }
+        Some("-L") if iter.peek().is_some() => {
+          let value = iter.next().unwrap();
+          match split_path_arg(&value) {
+            Some((kind, path)) => RustcFlag::LibSearch {
+              kind: Some(kind),
+              path,
+            },
+            None => RustcFlag::LibSearch {
+              kind: None,
+              path: PathBuf::from(value),
+            },
+          }
+        }
+        _ => RustcFlag::Raw(arg.to_string_lossy().into_owned()),
+      };
+      flags.push(flag);
+    }
+    flags
+  }
+}
+
+// The rustc options whose value may be passed as the next argument.
+pub(crate) const TAKES_VALUE: &[&str] = &[
+  "--crate-name",
+  "--crate-type",
+  "--edition",
+  "--emit",
+  "--print",
+  "--cfg",
+  "--check-cfg",
+  "--extern",
+  "--out-dir",
+  "-o",
+  "--target",
+  "--sysroot",
+  "--cap-lints",
+  "--remap-path-prefix",
+  "--error-format",
+  "--json",
+  "--color",
+  "--explain",
+  "--force-warn",
+  "-L",
+  "-l",
+  "-C",
+  "-Z",
+  "-A",
+  "-W",
+  "-D",
+  "-F",
+];
+
+// Pairs each of the rustc arguments `args` with its value: the next argument for the
+// options in `TAKES_VALUE`, or what follows the `=` of `--option=value`. Arguments that
+// aren't options, like the crate root, have no value.
+pub(crate) fn option_values(args: &[String]) -> Vec<(&str, Option<&str>)> {
+  let mut pairs = Vec::new();
+  let mut iter = args.iter();
+  while let Some(arg) = iter.next() {
+    let pair = match arg.split_once('=') {
+      Some((option, value)) if option.starts_with("--") => (option, Some(value)),
+      _ if TAKES_VALUE.contains(&arg.as_str()) => (arg.as_str(), iter.next().map(String::as_str)),
+      _ => (arg.as_str(), None),
+    };
+    pairs.push(pair);
+  }
+  pairs
+}
+
+// Splits `arg` at its first `=` into the (UTF-8) part before it and the path after it,
+// without going through a `String`, so that the path may be any bytes.
+fn split_path_arg(arg: &OsStr) -> Option<(String, PathBuf)> {
+  #[cfg(unix)]
+  {
+    use std::os::unix::ffi::OsStrExt;
+    let bytes = arg.as_bytes();
+    let eq = bytes.iter().position(|byte| *byte == b'=')?;
+    let key = std::str::from_utf8(&bytes[..eq]).ok()?;
+    Some((key.to_string(), PathBuf::from(OsStr::from_bytes(&bytes[eq + 1..]))))
+  }
+  // Windows paths that aren't UTF-8 (i.e. with unpaired surrogates) are rare enough to
+  // take lossily.
+  #[cfg(not(unix))]
+  {
+    let arg = arg.to_string_lossy();
+    let (key, path) = arg.split_once('=')?;
+    Some((key.to_string(), PathBuf::from(path)))
+  }
+}
+
+pub fn render(flags: &[RustcFlag]) -> Vec<String> {
+  flags.iter().map(RustcFlag::render).flatten().collect()
+}
+
+pub fn render_os(flags: &[RustcFlag]) -> Vec<OsString> {
+  flags.iter().map(RustcFlag::render_os).flatten().collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn args(args: &[&str]) -> Vec<OsString> {
+    args.iter().map(OsString::from).collect()
+  }
+
+  #[test]
+  fn parses_extern_modifiers_and_paths() {
+    let flags = RustcFlag::parse_args(args(&[
+      "--extern",
+      "noprelude,nounused:std=/lib/libstd.rlib",
+      "--extern",
+      "proc_macro",
+    ]));
+    assert_eq!(
+      flags,
+      vec![
+        RustcFlag::Extern {
+          name: "std".into(),
+          path: Some("/lib/libstd.rlib".into()),
+          modifiers: vec!["noprelude".into(), "nounused".into()],
+        },
+        RustcFlag::Extern {
+          name: "proc_macro".into(),
+          path: None,
+          modifiers: Vec::new(),
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn parses_lib_search_kinds() {
+    let flags = RustcFlag::parse_args(args(&["-L", "dependency=/deps", "-L", "/native"]));
+    assert_eq!(
+      flags,
+      vec![
+        RustcFlag::LibSearch {
+          kind: Some("dependency".into()),
+          path: "/deps".into(),
+        },
+        RustcFlag::LibSearch {
+          kind: None,
+          path: "/native".into(),
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn keeps_unknown_arguments_raw() {
+    assert_eq!(
+      RustcFlag::parse_args(args(&["-Cpanic=abort", "--extern"])),
+      vec![RustcFlag::Raw("-Cpanic=abort".into()), RustcFlag::Raw("--extern".into())]
+    );
+  }
+
+  #[test]
+  fn renders_like_cargo() {
+    let flags = vec![
+      RustcFlag::CrateName("foo".into()),
+      RustcFlag::CrateType(CrateType::Lib),
+      RustcFlag::Edition("2021".into()),
+      RustcFlag::Cfg("feature=\"std\"".into()),
+      RustcFlag::Emit(vec!["dep-info".into(), "metadata".into()]),
+      RustcFlag::codegen("opt-level", "3"),
+      RustcFlag::Unstable {
+        key: "unstable-options".into(),
+        value: None,
+      },
+      RustcFlag::Lint {
+        level: LintLevel::Deny,
+        name: "unsafe_code".into(),
+      },
+      RustcFlag::Input("src/lib.rs".into()),
+    ];
+    assert_eq!(
+      render(&flags),
+      vec![
+        "--crate-name",
+        "foo",
+        "--crate-type",
+        "lib",
+        "--edition=2021",
+        "--cfg",
+        "feature=\"std\"",
+        "--emit=dep-info,metadata",
+        "-C",
+        "opt-level=3",
+        "-Z",
+        "unstable-options",
+        "--deny=unsafe_code",
+        "src/lib.rs",
+      ]
+    );
+  }
+
+  #[test]
+  fn parsing_and_rendering_round_trips() {
+    let cargo = ["--extern", "nounused:a=/a.rlib", "-L", "native=/n", "-Zfoo"];
+    let flags = RustcFlag::parse_args(args(&cargo));
+    assert_eq!(render(&flags), cargo);
+  }
+
+  #[test]
+  fn pairs_options_with_values() {
+    let args = ["--crate-name", "foo", "--edition=2018", "-C", "opt-level=1", "lib.rs", "--test"]
+      .iter()
+      .map(|arg| arg.to_string())
+      .collect::<Vec<_>>();
+    assert_eq!(
+      option_values(&args),
+      vec![
+        ("--crate-name", Some("foo")),
+        ("--edition", Some("2018")),
+        ("-C", Some("opt-level=1")),
+        ("lib.rs", None),
+        ("--test", None),
+      ]
+    );
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn non_utf8_paths_survive_parsing() {
+    use std::os::unix::ffi::OsStrExt;
+    let dir = OsStr::from_bytes(b"/deps/\xff");
+    let mut extern_arg = OsString::from("a=");
+    extern_arg.push(dir);
+    let mut lib_arg = OsString::from("dependency=");
+    lib_arg.push(dir);
+    let cargo = vec!["--extern".into(), extern_arg, "-L".into(), lib_arg];
+    let flags = RustcFlag::parse_args(cargo.clone());
+    assert_eq!(
+      flags[1],
+      RustcFlag::LibSearch {
+        kind: Some("dependency".into()),
+        path: PathBuf::from(dir),
+      }
+    );
+    assert_eq!(render_os(&flags), cargo);
+  }
+}
@@ -1,60 +1,1480 @@
 use anyhow::{bail, Context as AnyhowContext, Result};
 use cargo::{
   core::{
-    compiler::{
-      build_map, compile, extern_args, lto, BuildPlan, CompileMode, Context, CrateType,
-      DefaultExecutor, Executor, JobQueue, Unit, UnitInterner,
-    },
-    Workspace,
+    compiler::{Context, UnitInterner},
+    FeatureValue, PackageIdSpec, Target, Workspace,
   },
   ops::{create_bcx, CompileFilter, CompileOptions, FilterRule, LibRule, Packages},
-  util::config::Config,
+  util::{config::Config, interning::InternedString},
 };
 use std::env;
-use std::process::Command;
-use std::sync::Arc;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::process::{Command, Output};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 use std::{
-  collections::HashMap,
-  path::{Path},
+  collections::{BTreeMap, HashMap},
+  path::{Path, PathBuf},
 };
+use timings::Phase;
 
+pub use cargo::core::compiler::{CompileMode, Unit};
+pub use cargo::core::PackageId;
 pub use cargo::core::resolver::CliFeatures;
+pub use builder::FlagsBuilder;
+pub use context::FlagsContext;
+pub use dep_info::parse_dep_info;
+pub use error::GenerateFlagsError;
+pub use flags::{LintLevel, RustcFlag};
+pub use package_env::PackageEnv;
+pub use progress::{FlagsProgress, ProgressEvent};
+pub use timings::Timings;
+pub use toolchain::ToolchainInfo;
+pub use unit_graph::{UnitEdge, UnitGraph, UnitNode};
 
-fn collect_units(cx: &Context, unit: &Unit) -> Vec<Unit> {
-  cx.unit_deps(unit)
-    .iter()
-    .map(|dep| collect_units(cx, &dep.unit).into_iter())
-    .flatten()
-    .chain(vec![unit.clone()].into_iter())
-    .collect()
+mod artifacts;
+mod builder;
+#[cfg(feature = "cache")]
+pub mod cache;
+#[cfg(feature = "cargo-cli")]
+pub mod cargo_cli;
+mod cargo_compat;
+mod context;
+mod dep_info;
+mod error;
+pub mod export;
+mod fallback;
+mod flags;
+mod lints;
+mod manifest;
+mod package_env;
+mod progress;
+#[cfg(feature = "rust-analyzer")]
+pub mod rust_analyzer;
+mod timings;
+mod toolchain;
+mod trim_paths;
+mod unit_flags;
+mod unit_graph;
+mod units;
+pub mod verify;
+pub mod wrapper;
+
+/// Where a set of flags came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FlagsOrigin {
+  /// Computed from a cargo package, as `cargo check` would.
+  Cargo,
+  /// A minimal flag set for a file outside any cargo package: no externs, no features,
+  /// no build-script environment.
+  Fallback,
+}
+
+/// The full configuration needed to invoke rustc on a crate the way cargo would.
+///
+/// With the `serde` feature, it can be serialized (e.g. to cache it across processes),
+/// and deserializing it gives back the same [`RustcFlags::to_args`]. Serialized flags
+/// carry a `schema_version`, so that a cache can tell when they were written by a version
+/// of this crate with a different layout.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RustcFlags {
+  /// Always [`RustcFlags::SCHEMA_VERSION`] for flags generated by this version.
+  pub schema_version: u32,
+  pub flags: Vec<RustcFlag>,
+  /// The crate root, which is also in `flags` as a [`RustcFlag::Input`]. Drivers that pass
+  /// it separately (e.g. through a file loader) can leave it out of the arguments with
+  /// [`RustcFlags::to_args_without_crate_root`].
+  pub crate_root: PathBuf,
+  /// Environment variables, including those set by the build script. Build script values
+  /// that aren't UTF-8 are left out with a warning.
+  pub env: BTreeMap<String, String>,
+  pub origin: FlagsOrigin,
+  /// The package's ID as cargo prints it in JSON messages, if there is a package.
+  pub package_id: Option<String>,
+  /// The resolved profile of the unit the flags were generated for, if there is a package.
+  pub profile: Option<ProfileInfo>,
+  /// The dependencies behind the `--extern` flags, in the same order.
+  pub externs: Vec<ExternDep>,
+  /// The rustc cargo resolved: `build.rustc` in config, else `RUSTC`, else `rustc`.
+  pub rustc: PathBuf,
+  /// What cargo would run rustc through, outermost first: `RUSTC_WRAPPER` (or
+  /// `build.rustc-wrapper`) and, for workspace members, `RUSTC_WORKSPACE_WRAPPER` (or
+  /// `build.rustc-workspace-wrapper`), like sccache or clippy-driver.
+  pub wrappers: Vec<PathBuf>,
+  /// What the compilation depends on, with [`FlagsOptions::collect_inputs`].
+  pub inputs: Option<UnitInputs>,
+  /// The [`FlagsOptions::file_overrides`] below the crate root's directory, with canonical
+  /// paths. rustc itself reads the files on disk, so a driver has to serve these instead,
+  /// e.g. from a `rustc_interface` file loader.
+  pub file_overrides: BTreeMap<PathBuf, String>,
+  /// The package's part of `env`, if there is a package. See
+  /// [`RustcFlags::set_package_env`].
+  pub package_env: Option<PackageEnv>,
+  /// How long generating the flags took, with [`FlagsOptions::timings`]. Not serialized.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  pub timings: Option<Timings>,
+}
+
+/// The files and environment variables a unit's compilation depends on, for knowing when
+/// flags or analysis results derived from it are stale.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnitInputs {
+  /// Every source file rustc read, from the dep-info file of the unit's last compilation
+  /// in the target directory. Empty if it was never compiled there.
+  pub sources: Vec<PathBuf>,
+  /// The `cargo:rerun-if-changed` paths of the unit's build script, or the package root
+  /// when the script didn't print any, as cargo then reruns it on any change in the
+  /// package.
+  pub build_script_inputs: Vec<PathBuf>,
+  /// The variables read with `env!`/`option_env!` and the build script's
+  /// `cargo:rerun-if-env-changed` ones, sorted.
+  pub env: Vec<String>,
+}
+
+/// A dependency passed to rustc with `--extern`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExternDep {
+  /// The dependency's own crate name.
+  pub name: String,
+  /// The name it's externed under when the dependent renames it
+  /// (`alias = { package = "..." }`).
+  pub rename: Option<String>,
+  /// The `.rmeta`/`.rlib`/proc-macro library the extern points at.
+  pub rmeta_path: Option<PathBuf>,
+  pub is_proc_macro: bool,
+  /// The dependency's package ID, formatted like [`RustcFlags::package_id`].
+  pub pkg_id: String,
+}
+
+/// The profile settings cargo resolved for a unit, for checking that e.g. dev rather than
+/// release was used. Not all of them are reflected in the flags.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProfileInfo {
+  pub name: String,
+  pub opt_level: String,
+  /// `None` when debuginfo is off.
+  pub debuginfo: Option<u32>,
+  /// `off`, `true`/`false`, or a named setting like `thin`.
+  pub lto: String,
+  pub codegen_units: Option<u32>,
+  pub debug_assertions: bool,
+  pub overflow_checks: bool,
+  pub panic: String,
+  pub incremental: bool,
+}
+
+impl RustcFlags {
+  /// Bumped whenever the serialized form of the flags changes incompatibly.
+  pub const SCHEMA_VERSION: u32 = 6;
+
+  /// Replaces the variables of [`RustcFlags::package_env`] in `env` with those of
+  /// `package_env`, e.g. after overriding its version.
+  pub fn set_package_env(&mut self, package_env: PackageEnv) {
+    if let Some(old) = &self.package_env {
+      for key in old.vars().keys() {
+        self.env.remove(key);
+      }
+    }
+    self.env.extend(package_env.vars());
+    self.package_env = Some(package_env);
+  }
+
+  /// Everything that determines the compilation, one item per line: the rustc and its
+  /// wrappers, the arguments, then the environment as `KEY=VALUE`. The flags are generated
+  /// in a deterministic order (features and externs sorted by name, the environment by
+  /// variable), so equal configurations give equal strings, fit for cache keys.
+  pub fn canonical_string(&self) -> String {
+    let programs = self.wrappers.iter().chain(std::iter::once(&self.rustc));
+    programs
+      .map(|program| format!("{}", program.display()))
+      .chain(flags::render(&self.flags))
+      .chain(self.env.iter().map(|(k, v)| format!("{}={}", k, v)))
+      .collect::<Vec<_>>()
+      .join("\n")
+  }
+
+  /// A 64-bit FNV-1a hash of [`RustcFlags::canonical_string`]. Unlike `std`'s hashers,
+  /// it's the same across Rust versions and processes.
+  pub fn fingerprint(&self) -> u64 {
+    fnv1a(self.canonical_string().as_bytes())
+  }
+
+  /// The rustc command line, starting with `rustc`.
+  pub fn to_args(&self) -> Vec<String> {
+    std::iter::once("rustc".to_string())
+      .chain(flags::render(&self.flags))
+      .collect()
+  }
+
+  /// Like [`RustcFlags::to_args`], without the crate root.
+  pub fn to_args_without_crate_root(&self) -> Vec<String> {
+    let flags = self
+      .flags
+      .iter()
+      .filter(|flag| !matches!(flag, RustcFlag::Input(_)))
+      .cloned()
+      .collect::<Vec<_>>();
+    std::iter::once("rustc".to_string())
+      .chain(flags::render(&flags))
+      .collect()
+  }
+
+  /// A command running [`RustcFlags::rustc`] with these flags, through the
+  /// [`RustcFlags::wrappers`] if there are any. The environment is set on the command
+  /// only, never on the current process. Paths are passed as they are, even if they
+  /// aren't UTF-8.
+  pub fn command(&self) -> Command {
+    let mut programs = self.wrappers.iter().chain(std::iter::once(&self.rustc));
+    let mut command = Command::new(programs.next().unwrap());
+    command
+      .args(programs)
+      .args(flags::render_os(&self.flags))
+      .envs(&self.env);
+    command
+  }
+
+  /// Writes the flags to a rustc response file at `path` and returns the short command
+  /// line `["rustc", "@<path>"]`, for when the full one would exceed OS length limits.
+  ///
+  /// rustc reads one argument per line with no quoting or escaping, so an argument
+  /// containing a newline can't be represented and is an error. So is one that isn't
+  /// UTF-8, which rustc requires of argfiles.
+  pub fn write_argfile(&self, path: impl AsRef<Path>) -> Result<Vec<String>> {
+    let path = path.as_ref();
+    if let Some(arg) = flags::render_os(&self.flags).iter().find(|arg| arg.to_str().is_none()) {
+      bail!("Argument {:?} isn't UTF-8 and can't be put in an argfile", arg);
+    }
+    let args = flags::render(&self.flags);
+    if let Some(arg) = args.iter().find(|arg| arg.contains('\n') || arg.contains('\r')) {
+      bail!("Argument {:?} contains a newline and can't be put in an argfile", arg);
+    }
+    fs::write(path, args.join("\n"))
+      .with_context(|| format!("Could not write argfile {}", path.display()))?;
+    Ok(vec!["rustc".into(), format!("@{}", path.display())])
+  }
+}
+
+// 64-bit FNV-1a, for hashes that have to be stable across processes.
+fn fnv1a(bytes: &[u8]) -> u64 {
+  bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+    (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+  })
+}
+
+/// Parses a `--features` value as given on the cargo command line, e.g. `"foo bar"`,
+/// `"foo,bar"` or `"dep/feat, baz"`, with default features enabled.
+pub fn parse_features_str(features: &str) -> Result<CliFeatures> {
+  // Cargo splits each `--features` value on whitespace and commas in `from_command_line`,
+  // so passing the string through unchanged gives exactly the CLI behavior.
+  CliFeatures::from_command_line(&[features.to_string()], false, true)
+}
+
+/// How to treat `Cargo.lock` when the requested resolution disagrees with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockfilePolicy {
+  /// Re-resolve and rewrite the lockfile as needed (cargo's default).
+  Update,
+  /// Error instead of updating the lockfile, like `--locked`.
+  Locked,
+  /// Like `Locked`, and also forbid network access, like `--frozen`.
+  Frozen,
+}
+
+impl Default for LockfilePolicy {
+  fn default() -> Self {
+    LockfilePolicy::Update
+  }
+}
+
+/// Resolver behavior, mapped onto cargo's (mostly unstable) resolver flags.
+#[derive(Debug, Clone, Default)]
+pub struct ResolverOptions {
+  /// `-Z minimal-versions`
+  pub minimal_versions: bool,
+  /// `-Z direct-minimal-versions`
+  pub direct_minimal_versions: bool,
+  /// `-Z precise-pre-release`
+  pub precise_pre_release: bool,
+  pub lockfile: LockfilePolicy,
+  /// Permit the `-Z` flags above on a non-nightly cargo.
+  pub allow_nightly_features: bool,
+}
+
+impl ResolverOptions {
+  fn unstable_flags(&self) -> Vec<String> {
+    let mut flags = Vec::new();
+    if self.minimal_versions {
+      flags.push("minimal-versions".to_string());
+    }
+    if self.direct_minimal_versions {
+      flags.push("direct-minimal-versions".to_string());
+    }
+    if self.precise_pre_release {
+      flags.push("precise-pre-release".to_string());
+    }
+    flags
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolManglingVersion {
+  Legacy,
+  V0,
+}
+
+impl SymbolManglingVersion {
+  fn as_str(&self) -> &'static str {
+    match self {
+      SymbolManglingVersion::Legacy => "legacy",
+      SymbolManglingVersion::V0 => "v0",
+    }
+  }
+
+  fn parse(s: &str) -> Option<Self> {
+    match s {
+      "legacy" => Some(SymbolManglingVersion::Legacy),
+      "v0" => Some(SymbolManglingVersion::V0),
+      _ => None,
+    }
+  }
+}
+
+/// An output kind for rustc's `--emit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emit {
+  DepInfo,
+  Metadata,
+  Link,
+  Mir,
+  LlvmIr,
+  LlvmBc,
+  Asm,
+  Obj,
+}
+
+impl Emit {
+  fn as_str(&self) -> &'static str {
+    match self {
+      Emit::DepInfo => "dep-info",
+      Emit::Metadata => "metadata",
+      Emit::Link => "link",
+      Emit::Mir => "mir",
+      Emit::LlvmIr => "llvm-ir",
+      Emit::LlvmBc => "llvm-bc",
+      Emit::Asm => "asm",
+      Emit::Obj => "obj",
+    }
+  }
+}
+
+/// Whether to pass `-C incremental`, and with which directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Incremental {
+  /// When the profile enables incremental compilation, with cargo's `incremental`
+  /// directory in the target directory, as cargo does.
+  Profile,
+  /// Always, with cargo's directory, e.g. for repeated driver runs on a release build.
+  Always,
+  /// Always, with this directory. rustc keeps a session per crate under it.
+  Dir(PathBuf),
+  /// Never.
+  Off,
+}
+
+impl Default for Incremental {
+  fn default() -> Self {
+    Incremental::Profile
+  }
+}
+
+/// When to generate flags for a file without a cargo package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FallbackMode {
+  /// Fall back only if no `Cargo.toml` encloses the source file and no
+  /// [`FlagsOptions::manifest_path`] is given.
+  Auto,
+  /// Always treat the source file as a standalone crate.
+  Always,
+  /// Never fall back; error if the file isn't part of a package.
+  Never,
+}
+
+impl Default for FallbackMode {
+  fn default() -> Self {
+    FallbackMode::Auto
+  }
+}
+
+/// Whether the target's build script is run for its environment. This also decides whether
+/// its artifact dependencies (`artifact = "bin"`/`"cdylib"`, for `CARGO_BIN_FILE_*` and
+/// friends) are built, which only `Run` does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildScriptMode {
+  /// Compile and run it (if it isn't up to date), as cargo would.
+  Run,
+  /// Read what it printed when cargo last ran it in the target directory, without
+  /// compiling or running anything. If it never ran there, nothing it sets is included.
+  UseCached,
+  /// Don't run it, e.g. for untrusted code. Neither `OUT_DIR` nor anything the script
+  /// would set is included.
+  Skip,
+}
+
+impl Default for BuildScriptMode {
+  fn default() -> Self {
+    BuildScriptMode::Run
+  }
+}
+
+/// Which cargo release's flag shape to reproduce. Only differences that this crate
+/// models are covered; everything else follows the linked cargo (see [`cargo_version`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CargoCompat {
+  /// The cargo this crate links against, which predates `--check-cfg`.
+  Linked,
+  /// cargo 1.80 and later, which also declares the expected cfgs with `--check-cfg`:
+  /// `docsrs`, every feature the package declares, the `check-cfg` list of
+  /// `[lints.rust] unexpected_cfgs`, and build scripts' `cargo::rustc-check-cfg`s.
+  CheckCfg,
+}
+
+impl Default for CargoCompat {
+  fn default() -> Self {
+    CargoCompat::Linked
+  }
+}
+
+/// A cargo target picked by kind and name, like `--test <name>` or `--example <name>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TargetSelector {
+  Lib,
+  Bin(String),
+  Example(String),
+  Test(String),
+  Bench(String),
 }
 
+impl TargetSelector {
+  // Builds only the selected target (and what it depends on). Selecting a test, example
+  // or bench makes cargo resolve dev-dependencies and, for tests and benches, compile in
+  // test mode.
+  fn filter(&self) -> CompileFilter {
+    let just = |name: &String| FilterRule::Just(vec![name.clone()]);
+    let none = || FilterRule::Just(vec![]);
+    let (lib, bins, examples, tests, benches) = match self {
+      TargetSelector::Lib => (LibRule::True, none(), none(), none(), none()),
+      TargetSelector::Bin(name) => (LibRule::False, just(name), none(), none(), none()),
+      TargetSelector::Example(name) => (LibRule::False, none(), just(name), none(), none()),
+      TargetSelector::Test(name) => (LibRule::False, none(), none(), just(name), none()),
+      TargetSelector::Bench(name) => (LibRule::False, none(), none(), none(), just(name)),
+    };
+    CompileFilter::Only {
+      all_targets: false,
+      lib,
+      bins,
+      examples,
+      tests,
+      benches,
+    }
+  }
+
+  pub(crate) fn matches(&self, target: &Target) -> bool {
+    match self {
+      TargetSelector::Lib => target.is_lib(),
+      TargetSelector::Bin(name) => target.is_bin() && target.name() == name,
+      TargetSelector::Example(name) => target.is_example() && target.name() == name,
+      TargetSelector::Test(name) => target.is_test() && target.name() == name,
+      TargetSelector::Bench(name) => target.is_bench() && target.name() == name,
+    }
+  }
+}
+
+/// A kind of cargo target, for [`FlagsOptions::target_priority`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TargetKind {
+  /// Any `[lib]`, including proc-macros and other crate types.
+  Lib,
+  Bin,
+  Example,
+  Test,
+  Bench,
+}
+
+impl TargetKind {
+  /// The default priority: libs, then bins, examples, tests and benches.
+  pub const DEFAULT_PRIORITY: &'static [TargetKind] = &[
+    TargetKind::Lib,
+    TargetKind::Bin,
+    TargetKind::Example,
+    TargetKind::Test,
+    TargetKind::Bench,
+  ];
+
+  pub(crate) fn matches(&self, target: &Target) -> bool {
+    match self {
+      TargetKind::Lib => target.is_lib(),
+      TargetKind::Bin => target.is_bin(),
+      TargetKind::Example => target.is_example(),
+      TargetKind::Test => target.is_test(),
+      TargetKind::Bench => target.is_bench(),
+    }
+  }
+}
+
+/// The version of the cargo library flag generation is modeled on.
+pub fn cargo_version() -> String {
+  cargo_compat::cargo_version()
+}
+
+pub struct FlagsOptions {
+  /// The `Cargo.toml` to load the workspace from. Defaults to the nearest one above the
+  /// source file.
+  ///
+  /// Any unit in the workspace's dependency graph can be matched, not just the members'.
+  /// So to get the flags a dependency (a file under `~/.cargo/registry/src`, or a `path`
+  /// dependency outside the workspace) is compiled with under the workspace's feature
+  /// resolution, point this at the workspace, since the nearest manifest would be the
+  /// dependency's own.
+  pub manifest_path: Option<PathBuf>,
+  pub features: CliFeatures,
+  /// Target triple (or target spec JSON) to compile for, as with `cargo --target`.
+  /// Defaults to the host, or `build.target` from cargo config.
+  pub target: Option<String>,
+  /// The profile to build with, like `release` for `--release` or a custom
+  /// `[profile.<name>]`. Defaults to the one cargo picks for `mode` (`dev`, or `test`).
+  pub profile: Option<String>,
+  pub lib_only: bool,
+  /// Generates flags for this target specifically, rather than the one whose files
+  /// contain the source path. The source path must still belong to it. Takes precedence
+  /// over `lib_only`. Needed when a file is shared by several targets but no lib, like a
+  /// module used by two `src/bin/` binaries, which is otherwise an error listing them.
+  pub target_selector: Option<TargetSelector>,
+  /// When several targets compile the source file and none has it as its crate root, the
+  /// first kind in this list with exactly one of them wins (a lib built for both the host
+  /// and the target counts once, the target build winning). Kinds missing from the list
+  /// are never picked, so an empty list makes every such file a
+  /// [`GenerateFlagsError::AmbiguousUnit`]. Defaults to [`TargetKind::DEFAULT_PRIORITY`].
+  /// See [`generate_candidates`] for the flags of all of them.
+  pub target_priority: Vec<TargetKind>,
+  /// Which cargo command to emulate, e.g. `Check { test: false }` for `cargo check` or
+  /// `Build` for `cargo build`. This decides the targets considered and `--emit`.
+  pub mode: CompileMode,
+  /// Compile the file's targets as their unit tests, so that `#[cfg(test)]` code and
+  /// dev-dependencies are included: `Check { test: false }` becomes `Check { test: true }`
+  /// and `Build` becomes `Test`, as with `cargo check --tests`/`cargo test --no-run`.
+  pub test: bool,
+  pub resolver: ResolverOptions,
+  /// Overrides the mangling version picked up from the unit's rustflags, by coming after
+  /// them.
+  pub symbol_mangling_version: Option<SymbolManglingVersion>,
+  /// Where rustc should write the `.rmeta`, instead of its default location.
+  pub metadata_out: Option<PathBuf>,
+  /// Where rustc should write the dep-info `.d` file, which can be read back with
+  /// [`parse_dep_info`].
+  pub dep_info_out: Option<PathBuf>,
+  pub fallback: FallbackMode,
+  /// Edition for fallback flags, defaulting to the latest stable edition.
+  pub fallback_edition: Option<String>,
+  /// A package ID spec like `foo@1.2.3` restricting which package's units may match,
+  /// for when several versions of a crate are in the graph.
+  pub package_id_spec: Option<String>,
+  /// The workspace member to compile, like `cargo -p`. Defaults to the package whose
+  /// manifest was loaded if the source file is in its directory, then to the member whose
+  /// directory contains the file (the innermost one, for nested members), and else to
+  /// cargo's default members, so that flags work from anywhere in a virtual workspace.
+  pub package: Option<String>,
+  /// Use this sysroot instead of probing `rustc --print sysroot`.
+  pub sysroot: Option<PathBuf>,
+  /// The rustup toolchain to probe the sysroot of, as with `rustup run <toolchain>`.
+  /// Without it, rustup picks the toolchain as it would for cargo in the package's
+  /// directory: `RUSTUP_TOOLCHAIN`, then `rust-toolchain.toml`, then the default.
+  pub toolchain: Option<String>,
+  /// Merge the feature cfgs and externs of every target in the matched package, so that
+  /// one command can compile any file in it. This may include externs that no single
+  /// target needs, e.g. a bin-only dependency when compiling the lib.
+  pub whole_package: bool,
+  /// If the manifest fails to load, retry without sections that can't affect flags
+  /// (`[package.metadata]`, `[workspace.metadata]`, `[badges]`).
+  pub tolerant_manifest: bool,
+  /// The rustc version used for version-dependent flags, instead of the one cargo probed.
+  pub rustc_version: Option<semver::Version>,
+  /// Emit `--error-format=json --json=artifacts,diagnostic-rendered-ansi`. rustc then
+  /// prints one JSON object per line on stderr: diagnostics as usual, plus
+  /// `{"artifact": "<path>", "emit": "<kind>"}` for every file written, where `<kind>`
+  /// is `metadata`, `link` or `dep-info`.
+  pub json_artifacts: bool,
+  /// Omit the `-L` deps directory and make every `--extern` path absolute, so the externs
+  /// don't depend on where the command runs. rustc then can't discover transitive
+  /// dependencies through `-L`, so crates whose metadata refers to rlibs that aren't
+  /// directly externed may fail to load.
+  pub absolute_externs_only: bool,
+  /// Make [`generate_rustc_flags_with`] write the flags to this response file and return
+  /// `["rustc", "@<argfile>"]`.
+  pub argfile: Option<PathBuf>,
+  /// When the crate's edition isn't stable on the detected rustc, set `RUSTC_BOOTSTRAP=1`
+  /// and pass `-Z unstable-options` instead of failing.
+  pub bootstrap_unstable_editions: bool,
+  /// How to get the environment set by the target's build script. This composes with
+  /// every `mode`, including test mode, where `--test`/`--cfg test` are unaffected.
+  pub build_scripts: BuildScriptMode,
+  /// Called with every unit whose sources contain the file, after the package filters
+  /// and before one of them is selected. Useful for debugging why a unit was (not) picked.
+  pub on_candidate: Option<Box<dyn Fn(&Unit)>>,
+  /// Error if an extern'd dependency artifact doesn't exist, meaning the dependency hasn't
+  /// been built yet or the target directory is stale. Off by default, since flags are
+  /// often generated before the dependencies are built. Checked after
+  /// [`FlagsOptions::build_missing_deps`] has had its chance to build them.
+  pub check_externs_built: bool,
+  /// Compile the dependencies whose extern'd artifacts don't exist yet, as `cargo check`
+  /// would (running their build scripts), before returning. Only applies with
+  /// [`BuildScriptMode::Run`]. Defaults to `true`.
+  pub build_missing_deps: bool,
+  /// Also set the `DEP_<LINKS>_<KEY>` variables that cargo only gives build scripts: the
+  /// metadata printed by the build scripts of the `links` packages the unit links with
+  /// (e.g. `DEP_OPENSSL_INCLUDE`). Their `-L` paths are in the flags either way.
+  pub dep_links_env: bool,
+  /// Emit `--remap-path-prefix` so paths under the sysroot appear under this prefix in
+  /// diagnostics and artifacts, making them comparable across machines. This doesn't
+  /// change `--sysroot` itself: whoever runs the command on another machine must
+  /// substitute their own sysroot there, keeping the same remapped prefix.
+  pub remap_sysroot: Option<PathBuf>,
+  /// Like [`FlagsOptions::remap_sysroot`], for the registry source cache
+  /// (`$CARGO_HOME/registry/src`).
+  pub remap_registry: Option<PathBuf>,
+  /// The `trim-paths` scope (e.g. `"diagnostics,object"`, `"all"` or `"none"`) instead
+  /// of the profile's, whose paths get remapped as cargo's `-Z trim-paths` does: the
+  /// package root to a workspace-relative (or `<name>-<version>`) path and the std
+  /// sources to `/rustc/<commit>`. The scope is passed with `-Z remap-path-scope`, so it
+  /// needs a nightly rustc.
+  pub trim_paths: Option<String>,
+  /// More `--remap-path-prefix` pairs of `(from, to)`, after all the others so that they
+  /// take precedence.
+  pub remap_path_prefixes: Vec<(PathBuf, PathBuf)>,
+  /// Redirects everything cargo would write outside this directory, leaving the source
+  /// tree untouched (e.g. when it's read-only):
+  /// - the target directory (and so every `OUT_DIR`) becomes `<dir>/target`,
+  /// - cargo's home (registry index, downloaded crates, git checkouts) becomes
+  ///   `<dir>/cargo-home`, which starts out empty, so dependencies are fetched again,
+  /// - `Cargo.lock` is never written: `LockfilePolicy::Update` acts as `Locked`, so a
+  ///   missing or outdated lockfile is an error.
+  pub hermetic_dir: Option<PathBuf>,
+  /// The target directory to use instead of the workspace's, like `CARGO_TARGET_DIR`.
+  /// Takes precedence over the one `hermetic_dir` implies.
+  pub target_dir: Option<PathBuf>,
+  /// Use this subdirectory of the target directory (e.g. `target/<name>`) as the target
+  /// directory, so that the check-mode artifacts and build script runs don't touch the
+  /// user's own, and a concurrent `cargo build` doesn't contend for them.
+  pub isolated_target_dir: Option<String>,
+  /// Like cargo, everything in the target directory is done while holding its build
+  /// lock, so a concurrent `cargo build` never sees half-written fingerprints. When this
+  /// is `false`, error out if another cargo process holds the lock rather than waiting
+  /// for it. Defaults to `true`.
+  pub wait_for_build_lock: bool,
+  /// Never touch the network, like `cargo --offline`: dependencies must already be in the
+  /// local registry cache or git checkouts.
+  pub offline: bool,
+  /// Cargo's home directory (registry, git checkouts, global config) instead of
+  /// `CARGO_HOME` or `~/.cargo`. Takes precedence over the one `hermetic_dir` implies.
+  pub cargo_home: Option<PathBuf>,
+  /// Config values as with `cargo --config`, e.g. `net.git-fetch-with-cli=true` or the
+  /// path of an extra config file.
+  pub config_overrides: Vec<String>,
+  /// Build these standard library crates from source, like `-Z build-std=core,alloc`,
+  /// so that the externs point at the locally built ones rather than the sysroot's. For
+  /// targets without a prebuilt std, e.g. a custom target spec JSON in `target`. Needs a
+  /// nightly cargo (or `allow_nightly_features`) and the `rust-src` component.
+  pub build_std: Option<Vec<String>>,
+  /// Enable the `required-features` of the target the source file belongs to, when they
+  /// aren't all enabled already. Cargo leaves such targets out of the unit graph, so
+  /// otherwise their files aren't found (with an error naming the missing features).
+  pub enable_required_features: bool,
+  /// Make the command a `rustc --print=<value>` query in the crate's configuration, like
+  /// `cargo rustc --print`, instead of a compilation. Useful values are `cfg`,
+  /// `target-libdir`, `sysroot`, `crate-name`, `file-names`, `target-spec-json` (nightly)
+  /// and `native-static-libs`; see `rustc --help` for the full list. `--emit` is left out,
+  /// as the ones that print without compiling ignore it anyway.
+  pub print: Option<String>,
+  /// The cargo release whose flags to reproduce.
+  pub cargo_compat: CargoCompat,
+  /// Parallelism for compiling build scripts, and the `NUM_JOBS` they see, e.g. to
+  /// throttle them in background analysis. Defaults to cargo's (the number of CPUs, or
+  /// `build.jobs`).
+  pub jobs: Option<u32>,
+  /// Emit a `-L` for every deps directory in the unit graph (e.g. host and target when
+  /// cross-compiling) and every `cargo:rustc-link-search` path from build scripts that
+  /// have already run in the target directory, instead of just the unit's own deps
+  /// directory.
+  pub all_search_paths: bool,
+  /// Add the `rust-<next edition>-compatibility` lints, as `cargo fix --edition` does,
+  /// while keeping the crate's current edition. Migrating to an edition that isn't stable
+  /// yet needs a nightly rustc. Only applies to local packages, not dependencies.
+  pub edition_migration: bool,
+  /// Append the rustflags cargo would pass (from `CARGO_ENCODED_RUSTFLAGS`, `RUSTFLAGS`,
+  /// or `build.rustflags`/`target.<triple>.rustflags` in `.cargo/config.toml`). When
+  /// `false`, only the target cpu, target features and relocation/code models are taken
+  /// from them. Defaults to `true`.
+  pub propagate_rustflags: bool,
+  /// What rustc should emit instead of what cargo would for `mode`, e.g.
+  /// `[Metadata, Link]` for a tool that needs both. `metadata_out` and `dep_info_out`
+  /// still apply.
+  pub emit: Option<Vec<Emit>>,
+  /// Where rustc keeps incremental state. Standalone files only get `-C incremental`
+  /// with `Incremental::Dir`.
+  pub incremental: Incremental,
+  /// Arguments appended after all the generated ones, e.g. `-Zalways-encode-mir` or
+  /// extra `--cfg`s.
+  pub extra_args: Vec<String>,
+  /// Fill in [`RustcFlags::inputs`].
+  pub collect_inputs: bool,
+  /// Contents of files that differ from what's on disk, by path, e.g. unsaved editor
+  /// buffers. The source file may be one that doesn't exist on disk yet, and fallback
+  /// flags look at its contents here. See [`RustcFlags::file_overrides`].
+  pub file_overrides: HashMap<PathBuf, String>,
+  /// Where to report progress. Cargo's messages (e.g. `Compiling foo`, warnings) go here
+  /// too, as [`ProgressEvent::Message`]s, instead of to stderr. Ignored by
+  /// [`generate_with_config`] and [`generate_for_workspace`], whose `Config` has a shell
+  /// already.
+  pub progress: Option<Arc<dyn FlagsProgress>>,
+  /// Let cargo, and the compilers it runs for build scripts and missing dependencies,
+  /// print to stderr as `cargo build` would. By default their output is kept quiet, and
+  /// the output of a failed compilation shows up in the error instead.
+  pub show_build_output: bool,
+  /// Fill in [`RustcFlags::timings`], for finding out which phase makes generation slow.
+  /// Only [`generate`], [`generate_with_config`] and [`generate_for_workspace`] time
+  /// their calls.
+  pub timings: bool,
+}
+
+impl Default for FlagsOptions {
+  fn default() -> Self {
+    FlagsOptions {
+      manifest_path: None,
+      features: CliFeatures::new_all(false),
+      target: None,
+      profile: None,
+      lib_only: false,
+      target_selector: None,
+      target_priority: TargetKind::DEFAULT_PRIORITY.to_vec(),
+      mode: CompileMode::Check { test: false },
+      test: false,
+      resolver: ResolverOptions::default(),
+      symbol_mangling_version: None,
+      metadata_out: None,
+      dep_info_out: None,
+      fallback: FallbackMode::default(),
+      fallback_edition: None,
+      package_id_spec: None,
+      package: None,
+      sysroot: None,
+      toolchain: None,
+      whole_package: false,
+      tolerant_manifest: false,
+      rustc_version: None,
+      json_artifacts: false,
+      absolute_externs_only: false,
+      argfile: None,
+      bootstrap_unstable_editions: false,
+      build_scripts: BuildScriptMode::default(),
+      on_candidate: None,
+      check_externs_built: false,
+      build_missing_deps: true,
+      dep_links_env: false,
+      remap_sysroot: None,
+      remap_registry: None,
+      trim_paths: None,
+      remap_path_prefixes: Vec::new(),
+      hermetic_dir: None,
+      target_dir: None,
+      isolated_target_dir: None,
+      wait_for_build_lock: true,
+      offline: false,
+      cargo_home: None,
+      config_overrides: Vec::new(),
+      build_std: None,
+      enable_required_features: false,
+      print: None,
+      cargo_compat: CargoCompat::default(),
+      jobs: None,
+      all_search_paths: false,
+      edition_migration: false,
+      propagate_rustflags: true,
+      emit: None,
+      incremental: Incremental::default(),
+      extra_args: Vec::new(),
+      collect_inputs: false,
+      file_overrides: HashMap::new(),
+      progress: None,
+      show_build_output: false,
+      timings: false,
+    }
+  }
+}
+
+// By hand, as callbacks have no `Debug`; they show as whether they're set. Overrides are
+// sorted, so that equal options always print the same, which `cache` relies on.
+impl fmt::Debug for FlagsOptions {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("FlagsOptions")
+      .field("manifest_path", &self.manifest_path)
+      .field("features", &self.features)
+      .field("target", &self.target)
+      .field("profile", &self.profile)
+      .field("lib_only", &self.lib_only)
+      .field("target_selector", &self.target_selector)
+      .field("target_priority", &self.target_priority)
+      .field("mode", &self.mode)
+      .field("test", &self.test)
+      .field("resolver", &self.resolver)
+      .field("symbol_mangling_version", &self.symbol_mangling_version)
+      .field("metadata_out", &self.metadata_out)
+      .field("dep_info_out", &self.dep_info_out)
+      .field("fallback", &self.fallback)
+      .field("fallback_edition", &self.fallback_edition)
+      .field("package_id_spec", &self.package_id_spec)
+      .field("package", &self.package)
+      .field("sysroot", &self.sysroot)
+      .field("toolchain", &self.toolchain)
+      .field("whole_package", &self.whole_package)
+      .field("tolerant_manifest", &self.tolerant_manifest)
+      .field("rustc_version", &self.rustc_version)
+      .field("json_artifacts", &self.json_artifacts)
+      .field("absolute_externs_only", &self.absolute_externs_only)
+      .field("argfile", &self.argfile)
+      .field("bootstrap_unstable_editions", &self.bootstrap_unstable_editions)
+      .field("build_scripts", &self.build_scripts)
+      .field("on_candidate", &self.on_candidate.is_some())
+      .field("check_externs_built", &self.check_externs_built)
+      .field("build_missing_deps", &self.build_missing_deps)
+      .field("dep_links_env", &self.dep_links_env)
+      .field("remap_sysroot", &self.remap_sysroot)
+      .field("remap_registry", &self.remap_registry)
+      .field("trim_paths", &self.trim_paths)
+      .field("remap_path_prefixes", &self.remap_path_prefixes)
+      .field("hermetic_dir", &self.hermetic_dir)
+      .field("target_dir", &self.target_dir)
+      .field("isolated_target_dir", &self.isolated_target_dir)
+      .field("wait_for_build_lock", &self.wait_for_build_lock)
+      .field("offline", &self.offline)
+      .field("cargo_home", &self.cargo_home)
+      .field("config_overrides", &self.config_overrides)
+      .field("build_std", &self.build_std)
+      .field("enable_required_features", &self.enable_required_features)
+      .field("print", &self.print)
+      .field("cargo_compat", &self.cargo_compat)
+      .field("jobs", &self.jobs)
+      .field("all_search_paths", &self.all_search_paths)
+      .field("edition_migration", &self.edition_migration)
+      .field("propagate_rustflags", &self.propagate_rustflags)
+      .field("emit", &self.emit)
+      .field("incremental", &self.incremental)
+      .field("extra_args", &self.extra_args)
+      .field("collect_inputs", &self.collect_inputs)
+      .field("file_overrides", &self.file_overrides.iter().collect::<BTreeMap<_, _>>())
+      .field("progress", &self.progress.is_some())
+      .field("show_build_output", &self.show_build_output)
+      .field("timings", &self.timings)
+      .finish()
+  }
+}
+
+fn make_config(options: &FlagsOptions) -> Result<Config> {
+  let resolver = &options.resolver;
+  let mut unstable_flags = resolver.unstable_flags();
+  if let Some(crates) = &options.build_std {
+    unstable_flags.push(format!("build-std={}", crates.join(",")));
+  }
+  let lockfile = match (&options.hermetic_dir, resolver.lockfile) {
+    (Some(_), LockfilePolicy::Update) => LockfilePolicy::Locked,
+    (_, lockfile) => lockfile,
+  };
+  let (frozen, locked) = match lockfile {
+    LockfilePolicy::Update => (false, false),
+    LockfilePolicy::Locked => (false, true),
+    LockfilePolicy::Frozen => (true, true),
+  };
+
+  let cargo_home = options.cargo_home.clone().or_else(|| {
+    options
+      .hermetic_dir
+      .as_ref()
+      .map(|dir| dir.join("cargo-home"))
+  });
+  let shell_output = match &options.progress {
+    Some(progress) => {
+      Some(Box::new(progress::ProgressWriter::new(progress.clone())) as Box<dyn Write>)
+    }
+    None if !options.show_build_output => Some(Box::new(io::sink()) as Box<dyn Write>),
+    None => None,
+  };
+  let mut config = cargo_compat::new_config(
+    resolver.allow_nightly_features,
+    cargo_home.as_deref(),
+    shell_output,
+  )?;
+  if !unstable_flags.is_empty() && !cargo_compat::nightly_features_allowed(&config) {
+    bail!(
+      "Options -Z {} require a nightly cargo (or `allow_nightly_features`)",
+      unstable_flags.join(" -Z ")
+    );
+  }
+
+  cargo_compat::configure(
+    &mut config,
+    cargo_compat::ConfigureArgs {
+      frozen,
+      locked,
+      offline: options.offline,
+      unstable_flags: &unstable_flags,
+      config_overrides: &options.config_overrides,
+      target_dir: options
+        .target_dir
+        .clone()
+        .or_else(|| options.hermetic_dir.as_ref().map(|dir| dir.join("target"))),
+    },
+  )?;
+  Ok(config)
+}
+
+// Loads the workspace, moving its target directory into the `isolated_target_dir`
+// subdirectory if there is one.
+fn load_workspace<'cfg>(
+  options: &FlagsOptions,
+  manifest_path: &Path,
+  config: &'cfg Config,
+) -> Result<Workspace<'cfg>> {
+  if let Some(progress) = &options.progress {
+    progress.on_event(ProgressEvent::ResolvingWorkspace);
+  }
+  let mut workspace = manifest::load_workspace(manifest_path, config, options.tolerant_manifest)
+    .context(GenerateFlagsError::WorkspaceLoad {
+      manifest_path: manifest_path.to_path_buf(),
+    })?;
+  if let Some(name) = &options.isolated_target_dir {
+    let target_dir = workspace.target_dir().join(name);
+    workspace.set_target_dir(target_dir);
+  }
+  Ok(workspace)
+}
+
+fn validate_output_paths(options: &FlagsOptions) -> Result<()> {
+  let outputs = [
+    ("metadata", &options.metadata_out),
+    ("dep-info", &options.dep_info_out),
+  ];
+  for (kind, out) in outputs.iter() {
+    if let Some(out) = out {
+      let parent = out
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+      if !parent.is_dir() {
+        bail!(
+          "Directory for {} output {} does not exist",
+          kind,
+          out.display()
+        );
+      }
+    }
+  }
+  Ok(())
+}
+
+// Matches cargo: check-like modes only produce metadata, and units that need upstream
+// object code (tests, executables) don't produce metadata at all.
+fn emit_kinds(
+  options: &FlagsOptions,
+  mode: CompileMode,
+  requires_upstream_objects: bool,
+) -> Vec<String> {
+  let metadata = match &options.metadata_out {
+    Some(metadata_out) => format!("metadata={}", metadata_out.display()),
+    None => "metadata".into(),
+  };
+
+  let dep_info = match &options.dep_info_out {
+    Some(dep_info_out) => format!("dep-info={}", dep_info_out.display()),
+    None => "dep-info".into(),
+  };
+
+  if let Some(kinds) = &options.emit {
+    return kinds
+      .iter()
+      .map(|kind| match kind {
+        Emit::DepInfo => dep_info.clone(),
+        Emit::Metadata => metadata.clone(),
+        kind => kind.as_str().to_string(),
+      })
+      .collect();
+  }
+
+  let mut emit = vec![dep_info];
+  if mode.is_check() || mode.is_doc() || mode.is_doc_test() {
+    emit.push(metadata);
+  } else if !requires_upstream_objects {
+    emit.push(metadata);
+    emit.push("link".into());
+  } else {
+    emit.push("link".into());
+  }
+  emit
+}
+
+// `--emit`, or `--print` when the caller asked for a query instead of a compilation.
+fn output_flag(
+  options: &FlagsOptions,
+  mode: CompileMode,
+  requires_upstream_objects: bool,
+) -> RustcFlag {
+  match &options.print {
+    Some(print) => RustcFlag::Raw(format!("--print={}", print)),
+    None => RustcFlag::Emit(emit_kinds(options, mode, requires_upstream_objects)),
+  }
+}
+
+/// Returns the rustc command line for `source_path` and sets its environment (like
+/// `OUT_DIR` and `CARGO_PKG_*`) on the current process. This is a thin wrapper over
+/// [`generate`], which returns the environment in [`RustcFlags::env`] instead and is the
+/// one to use from multithreaded hosts.
 pub fn generate_rustc_flags(
   source_path: impl AsRef<Path>,
   features: CliFeatures,
   lib_only: bool,
 ) -> Result<Vec<String>> {
-  let source_path = source_path.as_ref();
-
-  let rustc = env::var_os("RUSTC")
-    .map(|s| s.into_string().unwrap())
-    .unwrap_or("rustc".to_string());
-  let sysroot = String::from_utf8(
-    Command::new(rustc)
-      .args(&["--print", "sysroot"])
-      .output()?
-      .stdout,
+  generate_rustc_flags_with(
+    source_path,
+    &FlagsOptions {
+      features,
+      lib_only,
+      ..FlagsOptions::default()
+    },
+  )
+}
+
+lazy_static::lazy_static! {
+  static ref ENV_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// Generates the flags and sets their environment variables on the current process.
+///
+/// The process environment is shared, so concurrent calls are serialized by a
+/// process-wide lock: each call's generation and `set_var`s happen together. Prefer
+/// [`generate`], which returns the environment instead and needs no lock.
+pub fn generate_rustc_flags_with(
+  source_path: impl AsRef<Path>,
+  options: &FlagsOptions,
+) -> Result<Vec<String>> {
+  // A panic while holding the lock leaves nothing inconsistent, so ignore poisoning.
+  let _guard = ENV_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+  let flags = generate(source_path, options)?;
+  for (k, v) in &flags.env {
+    env::set_var(k, v);
+  }
+  match &options.argfile {
+    Some(argfile) => flags.write_argfile(argfile),
+    None => Ok(flags.to_args()),
+  }
+}
+
+/// Like [`generate_rustc_flags_with`], but returns the flags and environment without
+/// touching the process environment.
+///
+/// `source_path` may also be a package's `Cargo.toml`, selecting the target `cargo run`
+/// would: the `default-run` bin, else the only bin, else the lib.
+///
+/// Feature `--cfg`s are emitted sorted by feature name, exactly as cargo emits them.
+///
+/// Dependencies are resolved by cargo itself, so `[patch]` and `[replace]` apply as in a
+/// normal build and externs point at the artifacts of the patched sources.
+/// Likewise, `[target.'cfg(..)'.dependencies]` are externed exactly when the unit's
+/// compile target satisfies the cfg, as cargo decides it from that target's `--print cfg`.
+/// Optional dependencies are externed only when activated, including through weak
+/// `dep?/feature` syntax, which on its own never activates `dep`. Renamed dependencies
+/// (`alias = { package = "..." }`) are externed under their alias, optional or not.
+/// Every direct dependency is externed regardless of edition, so 2015-edition
+/// `extern crate` items resolve even when nothing is `use`d.
+/// Each extern names the artifact built with the dependency's resolved features (which
+/// are part of its file hash), including features enabled on path dependencies.
+///
+/// Files of proc-macros and of crates only used as build dependencies get the flags of
+/// their host unit: no `--target`, the host deps directory, and for proc-macros
+/// `--crate-type proc-macro` and `--extern proc_macro`. A crate used both by the host and
+/// the target gets the target's.
+///
+/// Nightly `#![feature(..)]`s are enabled by the source itself, so no extra flags are
+/// needed for them on a nightly rustc. A stable rustc rejects them, as it would under
+/// cargo, unless `RUSTC_BOOTSTRAP=1` is set in the command's environment.
+pub fn generate(source_path: impl AsRef<Path>, options: &FlagsOptions) -> Result<RustcFlags> {
+  generate_from(WorkspaceSource::Options, source_path.as_ref(), options)
+}
+
+// Where `generate_from` gets the workspace.
+enum WorkspaceSource<'a, 'cfg> {
+  // Loaded with a config made from the options, unless the file is standalone.
+  Options,
+  // Loaded with the caller's config, unless the file is standalone.
+  Config(&'cfg Config),
+  // Loaded by the caller, so there's no fallback.
+  Loaded(&'a Workspace<'cfg>),
+}
+
+// The body of `generate`, `generate_with_config` and `generate_for_workspace`.
+fn generate_from(
+  source: WorkspaceSource,
+  source_path: &Path,
+  options: &FlagsOptions,
+) -> Result<RustcFlags> {
+  let timings = timings::Collection::start(options.timings);
+  let source_path = &canonical_source_path(source_path, &options.file_overrides)?;
+
+  validate_output_paths(options)?;
+  let near = match &source {
+    WorkspaceSource::Loaded(workspace) => workspace.root(),
+    _ => source_path.as_path(),
+  };
+  let sysroot = timings::timed(Phase::Sysroot, || resolve_sysroot(options, near))?;
+
+  let features = &options.features;
+  let mut flags = match source {
+    WorkspaceSource::Loaded(workspace) => {
+      generate_in_workspace(workspace, source_path, options, features, sysroot)?
+    }
+    _ if use_fallback(options, source_path) => {
+      fallback::generate(source_path, options, &sysroot)?
+    }
+    WorkspaceSource::Config(config) => {
+      let manifest_path = manifest_path_for(options, source_path)?;
+      let workspace = timings::timed(Phase::WorkspaceLoad, || {
+        load_workspace(options, &manifest_path, config)
+      })?;
+      generate_in_workspace(&workspace, source_path, options, features, sysroot)?
+    }
+    WorkspaceSource::Options => {
+      let config = timings::timed(Phase::WorkspaceLoad, || make_config(options))?;
+      let manifest_path = manifest_path_for(options, source_path)?;
+      let workspace = timings::timed(Phase::WorkspaceLoad, || {
+        load_workspace(options, &manifest_path, &config)
+      })?;
+      generate_in_workspace(&workspace, source_path, options, features, sysroot)?
+    }
+  };
+  flags.timings = timings.map(timings::Collection::finish);
+  Ok(flags)
+}
+
+fn use_fallback(options: &FlagsOptions, source_path: &Path) -> bool {
+  match options.fallback {
+    FallbackMode::Auto => {
+      options.manifest_path.is_none() && manifest::enclosing_package_dir(source_path).is_none()
+    }
+    FallbackMode::Always => true,
+    FallbackMode::Never => false,
+  }
+}
+
+/// Like [`generate`], but with a `Config` the caller has already set up (e.g. with
+/// `Config::configure`), instead of one built from `options`. The options that only
+/// configure cargo ([`FlagsOptions::resolver`], `offline`, `cargo_home`, `target_dir`,
+/// `config_overrides` and `hermetic_dir`) are ignored.
+pub fn generate_with_config(
+  config: &Config,
+  source_path: impl AsRef<Path>,
+  options: &FlagsOptions,
+) -> Result<RustcFlags> {
+  generate_from(WorkspaceSource::Config(config), source_path.as_ref(), options)
+}
+
+/// Like [`generate`] once per feature combination in `combos`, each replacing
+/// `options.features`. The config and workspace are loaded once, and only the resolve and
+/// unit graph are redone per combination, which is much faster than separate calls.
+pub fn generate_for_feature_matrix(
+  source_path: impl AsRef<Path>,
+  combos: &[CliFeatures],
+  options: &FlagsOptions,
+) -> Result<HashMap<CliFeatures, RustcFlags>> {
+  let source_path = &canonical_source_path(source_path.as_ref(), &options.file_overrides)?;
+  validate_output_paths(options)?;
+  let sysroot = resolve_sysroot(options, source_path)?;
+
+  let mut all_flags = HashMap::new();
+  if use_fallback(options, source_path) {
+    // A standalone file has no features.
+    let flags = fallback::generate(source_path, options, &sysroot)?;
+    for features in combos {
+      all_flags.insert(features.clone(), flags.clone());
+    }
+    return Ok(all_flags);
+  }
+
+  let config = make_config(options)?;
+  let manifest_path = manifest_path_for(options, source_path)?;
+  let workspace = load_workspace(options, &manifest_path, &config)?;
+  for features in combos {
+    let flags = generate_in_workspace(&workspace, source_path, options, features, sysroot.clone())
+      .with_context(|| format!("Could not generate flags with features {:?}", features))?;
+    all_flags.insert(features.clone(), flags);
+  }
+  Ok(all_flags)
+}
+
+/// Generates flags for `source_path`, runs rustc with them, and returns its output.
+pub fn check_path(source_path: impl AsRef<Path>, features: CliFeatures) -> Result<Output> {
+  let flags = generate(
+    source_path,
+    &FlagsOptions {
+      features,
+      ..FlagsOptions::default()
+    },
   )?;
-  let sysroot = sysroot.trim().to_string();
+  Ok(flags.command().output()?)
+}
+
+/// Like [`generate`], but for a workspace the caller has already loaded, skipping
+/// manifest parsing.
+///
+/// Options that configure cargo's `Config` (like [`FlagsOptions::resolver`]) and the
+/// standalone-file fallback don't apply here, since the workspace's `Config` is used.
+pub fn generate_for_workspace(
+  workspace: &Workspace,
+  source_path: impl AsRef<Path>,
+  options: &FlagsOptions,
+) -> Result<RustcFlags> {
+  generate_from(WorkspaceSource::Loaded(workspace), source_path.as_ref(), options)
+}
+
+/// Assembles the flags for a unit the caller already picked out of `cx`'s unit graph,
+/// skipping source path matching. `cx` must be prepared as for a build (`prepare_units`,
+/// `prepare` and `build_map`). Returns the command line as [`RustcFlags::to_args`] does,
+/// without the environment.
+///
+/// `cx` is mutable because running the unit's build script goes through cargo's job
+/// queue, which records the script's output in `cx`. Unless `options.build_scripts` is
+/// [`BuildScriptMode::Run`], `cx` is left as it was.
+pub fn flags_for_unit(
+  cx: &mut Context,
+  unit: &Unit,
+  options: &FlagsOptions,
+) -> Result<Vec<String>> {
+  validate_output_paths(options)?;
+  let sysroot = resolve_sysroot(options, cx.bcx.ws.root())?;
+  let all_units = units::collect_units(cx, &cx.bcx.roots);
+  let flags = unit_flags::unit_flags(cx, unit, &all_units, options, &sysroot)?;
+  Ok(flags.to_args())
+}
+
+// Unit source paths are absolute, so relative paths (resolved against the current
+// directory) could never match them. Symlinks are resolved too, so that a link into a
+// package matches the unit owning the real file. An overridden file may not exist yet,
+// like a new buffer that was never saved.
+fn canonical_source_path(
+  source_path: &Path,
+  overrides: &HashMap<PathBuf, String>,
+) -> Result<PathBuf> {
+  if !source_path.exists() {
+    let path = canonical_override_path(source_path);
+    if overrides.keys().any(|key| canonical_override_path(key) == path) {
+      return Ok(path);
+    }
+  }
+
+  let is_symlink = fs::symlink_metadata(source_path)
+    .map_or(false, |metadata| metadata.file_type().is_symlink());
+  canonicalize(source_path).with_context(|| {
+    if is_symlink {
+      format!(
+        "Source file {} is a symlink to a file that does not exist",
+        source_path.display()
+      )
+    } else {
+      format!("Source file {} does not exist", source_path.display())
+    }
+  })
+}
 
-  let config = Config::default()?;
-  let manifest_path = Path::new("./Cargo.toml").canonicalize()?;
-  let workspace = Workspace::new(manifest_path.as_ref(), &config)?;
-  let mut compile_opts = CompileOptions::new(&config, CompileMode::Check { test: false })?;
-  compile_opts.spec = Packages::Default;
-  compile_opts.cli_features = features;
+// `Path::canonicalize`, minus the `\\?\` prefix it gives every path on Windows. The paths
+// editors, cargo and rustc pass around don't have it, and `Path::starts_with` doesn't
+// consider `\\?\C:\src` to be under `C:\`, so prefixed paths would match nothing.
+fn canonicalize(path: &Path) -> io::Result<PathBuf> {
+  path.canonicalize().map(strip_verbatim_prefix)
+}
 
-  if lib_only {
+// `\\?\C:\dir` becomes `C:\dir`, and `\\?\UNC\server\share` becomes `\\server\share`.
+// Verbatim paths without a plain form, like `\\?\Volume{..}`, are left alone.
+fn strip_verbatim_prefix(path: PathBuf) -> PathBuf {
+  if !cfg!(windows) {
+    return path;
+  }
+  match path.to_str().and_then(plain_form) {
+    Some(plain) => PathBuf::from(plain),
+    None => path,
+  }
+}
+
+// The plain form of the verbatim path `path`, or `None` if it isn't one or has none.
+fn plain_form(path: &str) -> Option<String> {
+  let rest = path.strip_prefix(r"\\?\")?;
+  match rest.strip_prefix(r"UNC\") {
+    Some(unc) => Some(format!(r"\\{}", unc)),
+    None if rest.as_bytes().get(1) == Some(&b':') => Some(rest.to_string()),
+    None => None,
+  }
+}
+
+// Like `canonical_source_path`, for a path whose file may not exist, in which case only
+// its directory is resolved.
+fn canonical_override_path(path: &Path) -> PathBuf {
+  if let Ok(path) = canonicalize(path) {
+    return path;
+  }
+  match (path.parent().map(canonicalize), path.file_name()) {
+    (Some(Ok(dir)), Some(name)) => dir.join(name),
+    _ => path.to_path_buf(),
+  }
+}
+
+// The overrides of the files under the crate root's directory, i.e. those the crate's
+// modules may be read from.
+fn crate_file_overrides(options: &FlagsOptions, crate_root: &Path) -> BTreeMap<PathBuf, String> {
+  let dir = match crate_root.parent() {
+    Some(dir) => dir,
+    None => return BTreeMap::new(),
+  };
+  options
+    .file_overrides
+    .iter()
+    .map(|(path, contents)| (canonical_override_path(path), contents))
+    .filter(|(path, _)| path.starts_with(dir))
+    .map(|(path, contents)| (path, contents.clone()))
+    .collect()
+}
+
+// The manifest to load the workspace from: the one given in `options`, or else the
+// nearest one above `source_path`. Cargo finds the workspace root from there.
+fn manifest_path_for(options: &FlagsOptions, source_path: &Path) -> Result<PathBuf> {
+  if let Some(manifest_path) = &options.manifest_path {
+    return canonical_manifest_path(manifest_path);
+  }
+  match manifest::enclosing_package_dir(source_path) {
+    Some(package_dir) => canonical_manifest_path(&package_dir.join("Cargo.toml")),
+    None => bail!("No Cargo.toml found above {}", source_path.display()),
+  }
+}
+
+fn canonical_manifest_path(manifest_path: &Path) -> Result<PathBuf> {
+  canonicalize(manifest_path).with_context(|| {
+    let manifest_path = env::current_dir()
+      .map(|cwd| cwd.join(manifest_path.strip_prefix(".").unwrap_or(manifest_path)))
+      .unwrap_or_else(|_| manifest_path.to_path_buf());
+    format!("Manifest not found at {}", manifest_path.display())
+  })
+}
+
+// `near` is a file or directory in the package, for rustup to find the package's
+// toolchain file from.
+fn resolve_sysroot(options: &FlagsOptions, near: &Path) -> Result<PathBuf> {
+  let dir = if near.is_dir() {
+    near
+  } else {
+    near.parent().unwrap_or(near)
+  };
+  match &options.sysroot {
+    Some(sysroot) => Ok(sysroot.clone()),
+    None => toolchain::probe_sysroot(options.toolchain.as_deref(), dir),
+  }
+}
+
+fn message_format_flags(options: &FlagsOptions) -> Vec<RustcFlag> {
+  if options.json_artifacts {
+    vec![
+      RustcFlag::Raw("--error-format=json".into()),
+      RustcFlag::Raw("--json=artifacts,diagnostic-rendered-ansi".into()),
+    ]
+  } else {
+    Vec::new()
+  }
+}
+
+fn remap_path_prefix(from: &Path, to: &Path) -> RustcFlag {
+  RustcFlag::Raw(format!(
+    "--remap-path-prefix={}={}",
+    from.display(),
+    to.display()
+  ))
+}
+
+fn sysroot_remap_flags(options: &FlagsOptions, sysroot: &Path) -> Vec<RustcFlag> {
+  match &options.remap_sysroot {
+    Some(prefix) => vec![remap_path_prefix(sysroot, prefix)],
+    None => Vec::new(),
+  }
+}
+
+// `features` is usually `options.features`, but the feature matrix passes each
+// combination in turn.
+fn compile_options<'cfg>(
+  workspace: &Workspace<'cfg>,
+  source_path: &Path,
+  options: &FlagsOptions,
+  mode: CompileMode,
+  features: &CliFeatures,
+) -> Result<CompileOptions> {
+  let mode = match mode {
+    CompileMode::Check { .. } if options.test => CompileMode::Check { test: true },
+    CompileMode::Build if options.test => CompileMode::Test,
+    mode => mode,
+  };
+  let mut compile_opts = CompileOptions::new(workspace.config(), mode)?;
+  // The loaded package, when the file is in its directory, is what cargo's default
+  // compiles. Otherwise (e.g. in a virtual workspace) it's the member owning the file, by
+  // full spec since a dependency may share its name.
+  let package = options.package.clone().or_else(|| {
+    let current = workspace.current_opt();
+    if current.map_or(false, |pkg| source_path.starts_with(pkg.root())) {
+      return None;
+    }
+    units::owning_member(workspace, source_path)
+      .map(|pkg| PackageIdSpec::from_package_id(pkg.package_id()).to_string())
+  });
+  compile_opts.spec = match package {
+    Some(package) => Packages::Packages(vec![package]),
+    None => Packages::Default,
+  };
+  compile_opts.cli_features = features.clone();
+  if let Some(jobs) = options.jobs {
+    compile_opts.build_config.jobs = jobs;
+  }
+  if let Some(profile) = &options.profile {
+    compile_opts.build_config.requested_profile = InternedString::new(profile);
+  }
+  if let Some(target) = &options.target {
+    compile_opts.build_config.requested_kinds =
+      cargo_compat::requested_kinds(workspace.config(), target)?;
+  }
+
+  if options.enable_required_features {
+    // Which features will be active isn't known before resolving, so this enables all of
+    // the target's required features. Those already enabled stay enabled.
+    if let Some((_, required)) = units::target_missing_features(workspace, source_path, &[]) {
+      let features = &compile_opts.cli_features;
+      let mut values = (*features.features).clone();
+      values.extend(required.iter().map(|f| FeatureValue::new(InternedString::new(f))));
+      compile_opts.cli_features = CliFeatures {
+        features: Rc::new(values),
+        all_features: features.all_features,
+        uses_default_features: features.uses_default_features,
+      };
+    }
+  }
+
+  if let Some(selector) = &options.target_selector {
+    compile_opts.filter = selector.filter();
+  } else if options.lib_only {
     compile_opts.filter = CompileFilter::Only {
       all_targets: false,
       lib: LibRule::Default,
@@ -63,128 +1483,543 @@ pub fn generate_rustc_flags(
       tests: FilterRule::Just(vec![]),
       benches: FilterRule::Just(vec![]),
     };
+  } else {
+    // Examples, integration tests and benches aren't built by default, and cargo only
+    // resolves dev-dependencies when one of them is explicitly selected. Whether the
+    // package is `publish = false` doesn't matter.
+    let in_dir = |dir| manifest::is_in_package_dir(source_path, dir);
+    let rule = |selected| {
+      if selected {
+        FilterRule::All
+      } else {
+        FilterRule::Just(vec![])
+      }
+    };
+    let (examples, tests, benches) = (in_dir("examples"), in_dir("tests"), in_dir("benches"));
+    if examples || tests || benches {
+      compile_opts.filter = CompileFilter::Only {
+        all_targets: false,
+        lib: LibRule::Default,
+        bins: FilterRule::Just(vec![]),
+        examples: rule(examples),
+        tests: rule(tests),
+        benches: rule(benches),
+      };
+    }
   }
 
+  Ok(compile_opts)
+}
+
+fn package_id_spec(options: &FlagsOptions) -> Result<Option<PackageIdSpec>> {
+  Ok(
+    options
+      .package_id_spec
+      .as_deref()
+      .map(PackageIdSpec::parse)
+      .transpose()?,
+  )
+}
+
+// Resolves `workspace` for compiling in `mode` with `features` and the rest of `options`,
+// then runs `f` on the prepared cargo context and every unit of its graph, dependencies
+// first. `source_path` narrows the packages, as in `compile_options`.
+fn with_workspace_context<T>(
+  workspace: &Workspace,
+  source_path: &Path,
+  options: &FlagsOptions,
+  mode: CompileMode,
+  features: &CliFeatures,
+  f: impl FnOnce(&mut Context, &[Unit]) -> Result<T>,
+) -> Result<T> {
+  let compile_opts = compile_options(workspace, source_path, options, mode, features)?;
+
+  let interner = UnitInterner::new();
+  let bcx = timings::timed(Phase::Resolve, || {
+    create_bcx(workspace, &compile_opts, &interner)
+  })?;
+  let mut cx = timings::timed(Phase::BuildMap, || {
+    cargo_compat::prepare_context(&bcx, options.wait_for_build_lock)
+  })?;
+  let all_units = units::collect_units(&cx, &bcx.roots);
+  f(&mut cx, &all_units)
+}
+
+// `with_workspace_context` for the workspace at `manifest_path`, loaded with a config
+// made from `options`, in `options.mode` with `options.features`.
+pub(crate) fn with_context<T>(
+  options: &FlagsOptions,
+  manifest_path: &Path,
+  source_path: &Path,
+  f: impl FnOnce(&mut Context, &[Unit]) -> Result<T>,
+) -> Result<T> {
+  let config = make_config(options)?;
+  let workspace = load_workspace(options, manifest_path, &config)?;
+  let (mode, features) = (options.mode, &options.features);
+  with_workspace_context(&workspace, source_path, options, mode, features, f)
+}
+
+fn generate_in_workspace(
+  workspace: &Workspace,
+  source_path: &Path,
+  options: &FlagsOptions,
+  features: &CliFeatures,
+  sysroot: PathBuf,
+) -> Result<RustcFlags> {
+  let package_id_spec = package_id_spec(options)?;
+  let mode = options.mode;
+  with_workspace_context(workspace, source_path, options, mode, features, |cx, all_units| {
+    let target_unit = if source_path.file_name() == Some("Cargo.toml".as_ref()) {
+      units::find_default_unit(all_units, source_path)?
+    } else if let Some(selector) = &options.target_selector {
+      units::find_selected_unit(cx, all_units, source_path, selector)?
+    } else {
+      units::find_target_unit(
+        cx,
+        all_units,
+        source_path,
+        package_id_spec.as_ref(),
+        options.on_candidate.as_deref(),
+        &options.target_priority,
+      )?
+    };
+    unit_flags::unit_flags(cx, target_unit, all_units, options, &sysroot)
+  })
+}
+
+/// The features enabled on every package in the dependency graph of the workspace at
+/// `manifest_path`, as resolved for `cargo check` with `features`. Nothing is compiled.
+pub fn resolved_features(
+  manifest_path: impl AsRef<Path>,
+  features: CliFeatures,
+) -> Result<HashMap<PackageId, Vec<String>>> {
+  let options = FlagsOptions {
+    features,
+    ..FlagsOptions::default()
+  };
+  let manifest_path = canonical_manifest_path(manifest_path.as_ref())?;
+  let config = make_config(&options)?;
+  let workspace = load_workspace(&options, &manifest_path, &config)?;
+  let compile_opts = compile_options(
+    &workspace,
+    &manifest_path,
+    &options,
+    options.mode,
+    &options.features,
+  )?;
+
   let interner = UnitInterner::new();
   let bcx = create_bcx(&workspace, &compile_opts, &interner)?;
-  let mut cx = Context::new(&bcx)?;
 
-  cx.lto = lto::generate(&bcx)?;
-  cx.prepare_units()?;
-  cx.prepare()?;
-  build_map(&mut cx)?;
+  let mut resolved: HashMap<PackageId, Vec<String>> = HashMap::new();
+  for unit in bcx.unit_graph.keys() {
+    let package_features = resolved.entry(unit.pkg.package_id()).or_default();
+    for feature in &unit.features {
+      if !package_features.iter().any(|f| f == feature.as_str()) {
+        package_features.push(feature.to_string());
+      }
+    }
+  }
+  for package_features in resolved.values_mut() {
+    package_features.sort();
+  }
 
-  let all_units = bcx
-    .roots
-    .iter()
-    .map(|root| collect_units(&cx, root).into_iter())
-    .flatten()
-    .collect::<Vec<_>>();
+  Ok(resolved)
+}
 
-  let target_unit = {
-    let matches = all_units
+/// Identifies a target that [`generate_all_rustc_flags`] generated flags for.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UnitId {
+  pub package_id: PackageId,
+  pub target_name: String,
+  /// `lib`, `bin`, `example`, `test` or `bench`.
+  pub target_kind: String,
+}
+
+impl UnitId {
+  fn of(unit: &Unit) -> Self {
+    UnitId {
+      package_id: unit.pkg.package_id(),
+      target_name: unit.target.name().to_string(),
+      target_kind: unit.target.kind().description().to_string(),
+    }
+  }
+
+  fn error_context(&self) -> String {
+    format!(
+      "Could not generate flags for {} `{}`",
+      self.target_kind, self.target_name
+    )
+  }
+}
+
+/// Generates flags for every root unit of the workspace at `manifest_path`, i.e. every
+/// target `cargo check` would check with `options`, resolving the workspace once for all
+/// of them. `options.target_selector` and `options.lib_only` narrow the targets as usual.
+///
+/// The slow part, running build scripts and building missing dependencies, happens up
+/// front for all units in one job queue, so it runs in parallel (up to `options.jobs`).
+/// The flags are then assembled one unit at a time. With the `rayon` feature,
+/// `generate_all_parallel` does the file reads of that step in parallel too.
+///
+/// With `options.mode` set to `CompileMode::Doctest`, this gives the `rustdoc --test`
+/// flags of every lib with doctests, as `cargo test --doc` would pass them, including
+/// the `--extern` for the lib itself.
+pub fn generate_all_rustc_flags(
+  manifest_path: impl AsRef<Path>,
+  options: &FlagsOptions,
+) -> Result<HashMap<UnitId, RustcFlags>> {
+  let manifest_path = canonical_manifest_path(manifest_path.as_ref())?;
+  validate_output_paths(options)?;
+  let sysroot = resolve_sysroot(options, &manifest_path)?;
+
+  with_context(options, &manifest_path, &manifest_path, |cx, all_units| {
+    let bcx = cx.bcx;
+    let roots = bcx.roots.iter().collect::<Vec<_>>();
+    unit_flags::run_prerequisites(cx, &roots, all_units, options)?;
+
+    let mut all_flags = HashMap::new();
+    for unit in &bcx.roots {
+      let id = UnitId::of(unit);
+      let flags = unit_flags::prepared_unit_flags(cx, unit, all_units, options, &sysroot)
+        .with_context(|| id.error_context())?;
+      all_flags.insert(id, flags);
+    }
+    Ok(all_flags)
+  })
+}
+
+/// Like [`generate_all_rustc_flags`], but finishes the units on rayon's thread pool. The
+/// cargo context is built once and only used from the calling thread, where each unit's
+/// flags are assembled from it; the workspace's manifests (for `[lints]`) and, with
+/// `options.collect_inputs`, the dep-info files and build script outputs are then read
+/// in parallel.
+#[cfg(feature = "rayon")]
+pub fn generate_all_parallel(
+  manifest_path: impl AsRef<Path>,
+  options: &FlagsOptions,
+) -> Result<HashMap<UnitId, RustcFlags>> {
+  use rayon::prelude::*;
+
+  let manifest_path = canonical_manifest_path(manifest_path.as_ref())?;
+  validate_output_paths(options)?;
+  let sysroot = resolve_sysroot(options, &manifest_path)?;
+
+  let planned = with_context(options, &manifest_path, &manifest_path, |cx, all_units| {
+    let bcx = cx.bcx;
+    let roots = bcx.roots.iter().collect::<Vec<_>>();
+    unit_flags::run_prerequisites(cx, &roots, all_units, options)?;
+    bcx
+      .roots
       .iter()
-      .filter(|root| {
-        let unit_src_path = root.target.src_path().path().unwrap();
-        match unit_src_path.parent() {
-          Some(src_dir) => source_path.ancestors().any(|ancestor| ancestor == src_dir),
-          None => false,
-        }
+      .map(|unit| {
+        let id = UnitId::of(unit);
+        let planned = unit_flags::planned_unit_flags(cx, unit, all_units, options, &sysroot)
+          .with_context(|| id.error_context())?;
+        Ok((id, planned))
       })
-      .collect::<Vec<_>>();
+      .collect::<Result<Vec<_>>>()
+  })?;
+  planned
+    .into_par_iter()
+    .map(|(id, planned)| {
+      let flags = planned.finish().with_context(|| id.error_context())?;
+      Ok((id, flags))
+    })
+    .collect()
+}
+
+/// Generates flags for every unit that could be compiling `source_path`, for callers that
+/// would rather choose among them than have [`FlagsOptions::target_priority`] do it, e.g.
+/// to ask the user which bin a file shared by two is being edited for. The units are in
+/// the order cargo's unit graph lists them; there's at least one.
+pub fn generate_candidates(
+  source_path: impl AsRef<Path>,
+  options: &FlagsOptions,
+) -> Result<Vec<(UnitId, RustcFlags)>> {
+  let source_path = &canonical_source_path(source_path.as_ref(), &options.file_overrides)?;
+  validate_output_paths(options)?;
+  let sysroot = resolve_sysroot(options, source_path)?;
+  let package_id_spec = package_id_spec(options)?;
 
-    match matches.len() {
-      0 => bail!("Could not find unit for path {}", source_path.display()),
-      1 => matches[0],
-      _ => matches
-        .into_iter()
-        .find(|unit| {
-          unit
-            .target
-            .rustc_crate_types()
-            .iter()
-            .any(|ty| *ty == CrateType::Lib)
-        })
-        .context("No lib target w/ multiple targets")?,
+  let manifest_path = manifest_path_for(options, source_path)?;
+  with_context(options, &manifest_path, source_path, |cx, all_units| {
+    let candidates = units::candidate_units(
+      cx,
+      all_units,
+      source_path,
+      package_id_spec.as_ref(),
+      options.on_candidate.as_deref(),
+    );
+    if candidates.is_empty() {
+      // Fails with the same error (and hints) as `generate`.
+      let spec = package_id_spec.as_ref();
+      units::find_target_unit(cx, all_units, source_path, spec, None, &[])?;
     }
+    unit_flags::run_prerequisites(cx, &candidates, all_units, options)?;
+
+    candidates
+      .into_iter()
+      .map(|unit| {
+        let id = UnitId::of(unit);
+        let flags = unit_flags::prepared_unit_flags(cx, unit, all_units, options, &sysroot)
+          .with_context(|| id.error_context())?;
+        Ok((id, flags))
+      })
+      .collect()
+  })
+}
+
+/// The unit graph of the workspace at `manifest_path`, from the same cargo context
+/// [`generate_all_rustc_flags`] uses with `options`. Nothing is compiled.
+pub fn unit_graph(manifest_path: impl AsRef<Path>, options: &FlagsOptions) -> Result<UnitGraph> {
+  let manifest_path = canonical_manifest_path(manifest_path.as_ref())?;
+  with_context(options, &manifest_path, &manifest_path, |cx, all_units| {
+    Ok(unit_graph::unit_graph(cx, all_units))
+  })
+}
+
+/// The deps directory of the unit owning `source_path`, i.e. the `-L` path in its flags,
+/// without generating the rest of the flags or running build scripts.
+pub fn deps_dir_for(source_path: impl AsRef<Path>, features: CliFeatures) -> Result<PathBuf> {
+  let options = FlagsOptions {
+    features,
+    ..FlagsOptions::default()
   };
+  let source_path = &canonical_source_path(source_path.as_ref(), &options.file_overrides)?;
+  let manifest_path = manifest_path_for(&options, source_path)?;
+  with_context(&options, &manifest_path, source_path, |cx, all_units| {
+    let priority = &options.target_priority;
+    let target_unit = units::find_target_unit(cx, all_units, source_path, None, None, priority)?;
+    Ok(cx.files().layout(target_unit.kind).deps().to_path_buf())
+  })
+}
 
-  // TODO: generate these from build_base_args
-  #[rustfmt::skip]
-  let unit_flags = vec![
-    "rustc".into(),
+/// Recomputes just the environment of previously generated `flags`, e.g. after the target
+/// directory moved and `OUT_DIR` changed, without regenerating the flags themselves. The
+/// unit is found again from the flags' crate root, so `options` should be the ones the
+/// flags were generated with.
+pub fn refresh_env(flags: &RustcFlags, options: &FlagsOptions) -> Result<HashMap<String, String>> {
+  if flags.origin == FlagsOrigin::Fallback {
+    return Ok(flags.env.clone().into_iter().collect());
+  }
 
-    "--crate-name".into(), target_unit.target.crate_name(),
+  let source_path = &flags.crate_root;
+  let package_id_spec = package_id_spec(options)?;
+  let manifest_path = manifest_path_for(options, source_path)?;
+  let mut env = with_context(options, &manifest_path, source_path, |cx, all_units| {
+    let target_unit = units::find_target_unit(
+      cx,
+      all_units,
+      source_path,
+      package_id_spec.as_ref(),
+      None,
+      &options.target_priority,
+    )?;
+    unit_flags::unit_env(cx, target_unit, all_units, options)
+  })?;
+  if let Some(bootstrap) = flags.env.get("RUSTC_BOOTSTRAP") {
+    env.insert("RUSTC_BOOTSTRAP".into(), bootstrap.clone());
+  }
+  Ok(env)
+}
 
-    // TODO: what if there are multiple crate types?
-    "--crate-type".into(), target_unit.target.kind().rustc_crate_types()[0].as_str().to_string(),
+/// Whether generating flags for `source_path` would run a build script. Only the matched
+/// unit's own build script is run (along with the build scripts of its
+/// `[build-dependencies]`, which it needs), so this is `false` exactly when the unit has
+/// none. Nothing is compiled or executed.
+pub fn requires_build_script(source_path: impl AsRef<Path>, features: CliFeatures) -> Result<bool> {
+  let options = FlagsOptions {
+    features,
+    ..FlagsOptions::default()
+  };
+  let source_path = &canonical_source_path(source_path.as_ref(), &options.file_overrides)?;
+  let manifest_path = manifest_path_for(&options, source_path)?;
+  with_context(&options, &manifest_path, source_path, |cx, all_units| {
+    let priority = &options.target_priority;
+    let target_unit = units::find_target_unit(cx, all_units, source_path, None, None, priority)?;
+    Ok(cx.find_build_script_metadata(target_unit).is_some())
+  })
+}
 
-    "--sysroot".into(), sysroot,
+/// The `cargo check -v` invocation that flag generation for `source_path` emulates. Running
+/// it shows rustc's command line in its `Running` line, for comparison with ours.
+pub fn equivalent_cargo_command(
+  source_path: impl AsRef<Path>,
+  features: CliFeatures,
+) -> Result<String> {
+  let options = FlagsOptions {
+    features,
+    ..FlagsOptions::default()
+  };
+  let source_path = &canonical_source_path(source_path.as_ref(), &options.file_overrides)?;
+  let manifest_path = manifest_path_for(&options, source_path)?;
+  let unit = with_context(&options, &manifest_path, source_path, |cx, all_units| {
+    let priority = &options.target_priority;
+    let unit = units::find_target_unit(cx, all_units, source_path, None, None, priority)?;
+    Ok(unit.clone())
+  })?;
 
-    // Path must be the crate root file, NOT the sliced file
-    format!("{}", target_unit.target.src_path().path().unwrap().display()),
+  let mut command = vec!["cargo".to_string(), "check".into()];
+  command.push("-p".into());
+  command.push(unit.pkg.name().to_string());
 
-    format!("--edition={}", target_unit.target.edition()),
+  let target = &unit.target;
+  let name = target.name().to_string();
+  if target.is_lib() {
+    command.push("--lib".into());
+  } else {
+    let kind = if target.is_bin() {
+      "--bin"
+    } else if target.is_example() {
+      "--example"
+    } else if target.is_test() {
+      "--test"
+    } else if target.is_bench() {
+      "--bench"
+    } else {
+      // Build scripts are compiled for any target of the package.
+      ""
+    };
+    if !kind.is_empty() {
+      command.push(kind.into());
+      command.push(name);
+    }
+  }
 
-    "-L".into(), format!("{}", cx.files().layout(target_unit.kind).deps().display()),
+  command.extend(feature_args(&options.features));
+  command.push("-v".into());
+  Ok(command.join(" "))
+}
 
-    // Avoids ICE looking for MIR data?
-    "--emit=dep-info,metadata".into(),
-  ];
+// `features` as cargo command line arguments.
+fn feature_args(features: &CliFeatures) -> Vec<String> {
+  let mut args = Vec::new();
+  if features.all_features {
+    args.push("--all-features".into());
+  }
+  if !features.uses_default_features {
+    args.push("--no-default-features".into());
+  }
+  if !features.features.is_empty() {
+    args.push("--features".into());
+    args.push(
+      features
+        .features
+        .iter()
+        .map(|feature| feature.to_string())
+        .collect::<Vec<_>>()
+        .join(","),
+    );
+  }
+  args
+}
 
-  let feature_flags = target_unit
-    .features
-    .iter()
-    .map(|feature| vec!["--cfg".into(), format!("feature=\"{}\"", feature)])
-    .flatten();
-
-  let extern_flags = extern_args(&cx, target_unit, &mut false)?
-    .into_iter()
-    .map(|s| s.into_string().unwrap());
-
-  let pkg = &target_unit.pkg;
-  let mut env = vec![
-    ("CARGO_PKG_VERSION", pkg.version().to_string()),
-    ("CARGO_PKG_NAME", pkg.name().to_string()),
-    (
-      "CARGO_MANIFEST_DIR",
-      format!("{}", manifest_path.parent().unwrap().display()),
-    ),
-    ("CARGO_PKG_VERSION_MAJOR", pkg.version().major.to_string()),
-    ("CARGO_PKG_VERSION_MINOR", pkg.version().minor.to_string()),
-    ("CARGO_PKG_VERSION_PATCH", pkg.version().patch.to_string()),
-  ]
-  .into_iter()
-  .map(|(k, v)| (k.to_string(), v))
-  .collect::<HashMap<_, _>>();
-
-  if let Some(target_meta) = cx.find_build_script_metadata(target_unit) {
-    let build_unit = cx.find_build_script_unit(target_unit).unwrap();
-    let mut queue = JobQueue::new(&bcx);
-    let mut plan = BuildPlan::new();
-    let exec = Arc::new(DefaultExecutor) as Arc<dyn Executor>;
-    compile(&mut cx, &mut queue, &mut plan, &build_unit, &exec, false)?;
-    queue.execute(&mut cx, &mut plan)?;
-
-    env.insert(
-      "OUT_DIR".into(),
-      format!("{}", cx.files().build_script_out_dir(&build_unit).display()),
+/// Flags for compiling a library's unit tests and its doctests.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TestFlags {
+  /// Flags for the `--test` harness build of the target.
+  pub test_flags: RustcFlags,
+  /// Flags for the doctests, with `--cfg doctest`. `None` if the target has no doctests.
+  pub doctest_flags: Option<RustcFlags>,
+}
+
+/// Generates the test-harness and doctest flags for the target owning `source_path` from
+/// a single cargo context, as `cargo test` would build them. `options.mode` is ignored.
+pub fn generate_test_flags(
+  source_path: impl AsRef<Path>,
+  options: &FlagsOptions,
+) -> Result<TestFlags> {
+  let source_path = &canonical_source_path(source_path.as_ref(), &options.file_overrides)?;
+  validate_output_paths(options)?;
+  let sysroot = resolve_sysroot(options, source_path)?;
+  let package_id_spec = package_id_spec(options)?;
+
+  let config = make_config(options)?;
+  let manifest_path = manifest_path_for(options, source_path)?;
+  let workspace = load_workspace(options, &manifest_path, &config)?;
+  let (mode, features) = (CompileMode::Test, &options.features);
+  with_workspace_context(&workspace, source_path, options, mode, features, |cx, all_units| {
+    // `cargo test` adds a `Doctest` unit next to the lib's `Test` unit, so both are found
+    // in the same unit graph.
+    let candidates = units::candidate_units(
+      cx,
+      all_units,
+      source_path,
+      package_id_spec.as_ref(),
+      options.on_candidate.as_deref(),
     );
+    let test_unit = units::select_unit(
+      candidates
+        .iter()
+        .copied()
+        .filter(|unit| unit.mode == CompileMode::Test)
+        .collect(),
+      source_path,
+      package_id_spec.as_ref(),
+      &options.target_priority,
+    )?;
+    let doctest_unit = candidates
+      .iter()
+      .copied()
+      .find(|unit| unit.mode.is_doc_test() && unit.target == test_unit.target);
+
+    let test_flags = unit_flags::unit_flags(cx, test_unit, all_units, options, &sysroot)?;
+    let doctest_flags = match doctest_unit {
+      Some(unit) => Some(unit_flags::unit_flags(cx, unit, all_units, options, &sysroot)?),
+      None => None,
+    };
+
+    Ok(TestFlags {
+      test_flags,
+      doctest_flags,
+    })
+  })
+}
 
-    let outputs = cx.build_script_outputs.lock().unwrap();
-    let output = outputs.get(target_meta).unwrap();
-    env.extend(output.env.clone().into_iter());
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn verbatim_paths_have_a_plain_form() {
+    assert_eq!(plain_form(r"\\?\C:\ws\src\lib.rs").as_deref(), Some(r"C:\ws\src\lib.rs"));
+    assert_eq!(
+      plain_form(r"\\?\UNC\server\share\ws\src\lib.rs").as_deref(),
+      Some(r"\\server\share\ws\src\lib.rs")
+    );
+    assert_eq!(plain_form(r"\\?\Volume{1234}\ws"), None);
   }
 
-  for (k, v) in env {
-    env::set_var(k, v);
+  #[test]
+  fn plain_paths_are_left_alone() {
+    assert_eq!(plain_form(r"C:\ws\src\lib.rs"), None);
+    assert_eq!(plain_form(r"\\server\share\ws\src\lib.rs"), None);
+    assert_eq!(plain_form("/ws/src/lib.rs"), None);
   }
 
-  Ok(
-    unit_flags
-      .into_iter()
-      .chain(feature_flags)
-      .chain(extern_flags)
-      .collect(),
-  )
+  #[cfg(windows)]
+  #[test]
+  fn stripped_paths_match_plain_ones() {
+    let cases = [
+      (r"\\?\C:\ws\src\bin\main.rs", r"C:\ws\src"),
+      (r"\\?\UNC\server\share\ws\src\bin\main.rs", r"\\server\share\ws\src"),
+    ];
+    for (verbatim, dir) in cases {
+      // Unstripped, the prefix keeps the file out of its own directory.
+      assert!(!Path::new(verbatim).starts_with(dir), "{}", verbatim);
+      let stripped = strip_verbatim_prefix(PathBuf::from(verbatim));
+      assert!(stripped.starts_with(dir), "{}", stripped.display());
+    }
+    let volume = PathBuf::from(r"\\?\Volume{1234}\ws");
+    assert_eq!(strip_verbatim_prefix(volume.clone()), volume);
+  }
+
+  #[cfg(not(windows))]
+  #[test]
+  fn other_platforms_keep_paths_as_they_are() {
+    let path = PathBuf::from(r"\\?\C:\ws");
+    assert_eq!(strip_verbatim_prefix(path.clone()), path);
+  }
 }
@@ -0,0 +1,182 @@
+// Translates a package's `[lints]` table into lint level flags the way cargo 1.74+ does.
+// The cargo we link against predates `[lints]`, so the manifest is read directly.
+
+use crate::flags::{LintLevel, RustcFlag};
+use anyhow::{bail, Context as AnyhowContext, Result};
+use std::{cmp::Reverse, fs, path::Path};
+use toml::Value;
+
+fn read_manifest(path: &Path) -> Result<Value> {
+  let contents =
+    fs::read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?;
+  contents
+    .parse::<Value>()
+    .with_context(|| format!("Could not parse {}", path.display()))
+}
+
+fn parse_level(name: &str, level: &str) -> Result<LintLevel> {
+  Ok(match level {
+    "forbid" => LintLevel::Forbid,
+    "deny" => LintLevel::Deny,
+    "warn" => LintLevel::Warn,
+    "allow" => LintLevel::Allow,
+    _ => bail!("Unknown level `{}` for lint `{}`", level, name),
+  })
+}
+
+// A lint is either `name = "level"` or `name = { level = "...", priority = N }`.
+fn parse_lint(name: &str, config: &Value) -> Result<(LintLevel, i64)> {
+  match config {
+    Value::String(level) => Ok((parse_level(name, level)?, 0)),
+    Value::Table(table) => {
+      let level = table
+        .get("level")
+        .and_then(Value::as_str)
+        .with_context(|| format!("Lint `{}` is missing a level", name))?;
+      let priority = table.get("priority").and_then(Value::as_integer).unwrap_or(0);
+      Ok((parse_level(name, level)?, priority))
+    }
+    _ => bail!("Invalid configuration for lint `{}`", name),
+  }
+}
+
+// The flags for the lints of each tool in `tools`, e.g. `rust` and `clippy`.
+fn tool_flags(tools: &toml::value::Table) -> Result<Vec<RustcFlag>> {
+  let mut levels = Vec::new();
+  for (tool, tool_lints) in tools {
+    let tool_lints = match tool_lints.as_table() {
+      Some(tool_lints) => tool_lints,
+      None => continue,
+    };
+    for (name, config) in tool_lints {
+      let (level, priority) = parse_lint(name, config)?;
+      let flag = RustcFlag::Lint {
+        level,
+        name: if tool == "rust" {
+          name.clone()
+        } else {
+          format!("{}::{}", tool, name)
+        },
+      };
+      levels.push((priority, Reverse(name.clone()), flag.render().concat(), flag));
+    }
+  }
+
+  // Same order as cargo: lowest priority first so higher priorities override, then by
+  // lint name (without the tool) in reverse, which puts the common `all` group last, and
+  // then by the flag itself.
+  levels.sort_by(|a, b| (a.0, &a.1, &a.2).cmp(&(b.0, &b.1, &b.2)));
+
+  Ok(levels.into_iter().map(|(_, _, _, flag)| flag).collect())
+}
+
+// The package's `[lints]` table, with `[lints] workspace = true` resolved against
+// `workspace_manifest_path`.
+fn package_lints(manifest_path: &Path, workspace_manifest_path: &Path) -> Result<Option<Value>> {
+  let manifest = read_manifest(manifest_path)?;
+  let lints = match manifest.get("lints") {
+    Some(lints) => lints.clone(),
+    None => return Ok(None),
+  };
+
+  let inherit = lints.get("workspace").and_then(Value::as_bool).unwrap_or(false);
+  if !inherit {
+    return Ok(Some(lints));
+  }
+  let workspace = read_manifest(workspace_manifest_path)?;
+  match workspace.get("workspace").and_then(|ws| ws.get("lints")) {
+    Some(lints) => Ok(Some(lints.clone())),
+    None => bail!(
+      "{} inherits lints, but {} has no [workspace.lints]",
+      manifest_path.display(),
+      workspace_manifest_path.display()
+    ),
+  }
+}
+
+/// Returns the lint flags for the package at `manifest_path`, resolving
+/// `[lints] workspace = true` against `workspace_manifest_path`.
+pub(crate) fn lint_flags(
+  manifest_path: &Path,
+  workspace_manifest_path: &Path,
+) -> Result<Vec<RustcFlag>> {
+  let lints = match package_lints(manifest_path, workspace_manifest_path)? {
+    Some(lints) => lints,
+    None => return Ok(Vec::new()),
+  };
+
+  let tools = match lints.as_table() {
+    Some(tools) => tools,
+    None => bail!("[lints] in {} must be a table", manifest_path.display()),
+  };
+
+  tool_flags(tools)
+}
+
+/// The cfgs declared with `[lints.rust] unexpected_cfgs = { check-cfg = [..] }`, like
+/// `cfg(foo, values("bar"))`, which cargo 1.80+ passes on as `--check-cfg`s.
+pub(crate) fn lint_check_cfgs(
+  manifest_path: &Path,
+  workspace_manifest_path: &Path,
+) -> Result<Vec<String>> {
+  let lints = package_lints(manifest_path, workspace_manifest_path)?;
+  let check_cfgs = lints
+    .as_ref()
+    .and_then(|lints| lints.get("rust"))
+    .and_then(|rust| rust.get("unexpected_cfgs"))
+    .and_then(|lint| lint.get("check-cfg"));
+  match check_cfgs {
+    None => Ok(Vec::new()),
+    Some(Value::Array(check_cfgs)) => check_cfgs
+      .iter()
+      .map(|check_cfg| match check_cfg {
+        Value::String(check_cfg) => Ok(check_cfg.clone()),
+        _ => bail!("`check-cfg` entries in {} must be strings", manifest_path.display()),
+      })
+      .collect(),
+    Some(_) => bail!("`check-cfg` in {} must be an array", manifest_path.display()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn flags(lints: &str) -> Vec<String> {
+    let lints = lints.parse::<Value>().unwrap();
+    crate::flags::render(&tool_flags(lints.as_table().unwrap()).unwrap())
+  }
+
+  #[test]
+  fn orders_by_priority_then_name() {
+    let flags = flags(
+      r#"
+      [rust]
+      unsafe_code = "forbid"
+      missing_docs = { level = "warn", priority = 1 }
+      unused = { level = "allow", priority = -1 }
+
+      [clippy]
+      all = { level = "deny", priority = -1 }
+      pedantic = "warn"
+      "#,
+    );
+    assert_eq!(
+      flags,
+      vec![
+        "--allow=unused",
+        "--deny=clippy::all",
+        "--forbid=unsafe_code",
+        "--warn=clippy::pedantic",
+        "--warn=missing_docs",
+      ]
+    );
+  }
+
+  #[test]
+  fn rejects_unknown_levels() {
+    let lints = "[rust]\nunsafe_code = \"loud\"".parse::<Value>().unwrap();
+    let err = tool_flags(lints.as_table().unwrap()).unwrap_err();
+    assert_eq!(err.to_string(), "Unknown level `loud` for lint `unsafe_code`");
+  }
+}
@@ -0,0 +1,224 @@
+// Loading the workspace, with a tolerant path for manifests that are only broken in
+// sections irrelevant to flag generation.
+
+use crate::cargo_compat;
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use cargo::{core::Workspace, util::config::Config};
+use std::{fs, path::Path};
+
+// The directory of the nearest `Cargo.toml` above `path`.
+pub(crate) fn enclosing_package_dir(path: &Path) -> Option<&Path> {
+  path
+    .ancestors()
+    .skip(1)
+    .find(|dir| dir.join("Cargo.toml").is_file())
+}
+
+// Whether `path` is inside the `dir` directory (e.g. `examples`) of its package.
+pub(crate) fn is_in_package_dir(path: &Path, dir: &str) -> bool {
+  enclosing_package_dir(path)
+    .and_then(|package_dir| path.strip_prefix(package_dir).ok())
+    .and_then(|rel| rel.components().next())
+    .map_or(false, |first| first.as_os_str() == dir)
+}
+
+// If `path` sits where cargo would autodiscover a target, but the package disables that
+// autodiscovery, explains why no target was found for it.
+pub(crate) fn autodiscovery_hint(path: &Path) -> Option<String> {
+  let package_dir = enclosing_package_dir(path)?;
+  let rel = path.strip_prefix(package_dir).ok()?;
+  let mut components = rel.components().map(|c| c.as_os_str().to_str().unwrap_or(""));
+  let (key, kind) = match (components.next()?, components.next()) {
+    ("src", Some("bin")) => ("autobins", "bin"),
+    ("examples", _) => ("autoexamples", "example"),
+    ("tests", _) => ("autotests", "test"),
+    ("benches", _) => ("autobenches", "bench"),
+    _ => return None,
+  };
+
+  let manifest_path = package_dir.join("Cargo.toml");
+  let manifest = fs::read_to_string(&manifest_path)
+    .ok()?
+    .parse::<toml::Value>()
+    .ok()?;
+  let enabled = manifest
+    .get("package")
+    .and_then(|package| package.get(key))
+    .and_then(toml::Value::as_bool)
+    .unwrap_or(true);
+  if enabled {
+    return None;
+  }
+
+  Some(format!(
+    "{} sets `{} = false`, so {} is not discovered automatically; declare it in a \
+     [[{}]] section to generate flags for it",
+    manifest_path.display(),
+    key,
+    path.display(),
+    kind
+  ))
+}
+
+// Sections that never influence the generated flags.
+fn is_ignorable_section(header: &str) -> bool {
+  let name = header.trim_matches(|c| c == '[' || c == ']').trim();
+  name == "badges"
+    || name.starts_with("badges.")
+    || name == "package.metadata"
+    || name.starts_with("package.metadata.")
+    || name == "workspace.metadata"
+    || name.starts_with("workspace.metadata.")
+}
+
+fn section_header(line: &str) -> Option<&str> {
+  let line = line.trim();
+  if line.starts_with('[') {
+    let header = line.split_once('#').map_or(line, |(header, _)| header);
+    Some(header.trim())
+  } else {
+    None
+  }
+}
+
+// Removes ignorable sections line by line, so that it works even when their contents
+// don't parse.
+fn strip_ignorable_sections(contents: &str) -> String {
+  let mut skipping = false;
+  let mut stripped = String::new();
+  for line in contents.lines() {
+    if let Some(header) = section_header(line) {
+      skipping = is_ignorable_section(header);
+    }
+    if !skipping {
+      stripped.push_str(line);
+      stripped.push('\n');
+    }
+  }
+  stripped
+}
+
+// Names the section containing a TOML syntax error, if there is one.
+fn locate_syntax_error(contents: &str) -> Option<String> {
+  let err = contents.parse::<toml::Value>().err()?;
+  let (line, _) = err.line_col()?;
+  let section = contents
+    .lines()
+    .take(line + 1)
+    .filter_map(section_header)
+    .last()
+    .unwrap_or("top level");
+  Some(format!(
+    "syntax error in section {} (line {}): {}",
+    section,
+    line + 1,
+    err
+  ))
+}
+
+fn declares_workspace(contents: &str) -> bool {
+  contents
+    .parse::<toml::Value>()
+    .map(|manifest| manifest.get("workspace").is_some())
+    .unwrap_or(false)
+}
+
+fn in_parent_workspace(manifest_path: &Path) -> bool {
+  let package_dir = match manifest_path.parent() {
+    Some(dir) => dir,
+    None => return false,
+  };
+  package_dir.ancestors().skip(1).any(|dir| {
+    fs::read_to_string(dir.join("Cargo.toml")).map_or(false, |contents| {
+      contents
+        .lines()
+        .filter_map(section_header)
+        .any(|header| header == "[workspace]")
+    })
+  })
+}
+
+pub(crate) fn load_workspace<'cfg>(
+  manifest_path: &Path,
+  config: &'cfg Config,
+  tolerant: bool,
+) -> Result<Workspace<'cfg>> {
+  let err = match Workspace::new(manifest_path, config) {
+    Ok(workspace) => return Ok(workspace),
+    Err(err) => anyhow!(err),
+  };
+
+  let contents = match fs::read_to_string(manifest_path) {
+    Ok(contents) => contents,
+    Err(_) => return Err(err),
+  };
+
+  // An ephemeral workspace only sees this manifest, so it would drop the other members of a
+  // workspace rooted here, and any `[patch]`/`[replace]` declared by an enclosing root.
+  if tolerant && !in_parent_workspace(manifest_path) {
+    let stripped = strip_ignorable_sections(&contents);
+    if stripped != contents && !declares_workspace(&stripped) {
+      if let Ok(workspace) = cargo_compat::ephemeral_workspace(manifest_path, &stripped, config) {
+        return Ok(workspace);
+      }
+    }
+  }
+
+  match locate_syntax_error(&contents) {
+    Some(location) => Err(err).with_context(|| {
+      format!(
+        "Could not load {}: {}",
+        manifest_path.display(),
+        location
+      )
+    }),
+    None => Err(err),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const BROKEN: &str = "\
+[package]
+name = \"foo\"
+version = \"0.1.0\"
+
+[package.metadata.docs]
+this is = not toml [
+
+[dependencies]
+bar = \"1\"
+";
+
+  #[test]
+  fn strips_metadata_sections_that_dont_parse() {
+    let stripped = strip_ignorable_sections(BROKEN);
+    assert!(!stripped.contains("not toml"));
+    assert!(stripped.contains("[dependencies]\nbar = \"1\""));
+    let manifest = stripped.parse::<toml::Value>().unwrap();
+    assert_eq!(manifest["package"]["name"].as_str(), Some("foo"));
+    assert!(!declares_workspace(&stripped));
+  }
+
+  #[test]
+  fn keeps_sections_that_matter() {
+    let contents = "[badges.x]\na = 1\n[[bin]] # comment\nname = \"b\"\n[workspace]\n";
+    let stripped = strip_ignorable_sections(contents);
+    assert_eq!(stripped, "[[bin]] # comment\nname = \"b\"\n[workspace]\n");
+    assert!(declares_workspace(&stripped));
+  }
+
+  #[test]
+  fn locates_syntax_errors() {
+    let message = locate_syntax_error(BROKEN).unwrap();
+    assert!(
+      message.starts_with("syntax error in section [package.metadata.docs] (line 6)"),
+      "{}",
+      message
+    );
+    assert!(locate_syntax_error("x = [\n").unwrap().contains("section top level"));
+    assert_eq!(locate_syntax_error("[package]\nname = \"foo\"\n"), None);
+  }
+}
@@ -0,0 +1,94 @@
+// The manifest-derived part of a unit's environment, kept structured alongside the flat
+// `RustcFlags::env` so that callers can adjust it.
+
+use cargo::core::Package;
+use std::{collections::BTreeMap, path::PathBuf};
+
+/// The `CARGO_PKG_*` and `CARGO_MANIFEST_*` variables of a package, as fields. Change them
+/// (or add to [`PackageEnv::extra`]) and apply them with [`crate::RustcFlags::set_package_env`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PackageEnv {
+  pub name: String,
+  /// The full version, e.g. `1.2.3-beta.1+abc`.
+  pub version: String,
+  pub version_major: u64,
+  pub version_minor: u64,
+  pub version_patch: u64,
+  /// The pre-release part of the version, e.g. `beta.1`, or empty.
+  pub version_pre: String,
+  /// The build metadata part of the version, e.g. `abc`, or empty. Cargo has no variable
+  /// for it; it's only part of `CARGO_PKG_VERSION`.
+  pub version_build: String,
+  pub authors: Vec<String>,
+  pub description: Option<String>,
+  pub homepage: Option<String>,
+  pub repository: Option<String>,
+  pub license: Option<String>,
+  pub license_file: Option<String>,
+  /// The `readme` path, as written in the manifest.
+  pub readme: Option<String>,
+  pub rust_version: Option<String>,
+  pub manifest_dir: PathBuf,
+  pub links: Option<String>,
+  /// More variables to set alongside, e.g. ones a custom build system provides.
+  pub extra: BTreeMap<String, String>,
+}
+
+impl PackageEnv {
+  pub(crate) fn new(pkg: &Package) -> Self {
+    let version = pkg.version();
+    let metadata = pkg.manifest().metadata();
+    PackageEnv {
+      name: pkg.name().to_string(),
+      version: version.to_string(),
+      version_major: version.major,
+      version_minor: version.minor,
+      version_patch: version.patch,
+      version_pre: version.pre.as_str().to_string(),
+      version_build: version.build.as_str().to_string(),
+      authors: metadata.authors.clone(),
+      description: metadata.description.clone(),
+      homepage: metadata.homepage.clone(),
+      repository: metadata.repository.clone(),
+      license: metadata.license.clone(),
+      license_file: metadata.license_file.clone(),
+      readme: metadata.readme.clone(),
+      rust_version: pkg.rust_version().map(str::to_string),
+      manifest_dir: pkg.root().to_path_buf(),
+      links: pkg.manifest().links().map(str::to_string),
+      extra: BTreeMap::new(),
+    }
+  }
+
+  /// The variables as cargo sets them: optional values it always sets are empty when
+  /// missing, while `CARGO_MANIFEST_LINKS` is only set with `links`.
+  pub fn vars(&self) -> BTreeMap<String, String> {
+    let or_empty = |value: &Option<String>| value.clone().unwrap_or_default();
+    let mut vars = vec![
+      ("CARGO_PKG_NAME", self.name.clone()),
+      ("CARGO_PKG_VERSION", self.version.clone()),
+      ("CARGO_PKG_VERSION_MAJOR", self.version_major.to_string()),
+      ("CARGO_PKG_VERSION_MINOR", self.version_minor.to_string()),
+      ("CARGO_PKG_VERSION_PATCH", self.version_patch.to_string()),
+      ("CARGO_PKG_VERSION_PRE", self.version_pre.clone()),
+      ("CARGO_PKG_AUTHORS", self.authors.join(":")),
+      ("CARGO_PKG_DESCRIPTION", or_empty(&self.description)),
+      ("CARGO_PKG_HOMEPAGE", or_empty(&self.homepage)),
+      ("CARGO_PKG_REPOSITORY", or_empty(&self.repository)),
+      ("CARGO_PKG_LICENSE", or_empty(&self.license)),
+      ("CARGO_PKG_LICENSE_FILE", or_empty(&self.license_file)),
+      ("CARGO_PKG_README", or_empty(&self.readme)),
+      ("CARGO_PKG_RUST_VERSION", or_empty(&self.rust_version)),
+      ("CARGO_MANIFEST_DIR", format!("{}", self.manifest_dir.display())),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v))
+    .collect::<BTreeMap<_, _>>();
+    if let Some(links) = &self.links {
+      vars.insert("CARGO_MANIFEST_LINKS".into(), links.clone());
+    }
+    vars.extend(self.extra.clone());
+    vars
+  }
+}
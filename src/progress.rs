@@ -0,0 +1,68 @@
+// Progress reporting for the slow parts of flag generation, which cargo would otherwise
+// narrate on the inherited stderr.
+
+use std::io::{self, Write};
+use std::sync::Arc;
+
+/// Something slow that flag generation is starting on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProgressEvent {
+  /// Loading the workspace and resolving its dependencies and features.
+  ResolvingWorkspace,
+  /// Compiling the build script of the named package, which hasn't been compiled in the
+  /// target directory yet.
+  CompilingBuildScript(String),
+  /// Running the build script of the named package, unless cargo finds its output fresh.
+  RunningBuildScript(String),
+  /// A line cargo printed, like `Compiling foo v0.1.0` or a warning.
+  Message(String),
+}
+
+/// Receives [`ProgressEvent`]s, possibly from cargo's worker threads. Implemented for
+/// closures taking the event.
+pub trait FlagsProgress: Send + Sync {
+  fn on_event(&self, event: ProgressEvent);
+}
+
+impl<F: Fn(ProgressEvent) + Send + Sync> FlagsProgress for F {
+  fn on_event(&self, event: ProgressEvent) {
+    self(event)
+  }
+}
+
+// Cargo's shell output, forwarded as one `ProgressEvent::Message` per line.
+pub(crate) struct ProgressWriter {
+  progress: Arc<dyn FlagsProgress>,
+  line: Vec<u8>,
+}
+
+impl ProgressWriter {
+  pub(crate) fn new(progress: Arc<dyn FlagsProgress>) -> Self {
+    ProgressWriter {
+      progress,
+      line: Vec::new(),
+    }
+  }
+}
+
+impl Write for ProgressWriter {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    for &byte in buf {
+      if byte == b'\n' {
+        let line = String::from_utf8_lossy(&self.line).trim().to_string();
+        self.line.clear();
+        if !line.is_empty() {
+          self.progress.on_event(ProgressEvent::Message(line));
+        }
+      } else {
+        self.line.push(byte);
+      }
+    }
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
@@ -0,0 +1,74 @@
+//! Output for rust-analyzer's `overrideCommand` settings.
+//!
+//! rust-analyzer reads cargo's `--message-format=json` stream from these commands. For each
+//! generated flag set, [`messages`] produces one line of JSON of the form
+//!
+//! ```json
+//! {
+//!   "reason": "build-script-executed",
+//!   "package_id": "foo 0.1.0 (path+file:///path/to/foo)",
+//!   "linked_libs": [],
+//!   "linked_paths": [],
+//!   "cfgs": ["feature=\"default\"", "test"],
+//!   "env": [["CARGO_PKG_NAME", "foo"], ["OUT_DIR", "/path/to/out"]],
+//!   "out_dir": "/path/to/out"
+//! }
+//! ```
+//!
+//! `cfgs` holds every `--cfg` of the flag set, `env` is sorted by variable name, and
+//! `out_dir` is `null` when the crate has no build script. Fallback flags have no package
+//! and produce no message.
+
+use crate::{RustcFlag, RustcFlags};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct BuildScriptExecuted<'a> {
+  reason: &'static str,
+  package_id: &'a str,
+  linked_libs: Vec<String>,
+  linked_paths: Vec<String>,
+  cfgs: Vec<&'a str>,
+  env: Vec<(&'a str, &'a str)>,
+  out_dir: Option<&'a str>,
+}
+
+fn message(flags: &RustcFlags) -> Option<String> {
+  let package_id = flags.package_id.as_deref()?;
+
+  let cfgs = flags
+    .flags
+    .iter()
+    .filter_map(|flag| match flag {
+      RustcFlag::Cfg(cfg) => Some(cfg.as_str()),
+      _ => None,
+    })
+    .collect();
+
+  let mut env = flags
+    .env
+    .iter()
+    .map(|(k, v)| (k.as_str(), v.as_str()))
+    .collect::<Vec<_>>();
+  env.sort_unstable();
+
+  let message = BuildScriptExecuted {
+    reason: "build-script-executed",
+    package_id,
+    linked_libs: Vec::new(),
+    linked_paths: Vec::new(),
+    cfgs,
+    env,
+    out_dir: flags.env.get("OUT_DIR").map(String::as_str),
+  };
+  Some(serde_json::to_string(&message).unwrap())
+}
+
+/// Renders flag sets as newline-delimited cargo JSON messages.
+pub fn messages<'a>(flags: impl IntoIterator<Item = &'a RustcFlags>) -> String {
+  flags
+    .into_iter()
+    .filter_map(message)
+    .map(|line| line + "\n")
+    .collect()
+}
@@ -0,0 +1,117 @@
+// Per-phase timings of flag generation. The phases are spread over several functions, so
+// they're collected in a thread local rather than threaded through every signature; cargo
+// does all of its work for a call on the calling thread anyway.
+
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
+
+/// How long each phase of generating flags took, with [`crate::FlagsOptions::timings`].
+/// Phases that didn't run (e.g. build scripts for fallback flags) are zero.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Timings {
+  /// Probing rustc for the sysroot, which is cached after the first probe per toolchain.
+  pub sysroot: Duration,
+  /// Setting up cargo's config and reading the workspace's manifests.
+  pub workspace_load: Duration,
+  /// Resolving dependencies and features, and building the unit graph.
+  pub resolve: Duration,
+  /// Preparing the target directory layout and cargo's map of build scripts.
+  pub build_map: Duration,
+  /// Compiling and running build scripts, and compiling missing dependencies.
+  pub build_scripts: Duration,
+  /// Assembling the `--extern` arguments.
+  pub externs: Duration,
+  /// The whole call, including what isn't broken out above.
+  pub total: Duration,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Phase {
+  Sysroot,
+  WorkspaceLoad,
+  Resolve,
+  BuildMap,
+  BuildScripts,
+  Externs,
+}
+
+thread_local! {
+  static CURRENT: RefCell<Option<Timings>> = RefCell::new(None);
+}
+
+// Runs `f`, adding its duration to `phase` if timings are being collected.
+pub(crate) fn timed<T>(phase: Phase, f: impl FnOnce() -> T) -> T {
+  let start = Instant::now();
+  let result = f();
+  let elapsed = start.elapsed();
+  CURRENT.with(|current| {
+    if let Some(timings) = current.borrow_mut().as_mut() {
+      let total = match phase {
+        Phase::Sysroot => &mut timings.sysroot,
+        Phase::WorkspaceLoad => &mut timings.workspace_load,
+        Phase::Resolve => &mut timings.resolve,
+        Phase::BuildMap => &mut timings.build_map,
+        Phase::BuildScripts => &mut timings.build_scripts,
+        Phase::Externs => &mut timings.externs,
+      };
+      *total += elapsed;
+    }
+  });
+  result
+}
+
+// Collects timings on this thread until it's finished or dropped, e.g. on an error.
+pub(crate) struct Collection {
+  start: Instant,
+}
+
+impl Collection {
+  pub(crate) fn start(enabled: bool) -> Option<Collection> {
+    if !enabled {
+      return None;
+    }
+    CURRENT.with(|current| *current.borrow_mut() = Some(Timings::default()));
+    Some(Collection {
+      start: Instant::now(),
+    })
+  }
+
+  pub(crate) fn finish(self) -> Timings {
+    let mut timings = CURRENT
+      .with(|current| current.borrow_mut().take())
+      .unwrap_or_default();
+    timings.total = self.start.elapsed();
+    timings
+  }
+}
+
+impl Drop for Collection {
+  fn drop(&mut self) {
+    CURRENT.with(|current| *current.borrow_mut() = None);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn only_collections_record() {
+    timed(Phase::Resolve, || std::thread::sleep(Duration::from_millis(1)));
+    let collection = Collection::start(true).unwrap();
+    timed(Phase::Externs, || std::thread::sleep(Duration::from_millis(1)));
+    let timings = collection.finish();
+    assert_eq!(timings.resolve, Duration::ZERO);
+    assert!(timings.externs >= Duration::from_millis(1));
+    assert!(timings.total >= timings.externs);
+    assert!(Collection::start(false).is_none());
+  }
+
+  #[test]
+  fn dropping_a_collection_stops_it() {
+    drop(Collection::start(true));
+    timed(Phase::Sysroot, || ());
+    assert!(CURRENT.with(|current| current.borrow().is_none()));
+  }
+}
@@ -0,0 +1,178 @@
+use crate::FlagsOptions;
+use anyhow::{bail, Context as AnyhowContext, Result};
+use std::{
+  collections::HashMap,
+  env,
+  path::{Path, PathBuf},
+  process::Command,
+  sync::Mutex,
+};
+
+// Probe results by rustc, toolchain and directory (which picks up `rust-toolchain` files).
+type ProbeKey = (PathBuf, Option<String>, PathBuf);
+
+lazy_static::lazy_static! {
+  static ref SYSROOTS: Mutex<HashMap<ProbeKey, PathBuf>> = Mutex::new(HashMap::new());
+  static ref VERSIONS: Mutex<HashMap<ProbeKey, String>> = Mutex::new(HashMap::new());
+}
+
+fn probe_key(toolchain: Option<&str>, dir: &Path) -> ProbeKey {
+  (rustc_path(), toolchain.map(str::to_string), dir.to_path_buf())
+}
+
+/// The toolchain flags are generated for, queried from rustc once per process and
+/// toolchain, and shared with flag generation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolchainInfo {
+  pub sysroot: PathBuf,
+  /// The triple rustc runs on, e.g. `x86_64-unknown-linux-gnu`.
+  pub host: String,
+  /// [`FlagsOptions::target`], or the host when it isn't set. `build.target` in cargo's
+  /// config isn't consulted.
+  pub target: String,
+}
+
+impl ToolchainInfo {
+  /// Queries the toolchain a package near `near` (a file or directory in it) builds with,
+  /// honoring [`FlagsOptions::sysroot`], [`FlagsOptions::toolchain`] and
+  /// [`FlagsOptions::target`].
+  pub fn query(options: &FlagsOptions, near: impl AsRef<Path>) -> Result<ToolchainInfo> {
+    let near = near.as_ref();
+    let dir = if near.is_dir() {
+      near
+    } else {
+      near.parent().unwrap_or(near)
+    };
+    let toolchain = options.toolchain.as_deref();
+    let sysroot = match &options.sysroot {
+      Some(sysroot) => sysroot.clone(),
+      None => probe_sysroot(toolchain, dir)?,
+    };
+    let host = probe_host(toolchain, dir)?;
+    Ok(ToolchainInfo {
+      sysroot,
+      target: options.target.clone().unwrap_or_else(|| host.clone()),
+      host,
+    })
+  }
+}
+
+fn probe_host(toolchain: Option<&str>, dir: &Path) -> Result<String> {
+  let version = probe_version(toolchain, dir)?;
+  match version.lines().find_map(|line| line.strip_prefix("host: ")) {
+    Some(host) => Ok(host.trim().to_string()),
+    None => bail!("`{} -vV` did not print a host triple", rustc_path().display()),
+  }
+}
+
+// The output of `rustc -vV`, which identifies the toolchain down to its commit.
+pub(crate) fn probe_version(toolchain: Option<&str>, dir: &Path) -> Result<String> {
+  let key = probe_key(toolchain, dir);
+  if let Some(version) = VERSIONS.lock().unwrap().get(&key) {
+    return Ok(version.clone());
+  }
+
+  let rustc = &key.0;
+  let output = rustc_command(rustc, toolchain, dir)
+    .arg("-vV")
+    .output()
+    .with_context(|| format!("Could not run `{} -vV`", rustc.display()))?;
+  if !output.status.success() {
+    bail!(
+      "`{} -vV` exited with {}: {}",
+      rustc.display(),
+      output.status,
+      String::from_utf8_lossy(&output.stderr).trim()
+    );
+  }
+  let version = String::from_utf8_lossy(&output.stdout).into_owned();
+  VERSIONS.lock().unwrap().insert(key, version.clone());
+  Ok(version)
+}
+
+pub(crate) fn rustc_path() -> PathBuf {
+  env::var_os("RUSTC").map_or_else(|| PathBuf::from("rustc"), PathBuf::from)
+}
+
+// Runs rustc in `dir`, through `rustup run` if a toolchain is given.
+fn rustc_command(rustc: &Path, toolchain: Option<&str>, dir: &Path) -> Command {
+  let mut command = match toolchain {
+    Some(toolchain) => {
+      let mut command = Command::new("rustup");
+      command.args(&["run", toolchain]).arg(rustc);
+      command
+    }
+    None => Command::new(rustc),
+  };
+  command.current_dir(dir);
+  command
+}
+
+fn print_sysroot(rustc: &Path, toolchain: Option<&str>, dir: &Path) -> Result<PathBuf> {
+  let output = rustc_command(rustc, toolchain, dir)
+    .args(&["--print", "sysroot"])
+    .output()?;
+  if !output.status.success() {
+    bail!(
+      "`{} --print sysroot` exited with {}: {}",
+      rustc.display(),
+      output.status,
+      String::from_utf8_lossy(&output.stderr).trim()
+    );
+  }
+  let sysroot = String::from_utf8(output.stdout)?;
+  let sysroot = sysroot.trim();
+  if sysroot.is_empty() {
+    bail!("`{} --print sysroot` printed nothing", rustc.display());
+  }
+  Ok(PathBuf::from(sysroot))
+}
+
+// `rustup which rustc` gives `<sysroot>/bin/rustc`.
+fn rustup_sysroot(toolchain: Option<&str>, dir: &Path) -> Option<PathBuf> {
+  let mut command = Command::new("rustup");
+  command.args(&["which", "rustc"]).current_dir(dir);
+  if let Some(toolchain) = toolchain {
+    command.args(&["--toolchain", toolchain]);
+  }
+  let output = command.output().ok()?;
+  if !output.status.success() {
+    return None;
+  }
+  let rustc = PathBuf::from(String::from_utf8(output.stdout).ok()?.trim());
+  Some(rustc.parent()?.parent()?.to_path_buf())
+}
+
+/// Finds the sysroot by asking rustc, falling back to the `SYSROOT`/`RUST_SYSROOT`
+/// environment variables and then rustup for drivers that don't support `--print sysroot`.
+/// rustc runs in `dir`, so that rustup picks the toolchain of the package there. Only
+/// what rustc printed is cached.
+pub(crate) fn probe_sysroot(toolchain: Option<&str>, dir: &Path) -> Result<PathBuf> {
+  let key = probe_key(toolchain, dir);
+  if let Some(sysroot) = SYSROOTS.lock().unwrap().get(&key) {
+    return Ok(sysroot.clone());
+  }
+  let probe_error = match print_sysroot(&key.0, toolchain, dir) {
+    Ok(sysroot) => {
+      SYSROOTS.lock().unwrap().insert(key, sysroot.clone());
+      return Ok(sysroot);
+    }
+    Err(err) => err,
+  };
+
+  for var in &["SYSROOT", "RUST_SYSROOT"] {
+    if let Some(sysroot) = env::var_os(var).filter(|s| !s.is_empty()) {
+      return Ok(PathBuf::from(sysroot));
+    }
+  }
+
+  if let Some(sysroot) = rustup_sysroot(toolchain, dir) {
+    return Ok(sysroot);
+  }
+
+  bail!(
+    "Could not determine the sysroot: {}. Set the `sysroot` option, or the SYSROOT \
+     environment variable, to the output of `rustc --print sysroot`",
+    probe_error
+  )
+}
@@ -0,0 +1,108 @@
+// Path remapping like cargo's `trim-paths` profile setting (`-Z trim-paths`). The cargo we
+// link against predates it, so the profile is read from the workspace manifest directly.
+
+use crate::flags::RustcFlag;
+use anyhow::{bail, Context as AnyhowContext, Result};
+use cargo::core::compiler::{Context, Unit};
+use std::{fs, path::Path};
+use toml::Value;
+
+// Profiles cargo defines without being declared, and what they inherit from.
+fn builtin_parent(profile: &str) -> Option<&'static str> {
+  match profile {
+    "test" => Some("dev"),
+    "bench" => Some("release"),
+    _ => None,
+  }
+}
+
+/// The `-Z remap-path-scope` value of `profile`'s `trim-paths` in the workspace manifest,
+/// following `inherits`, or `None` if it's unset or disables trimming.
+pub(crate) fn profile_trim_paths(ws_manifest: &Path, profile: &str) -> Result<Option<String>> {
+  let manifest = fs::read_to_string(ws_manifest)
+    .with_context(|| format!("Could not read {}", ws_manifest.display()))?
+    .parse::<Value>()
+    .with_context(|| format!("Could not parse {}", ws_manifest.display()))?;
+  let profiles = manifest.get("profile");
+
+  let mut name = profile.to_string();
+  // Bounded, in case of an `inherits` cycle.
+  for _ in 0..16 {
+    let table = profiles.and_then(|profiles| profiles.get(&name));
+    match table.and_then(|table| table.get("trim-paths")) {
+      Some(Value::Boolean(true)) => return Ok(Some("all".into())),
+      Some(Value::Boolean(false)) => return Ok(None),
+      Some(Value::String(scope)) if scope == "none" => return Ok(None),
+      Some(Value::String(scope)) => return Ok(Some(scope.clone())),
+      Some(Value::Array(scopes)) if scopes.is_empty() => return Ok(None),
+      Some(Value::Array(scopes)) => {
+        let scopes = scopes
+          .iter()
+          .map(|scope| scope.as_str().map(str::to_string))
+          .collect::<Option<Vec<_>>>();
+        match scopes {
+          Some(scopes) => return Ok(Some(scopes.join(","))),
+          None => bail!("`trim-paths` of profile `{}` must list strings", name),
+        }
+      }
+      Some(_) => bail!("Invalid `trim-paths` for profile `{}`", name),
+      None => {}
+    }
+    let parent = table
+      .and_then(|table| table.get("inherits"))
+      .and_then(Value::as_str)
+      .or_else(|| builtin_parent(&name));
+    match parent {
+      Some(parent) => name = parent.to_string(),
+      None => return Ok(None),
+    }
+  }
+  Ok(None)
+}
+
+/// The flags cargo passes for `trim-paths` with `scope`: the scope itself, then remaps of
+/// the unit's package root (to `.` for workspace members, to `<name>-<version>` for
+/// other path dependencies, and to the package directory for registry and git sources)
+/// and of the sysroot's std sources to `/rustc/<commit>`.
+pub(crate) fn trim_paths_flags(
+  cx: &Context,
+  unit: &Unit,
+  sysroot: &Path,
+  scope: &str,
+) -> Vec<RustcFlag> {
+  let mut flags = vec![RustcFlag::Unstable {
+    key: "remap-path-scope".into(),
+    value: Some(scope.into()),
+  }];
+
+  let pkg_root = unit.pkg.root();
+  let ws_root = cx.bcx.ws.root();
+  let source_id = unit.pkg.package_id().source_id();
+  let package_remap = if source_id.is_git() {
+    let checkouts = cx.bcx.config.git_checkouts_path();
+    format!("{}=", checkouts.as_path_unlocked().display())
+  } else if source_id.is_registry() {
+    let parent = pkg_root.parent().unwrap_or(pkg_root);
+    format!("{}=", parent.display())
+  } else if pkg_root.starts_with(ws_root) {
+    format!("{}=.", ws_root.display())
+  } else {
+    format!("{}={}-{}", pkg_root.display(), unit.pkg.name(), unit.pkg.version())
+  };
+  flags.push(RustcFlag::Raw(format!("--remap-path-prefix={}", package_remap)));
+
+  let rustc = cx.bcx.rustc();
+  let commit = rustc
+    .verbose_version
+    .lines()
+    .find_map(|line| line.strip_prefix("commit-hash: "))
+    .filter(|hash| *hash != "unknown")
+    .map(str::to_string)
+    .unwrap_or_else(|| rustc.version.to_string());
+  flags.push(RustcFlag::Raw(format!(
+    "--remap-path-prefix={}={}",
+    sysroot.join("lib/rustlib/src/rust").display(),
+    Path::new("/rustc").join(commit).display()
+  )));
+  flags
+}
@@ -0,0 +1,1273 @@
+// Assembles the flags and environment for a single unit.
+
+use crate::{
+  artifacts, cargo_compat, dep_info, lints, parse_dep_info,
+  timings::{self, Phase},
+  trim_paths, units, BuildScriptMode, CargoCompat, ExternDep, FlagsOptions, FlagsOrigin,
+  GenerateFlagsError, Incremental, PackageEnv, ProfileInfo, ProgressEvent, RustcFlag,
+  RustcFlags, SymbolManglingVersion, UnitInputs,
+};
+use anyhow::{bail, Context as AnyhowContext, Result};
+use cargo::core::{
+  compiler::{CompileKind, CompileMode, Context, CrateType, Unit},
+  profiles::{Lto, PanicStrategy, Profile, Strip},
+  Package,
+};
+use semver::Version;
+use std::{
+  collections::HashMap,
+  env, fs,
+  path::{Path, PathBuf},
+};
+
+// Finds every value of `-C <key>=<value>` in a rustflags list, in order, accepting all
+// the spellings rustc does.
+fn codegen_flag_values(args: &[String], key: &str) -> Vec<String> {
+  let mut found = Vec::new();
+  let mut iter = args.iter();
+  while let Some(arg) = iter.next() {
+    let value = if arg == "-C" || arg == "--codegen" {
+      iter.next().map(String::as_str)
+    } else if let Some(rest) = arg.strip_prefix("--codegen=") {
+      Some(rest)
+    } else {
+      arg.strip_prefix("-C")
+    };
+
+    if let Some((k, v)) = value.and_then(|value| value.split_once('=')) {
+      if k == key {
+        found.push(v.to_string());
+      }
+    }
+  }
+  found
+}
+
+// The value of a codegen flag, where the last occurrence wins as it does in rustc.
+fn find_codegen_flag(args: &[String], key: &str) -> Option<String> {
+  codegen_flag_values(args, key).pop()
+}
+
+// rustc enables debug assertions (and with them overflow checks) by default only at
+// opt-level 0. Like cargo, only emit the settings that differ from what rustc infers from
+// the opt-level we pass.
+fn assertion_flags(profile: &Profile, emitted_opt_level: &str) -> Vec<RustcFlag> {
+  let mut flags = Vec::new();
+  let debug_assertions = profile.debug_assertions;
+  let overflow_checks = profile.overflow_checks;
+  if emitted_opt_level != "0" {
+    if debug_assertions {
+      flags.push(RustcFlag::codegen("debug-assertions", "on"));
+      if !overflow_checks {
+        flags.push(RustcFlag::codegen("overflow-checks", "off"));
+      }
+    } else if overflow_checks {
+      flags.push(RustcFlag::codegen("overflow-checks", "on"));
+    }
+  } else if !debug_assertions {
+    flags.push(RustcFlag::codegen("debug-assertions", "off"));
+    if overflow_checks {
+      flags.push(RustcFlag::codegen("overflow-checks", "on"));
+    }
+  } else if !overflow_checks {
+    flags.push(RustcFlag::codegen("overflow-checks", "off"));
+  }
+  flags
+}
+
+fn rustc_version(cx: &Context, options: &FlagsOptions) -> Version {
+  options
+    .rustc_version
+    .clone()
+    .unwrap_or_else(|| cx.bcx.rustc().version.clone())
+}
+
+// Nightly and locally built rustcs accept unstable options without `RUSTC_BOOTSTRAP`.
+fn is_nightly(version: &Version) -> bool {
+  version.pre.as_str().contains("nightly") || version.pre.as_str().contains("dev")
+}
+
+// The first stable rustc release supporting each edition.
+fn edition_stable_since(edition: &str) -> Option<Version> {
+  match edition {
+    "2015" => Some(Version::new(1, 0, 0)),
+    "2018" => Some(Version::new(1, 31, 0)),
+    "2021" => Some(Version::new(1, 56, 0)),
+    "2024" => Some(Version::new(1, 85, 0)),
+    _ => None,
+  }
+}
+
+// Whether an edition needs `-Z unstable-options` on this rustc, erroring if it can't be
+// used at all without opting into `RUSTC_BOOTSTRAP`.
+fn edition_is_unstable(edition: &str, version: &Version, options: &FlagsOptions) -> Result<bool> {
+  let stable = match edition_stable_since(edition) {
+    Some(since) => *version >= since,
+    None => false,
+  };
+  if stable {
+    return Ok(false);
+  }
+
+  if !is_nightly(version) && !options.bootstrap_unstable_editions {
+    bail!(
+      "Edition {} is not stable on rustc {}. Use a newer or nightly toolchain, lower the \
+       package's edition, or set `bootstrap_unstable_editions` to build it with \
+       RUSTC_BOOTSTRAP=1",
+      edition,
+      version
+    );
+  }
+  Ok(true)
+}
+
+// Cargo silently skips build script output lines that aren't UTF-8, so a
+// `cargo:rustc-env` with a non-UTF-8 value never reaches `output.env`. Finds the keys of
+// such lines so they can at least be reported.
+fn non_utf8_env_keys(output: &[u8]) -> Vec<String> {
+  output
+    .split(|b| *b == b'\n')
+    .filter(|line| std::str::from_utf8(line).is_err())
+    .filter_map(|line| line.strip_prefix(b"cargo:rustc-env=".as_ref()))
+    .map(|assignment| {
+      let key = assignment.split(|b| *b == b'=').next().unwrap_or_default();
+      String::from_utf8_lossy(key).into_owned()
+    })
+    .collect()
+}
+
+fn warn_non_utf8_env(cx: &Context, pkg: &Package, raw_output: &[u8]) -> Result<()> {
+  for key in non_utf8_env_keys(raw_output) {
+    cx.bcx.config.shell().warn(format!(
+      "build script for {} set `{}` to a non-UTF-8 value, which is omitted from the env",
+      pkg.name(),
+      key
+    ))?;
+  }
+  Ok(())
+}
+
+// The values of the `cargo:<key>=` lines in a build script's recorded output, read
+// without running it. `cargo::` is the spelling of newer cargos, which may have written
+// the output.
+fn recorded_values(raw_output: &[u8], key: &str) -> Vec<String> {
+  let (old, new) = (format!("cargo:{}=", key), format!("cargo::{}=", key));
+  raw_output
+    .split(|b| *b == b'\n')
+    .filter_map(|line| std::str::from_utf8(line).ok())
+    .map(|line| line.trim_end_matches('\r'))
+    .filter_map(|line| line.strip_prefix(&old).or_else(|| line.strip_prefix(&new)))
+    .map(|value| value.to_string())
+    .collect()
+}
+
+fn recorded_env(raw_output: &[u8]) -> HashMap<String, String> {
+  recorded_values(raw_output, "rustc-env")
+    .iter()
+    .filter_map(|assignment| assignment.split_once('='))
+    .map(|(key, value)| (key.to_string(), value.to_string()))
+    .collect()
+}
+
+// The flags cargo adds from build scripts' output: the unit's own script's cfgs (and
+// check-cfgs, for cargos that know them) and `-l` libraries, and the `-L` paths of every
+// script it links with, like `openssl-sys`'s for its dependents. Scripts that haven't run
+// in the target directory contribute nothing.
+fn build_script_flags(
+  cx: &Context,
+  target_unit: &Unit,
+  all_units: &[Unit],
+  options: &FlagsOptions,
+) -> Vec<RustcFlag> {
+  let scripts = match cx.build_scripts.get(target_unit) {
+    Some(scripts) if options.build_scripts != BuildScriptMode::Skip => scripts.clone(),
+    _ => return Vec::new(),
+  };
+  let own_meta = cx.find_build_script_metadata(target_unit);
+  // As in cargo, bins only link the package's native libraries through its lib, if any.
+  let pass_l_flag =
+    target_unit.target.is_lib() || !target_unit.pkg.targets().iter().any(|t| t.is_lib());
+
+  let mut flags = Vec::new();
+  for (_, meta) in &scripts.to_link {
+    let run_unit = all_units
+      .iter()
+      .find(|unit| unit.mode.is_run_custom_build() && cx.files().metadata(unit) == *meta);
+    let raw_output = match run_unit {
+      Some(unit) => fs::read(cx.files().build_script_run_dir(unit).join("output")),
+      None => continue,
+    };
+    let raw_output = match raw_output {
+      Ok(raw_output) => raw_output,
+      Err(_) => continue,
+    };
+
+    for path in recorded_values(&raw_output, "rustc-link-search") {
+      flags.extend(RustcFlag::parse_args(vec!["-L".into(), path.into()]));
+    }
+    if Some(*meta) != own_meta {
+      continue;
+    }
+    if pass_l_flag {
+      for lib in recorded_values(&raw_output, "rustc-link-lib") {
+        flags.push(RustcFlag::Raw("-l".into()));
+        flags.push(RustcFlag::Raw(lib));
+      }
+    }
+    for cfg in recorded_values(&raw_output, "rustc-cfg") {
+      flags.push(RustcFlag::Cfg(cfg));
+    }
+    if options.cargo_compat == CargoCompat::CheckCfg {
+      for check_cfg in recorded_values(&raw_output, "rustc-check-cfg") {
+        flags.push(RustcFlag::Raw("--check-cfg".into()));
+        flags.push(RustcFlag::Raw(check_cfg));
+      }
+    }
+  }
+  flags
+}
+
+// The deps directories of every unit's layout, then the link search paths printed by
+// build scripts that have run, here or in an earlier cargo build.
+fn search_path_flags(cx: &Context, all_units: &[Unit]) -> Vec<RustcFlag> {
+  let mut flags = all_units
+    .iter()
+    .map(|unit| RustcFlag::LibSearch {
+      kind: None,
+      path: cx.files().layout(unit.kind).deps().to_path_buf(),
+    })
+    .collect::<Vec<_>>();
+
+  for unit in all_units.iter().filter(|unit| unit.mode.is_run_custom_build()) {
+    let output = cx.files().build_script_run_dir(unit).join("output");
+    let raw_output = match fs::read(output) {
+      Ok(raw_output) => raw_output,
+      Err(_) => continue,
+    };
+    for path in recorded_values(&raw_output, "rustc-link-search") {
+      flags.extend(RustcFlag::parse_args(vec!["-L".into(), path.into()]));
+    }
+  }
+  flags
+}
+
+// A package's ID as cargo prints it in JSON messages.
+pub(crate) fn package_id_string(pkg: &Package) -> String {
+  format!(
+    "{} {} ({})",
+    pkg.name(),
+    pkg.version(),
+    pkg.package_id().source_id().as_url()
+  )
+}
+
+fn profile_info(profile: &Profile) -> ProfileInfo {
+  let lto = match profile.lto {
+    Lto::Off => "off".to_string(),
+    Lto::Bool(enabled) => enabled.to_string(),
+    Lto::Named(name) => name.to_string(),
+  };
+  ProfileInfo {
+    name: profile.name.to_string(),
+    opt_level: profile.opt_level.to_string(),
+    debuginfo: profile.debuginfo,
+    lto,
+    codegen_units: profile.codegen_units,
+    debug_assertions: profile.debug_assertions,
+    overflow_checks: profile.overflow_checks,
+    panic: profile.panic.to_string(),
+    incremental: profile.incremental,
+  }
+}
+
+// The units whose cfgs and externs go into the flags: just the target unit, except in
+// whole-package mode, where those of every target in the package are merged so the flags
+// can compile any of its files.
+fn flag_units<'a>(
+  target_unit: &'a Unit,
+  all_units: &'a [Unit],
+  options: &FlagsOptions,
+) -> Vec<&'a Unit> {
+  if !options.whole_package {
+    return vec![target_unit];
+  }
+  let mut units = vec![target_unit];
+  for unit in all_units {
+    // Host builds of the package (when it's also a build dependency) would extern host
+    // artifacts, which the target build can't link.
+    if unit.pkg.package_id() == target_unit.pkg.package_id()
+      && unit.kind == target_unit.kind
+      && !unit.target.is_custom_build()
+      && !units.contains(&unit)
+    {
+      units.push(unit);
+    }
+  }
+  units
+}
+
+// Compiles what the target units need before their flags can be assembled: their build
+// scripts and, with `build_missing_deps`, the dependencies they extern that haven't been
+// built yet. Each goes through a single job queue, so independent units (e.g. the build
+// scripts of a whole workspace) are built in parallel.
+pub(crate) fn run_prerequisites(
+  cx: &mut Context,
+  target_units: &[&Unit],
+  all_units: &[Unit],
+  options: &FlagsOptions,
+) -> Result<()> {
+  if options.build_scripts != BuildScriptMode::Run {
+    return Ok(());
+  }
+
+  let mut scripts = Vec::new();
+  let mut missing = Vec::new();
+  for target_unit in target_units {
+    if let (Some(build_unit), Some(meta)) = (
+      cx.find_build_script_unit(target_unit),
+      cx.find_build_script_metadata(target_unit),
+    ) {
+      if !scripts.iter().any(|(unit, _)| *unit == build_unit) {
+        scripts.push((build_unit, meta));
+      }
+    }
+    if options.build_missing_deps {
+      for unit in flag_units(target_unit, all_units, options) {
+        for dep in cx.unit_deps(unit) {
+          if dep.unit.mode.is_run_custom_build() || missing.contains(&dep.unit) {
+            continue;
+          }
+          if let Some(path) = cargo_compat::linkable_output(cx, &dep.unit)? {
+            if !path.exists() {
+              missing.push(dep.unit.clone());
+            }
+          }
+        }
+      }
+    }
+  }
+
+  // Scripts cargo's fingerprints say are up to date needn't go through the job queue.
+  let mut stale = Vec::new();
+  for (build_unit, meta) in scripts {
+    if !cargo_compat::load_fresh_build_script(cx, &build_unit, meta)? {
+      stale.push((build_unit, meta));
+    }
+  }
+  let scripts = stale;
+
+  if let Some(progress) = &options.progress {
+    for (build_unit, _) in &scripts {
+      let name = build_unit.pkg.name().to_string();
+      if !build_script_compiled(cx, build_unit)? {
+        progress.on_event(ProgressEvent::CompilingBuildScript(name.clone()));
+      }
+      progress.on_event(ProgressEvent::RunningBuildScript(name));
+    }
+  }
+
+  let build_units = scripts.iter().map(|(unit, _)| unit.clone()).collect::<Vec<_>>();
+  let quiet = !options.show_build_output;
+  if !build_units.is_empty() {
+    if let Err(err) = cargo_compat::compile_units(cx, &build_units, quiet) {
+      // The script that failed is (in all likelihood) the first one without output.
+      let outputs = cx.build_script_outputs.lock().unwrap();
+      let failed = scripts
+        .iter()
+        .find(|(_, meta)| outputs.get(*meta).is_none())
+        .or_else(|| scripts.first());
+      return Err(match failed {
+        Some((unit, _)) => err.context(GenerateFlagsError::BuildScriptFailed {
+          package: unit.pkg.name().to_string(),
+        }),
+        None => err,
+      });
+    }
+  }
+  // Building the missing dependencies runs their build scripts, which is why this only
+  // happens when build scripts may run anyway.
+  if !missing.is_empty() {
+    cargo_compat::compile_units(cx, &missing, quiet)?;
+  }
+  Ok(())
+}
+
+// Whether the executable of the build script that `run_unit` runs is in the target
+// directory, so that only running it is left.
+fn build_script_compiled(cx: &Context, run_unit: &Unit) -> Result<bool> {
+  for dep in cx.unit_deps(run_unit) {
+    if dep.unit.target.is_custom_build() && !dep.unit.mode.is_run_custom_build() {
+      return Ok(cx.outputs(&dep.unit)?.iter().all(|output| output.path.exists()));
+    }
+  }
+  Ok(false)
+}
+
+// Runs the unit's prerequisites, then assembles its flags.
+pub(crate) fn unit_flags(
+  cx: &mut Context,
+  target_unit: &Unit,
+  all_units: &[Unit],
+  options: &FlagsOptions,
+  sysroot: &Path,
+) -> Result<RustcFlags> {
+  timings::timed(Phase::BuildScripts, || {
+    run_prerequisites(cx, &[target_unit], all_units, options)
+  })?;
+  prepared_unit_flags(cx, target_unit, all_units, options, sysroot)
+}
+
+// Assembles the flags of a unit whose prerequisites have run. Only reads `cx`, but does
+// create the `CARGO_TARGET_TMPDIR` of tests and benches (see `prepared_unit_env`).
+pub(crate) fn prepared_unit_flags(
+  cx: &Context,
+  target_unit: &Unit,
+  all_units: &[Unit],
+  options: &FlagsOptions,
+  sysroot: &Path,
+) -> Result<RustcFlags> {
+  planned_unit_flags(cx, target_unit, all_units, options, sysroot)?.finish()
+}
+
+// The parts of a unit's flags that only read files (manifests for lints, dep-info and
+// build script output for inputs), left for `PlannedFlags::finish`.
+enum Deferred {
+  // `lints::lint_flags` of a package manifest, in the workspace of the second one.
+  Lints(PathBuf, PathBuf),
+  // `lints::lint_check_cfgs`, likewise, as `--check-cfg`s.
+  CheckCfgs(PathBuf, PathBuf),
+}
+
+impl Deferred {
+  fn resolve(self) -> Result<Vec<RustcFlag>> {
+    match self {
+      Deferred::Lints(manifest, ws_manifest) => lints::lint_flags(&manifest, &ws_manifest),
+      Deferred::CheckCfgs(manifest, ws_manifest) => {
+        let check_cfgs = lints::lint_check_cfgs(&manifest, &ws_manifest)?;
+        Ok(
+          check_cfgs
+            .into_iter()
+            .flat_map(|check_cfg| {
+              vec![RustcFlag::Raw("--check-cfg".into()), RustcFlag::Raw(check_cfg)]
+            })
+            .collect(),
+        )
+      }
+    }
+  }
+}
+
+// A unit's flags, short of what `Deferred` and `InputFiles` read. Unlike the cargo context
+// they come from, these can be sent to other threads, so that `generate_all_parallel` can
+// finish units in parallel.
+pub(crate) struct PlannedFlags {
+  flags: RustcFlags,
+  // Each goes into `flags.flags` at its index.
+  deferred: Vec<(usize, Deferred)>,
+  inputs: Option<InputFiles>,
+}
+
+impl PlannedFlags {
+  pub(crate) fn finish(self) -> Result<RustcFlags> {
+    let mut flags = self.flags;
+    // From the back, so that the indices of the earlier ones stay valid.
+    for (index, deferred) in self.deferred.into_iter().rev() {
+      let resolved = deferred.resolve()?;
+      flags.flags.splice(index..index, resolved);
+    }
+    flags.inputs = self.inputs.map(InputFiles::read);
+    Ok(flags)
+  }
+}
+
+// Like `prepared_unit_flags`, but leaves the file reads for `PlannedFlags::finish`.
+pub(crate) fn planned_unit_flags(
+  cx: &Context,
+  target_unit: &Unit,
+  all_units: &[Unit],
+  options: &FlagsOptions,
+  sysroot: &Path,
+) -> Result<PlannedFlags> {
+  // Reimplements cargo's `build_base_args`, which isn't exposed.
+  let crate_root = units::crate_root(target_unit)?;
+  let mut flags = vec![RustcFlag::CrateName(target_unit.target.crate_name())];
+
+  // Like cargo, test harnesses get `--test` instead of a crate type, and test-mode targets
+  // without a harness keep their crate type but get `--cfg test`.
+  let test = target_unit.mode.is_any_test();
+  // A target can have several crate types (e.g. `["lib", "cdylib"]`), which rustc
+  // builds in one invocation, sharing the `-C extra-filename` suffix.
+  if !test {
+    for crate_type in target_unit.target.rustc_crate_types() {
+      flags.push(RustcFlag::CrateType(crate_type));
+    }
+  }
+  // rustdoc has to be told that the crate whose doctests it collects is a proc-macro.
+  if target_unit.mode.is_doc_test() && target_unit.target.proc_macro() {
+    flags.push(RustcFlag::CrateType(CrateType::ProcMacro));
+  }
+
+  let unit_sysroot = cargo_compat::build_std_sysroot(cx, target_unit.kind)
+    .unwrap_or_else(|| sysroot.to_path_buf());
+  flags.extend(vec![
+    RustcFlag::Sysroot(unit_sysroot),
+    // Path must be the crate root file, NOT the sliced file
+    RustcFlag::Input(crate_root.to_path_buf()),
+    RustcFlag::Edition(target_unit.target.edition().to_string()),
+  ]);
+  if let CompileKind::Target(target) = target_unit.kind {
+    flags.push(RustcFlag::Target(target.rustc_target().to_string()));
+  }
+  if !options.absolute_externs_only {
+    flags.push(RustcFlag::LibSearch {
+      kind: None,
+      path: cx.files().layout(target_unit.kind).deps().to_path_buf(),
+    });
+    // As in `cargo test --doc`, doctests also search the host deps directory, since
+    // they're linked into a binary that can use the crate's proc-macro dependencies.
+    if target_unit.mode.is_doc_test() && !target_unit.kind.is_host() {
+      flags.push(RustcFlag::LibSearch {
+        kind: Some("dependency".into()),
+        path: cx.files().layout(CompileKind::Host).deps().to_path_buf(),
+      });
+    }
+    if options.all_search_paths {
+      for flag in search_path_flags(cx, all_units) {
+        if !flags.contains(&flag) {
+          flags.push(flag);
+        }
+      }
+    }
+  }
+  // Avoids ICE looking for MIR data?
+  flags.push(crate::output_flag(
+    options,
+    target_unit.mode,
+    target_unit.requires_upstream_objects(),
+  ));
+  flags.extend(crate::message_format_flags(options));
+  flags.extend(crate::sysroot_remap_flags(options, sysroot));
+  if let Some(prefix) = &options.remap_registry {
+    let registry_src = cx.bcx.config.registry_source_path();
+    flags.push(crate::remap_path_prefix(
+      registry_src.as_path_unlocked(),
+      prefix,
+    ));
+  }
+  let trim_scope = match &options.trim_paths {
+    Some(scope) if scope == "none" => None,
+    Some(scope) => Some(scope.clone()),
+    None => trim_paths::profile_trim_paths(cx.bcx.ws.root_manifest(), &target_unit.profile.name)?,
+  };
+  if let Some(scope) = trim_scope {
+    flags.extend(trim_paths::trim_paths_flags(cx, target_unit, sysroot, &scope));
+  }
+  // Last, since rustc applies the last matching remap.
+  for (from, to) in &options.remap_path_prefixes {
+    flags.push(crate::remap_path_prefix(from, to));
+  }
+
+  let edition = target_unit.target.edition().to_string();
+  let version = rustc_version(cx, options);
+  let unstable_edition = edition_is_unstable(&edition, &version, options)?;
+  if unstable_edition {
+    flags.push(RustcFlag::Unstable {
+      key: "unstable-options".into(),
+      value: None,
+    });
+  }
+
+  // Like cargo, leave out the settings that match rustc's defaults.
+  let opt_level = target_unit.profile.opt_level.as_str();
+  if opt_level != "0" {
+    flags.push(RustcFlag::codegen("opt-level", opt_level));
+  }
+  if let Some(debuginfo) = target_unit.profile.debuginfo {
+    flags.push(RustcFlag::codegen("debuginfo", debuginfo.to_string()));
+  }
+  if target_unit.profile.panic != PanicStrategy::Unwind {
+    flags.push(RustcFlag::codegen("panic", target_unit.profile.panic.to_string()));
+  }
+  for option in cargo_compat::lto_codegen_options(cx, target_unit) {
+    flags.push(match option.split_once('=') {
+      Some((key, value)) => RustcFlag::codegen(key, value),
+      None => RustcFlag::Codegen {
+        key: option,
+        value: None,
+      },
+    });
+  }
+  flags.extend(assertion_flags(&target_unit.profile, opt_level));
+
+  let prefer_dynamic = (target_unit.target.for_host() && !target_unit.target.is_custom_build())
+    || (target_unit.target.rustc_crate_types().contains(&CrateType::Dylib)
+      && !cx.is_primary_package(target_unit));
+  if prefer_dynamic {
+    flags.push(RustcFlag::Codegen {
+      key: "prefer-dynamic".into(),
+      value: None,
+    });
+  }
+  if target_unit.profile.rpath {
+    flags.push(RustcFlag::Codegen {
+      key: "rpath".into(),
+      value: None,
+    });
+  }
+
+  // Stripping only affects linked artifacts, so check builds never get it.
+  // `-C strip` was `-Z strip` before 1.59.
+  let profile = &target_unit.profile;
+  if profile.strip != Strip::None && !target_unit.mode.is_check() && !target_unit.mode.is_doc() {
+    if version < Version::new(1, 59, 0) {
+      flags.push(RustcFlag::Unstable {
+        key: "strip".into(),
+        value: Some(profile.strip.to_string()),
+      });
+    } else {
+      flags.push(RustcFlag::codegen("strip", profile.strip.to_string()));
+    }
+  }
+
+  let incremental_dir = match &options.incremental {
+    Incremental::Profile if target_unit.profile.incremental => {
+      Some(cx.files().layout(target_unit.kind).incremental().to_path_buf())
+    }
+    Incremental::Always => Some(cx.files().layout(target_unit.kind).incremental().to_path_buf()),
+    Incremental::Dir(dir) => Some(dir.clone()),
+    Incremental::Profile | Incremental::Off => None,
+  };
+  if let Some(dir) = incremental_dir {
+    flags.push(RustcFlag::codegen("incremental", format!("{}", dir.display())));
+  }
+  if let Some(split) = target_unit.profile.split_debuginfo {
+    if cx.bcx.target_data.info(target_unit.kind).supports_split_debuginfo {
+      flags.push(RustcFlag::codegen("split-debuginfo", split.as_str()));
+    }
+  }
+
+  // Like cargo, only pass `codegen-units` when the profile sets it, leaving rustc's own
+  // default (which depends on incremental compilation) otherwise.
+  if let Some(codegen_units) = target_unit.profile.codegen_units {
+    flags.push(RustcFlag::codegen("codegen-units", codegen_units.to_string()));
+  }
+
+  if test && target_unit.target.harness() {
+    flags.push(RustcFlag::Raw("--test".into()));
+  } else if test {
+    flags.push(RustcFlag::Cfg("test".into()));
+  }
+
+  // rustdoc sets `cfg(doctest)` itself when collecting doctests, so a plain rustc
+  // invocation needs it spelled out.
+  if target_unit.mode.is_doc_test() {
+    flags.push(RustcFlag::Cfg("doctest".into()));
+  }
+
+  // Like cargo (short of `-vv`), silence the lints of dependencies that aren't local.
+  let manifests = || {
+    let manifest = target_unit.pkg.manifest_path().to_path_buf();
+    (manifest, cx.bcx.ws.root_manifest().to_path_buf())
+  };
+  let mut deferred = Vec::new();
+  if target_unit.is_local() {
+    let (manifest, ws_manifest) = manifests();
+    deferred.push((flags.len(), Deferred::Lints(manifest, ws_manifest)));
+  } else if !cx.bcx.config.extra_verbose() {
+    flags.push(RustcFlag::Raw("--cap-lints".into()));
+    flags.push(RustcFlag::Raw("allow".into()));
+  }
+
+  // Cargo restricts every crate it compiles, dependencies included, to the nightly
+  // features in `-Z allow-features`.
+  if let Some(allowed) = &cx.bcx.config.cli_unstable().allow_features {
+    flags.push(RustcFlag::Unstable {
+      key: "allow-features".into(),
+      value: Some(allowed.iter().map(String::as_str).collect::<Vec<_>>().join(",")),
+    });
+  }
+
+  // As `cargo fix --edition` does: stay on the current edition, with the lints that
+  // flag what would break in the next one. They're force-warned so that `[lints]` or
+  // attributes can't silence them, which would also get past a dependency's
+  // `--cap-lints`; like `cargo fix`, only local packages are migrated.
+  if options.edition_migration && target_unit.is_local() {
+    let next = match edition.as_str() {
+      "2015" => "2018",
+      "2018" => "2021",
+      "2021" => "2024",
+      _ => bail!("There is no edition after {} to migrate to", edition),
+    };
+    if version < Version::new(1, 56, 0) {
+      bail!("Edition migration needs `--force-warn`, which rustc {} lacks", version);
+    }
+    let stable = edition_stable_since(next).map_or(false, |since| version >= since);
+    if !stable && !is_nightly(&version) {
+      bail!(
+        "Migrating to edition {} needs a nightly rustc until {} is stable, not rustc {}",
+        next,
+        next,
+        version
+      );
+    }
+    flags.push(RustcFlag::Raw(format!(
+      "--force-warn=rust-{}-compatibility",
+      next
+    )));
+  }
+
+  // Cargo passes `RUSTFLAGS`, `CARGO_ENCODED_RUSTFLAGS` or the config's
+  // `build.rustflags`/`target.<triple>.rustflags`, whichever applies, after its own flags.
+  // Without them, only the rustflags that change codegen and, through rustc, the
+  // `target_feature` cfgs are kept. rustc derives those cfgs from the flags itself (and
+  // rejects them as explicit `--cfg`s), so only the flags are passed. Unlike the cpu,
+  // every `target-feature` occurrence accumulates.
+  let rustflags = cx.bcx.rustflags_args(target_unit);
+  if options.propagate_rustflags {
+    flags.extend(rustflags.iter().cloned().map(RustcFlag::Raw));
+  } else {
+    for key in &["target-cpu", "relocation-model", "code-model"] {
+      if let Some(value) = find_codegen_flag(rustflags, key) {
+        flags.push(RustcFlag::codegen(*key, value));
+      }
+    }
+    for features in codegen_flag_values(rustflags, "target-feature") {
+      flags.push(RustcFlag::codegen("target-feature", features));
+    }
+  }
+
+  // Cargo doesn't tie the mangling scheme to the edition: rustc defaults to legacy
+  // everywhere, and v0 is only selected through rustflags. Passing `legacy` explicitly
+  // requires `-Z unstable-options`, so it is only emitted when the caller asks for it.
+  // Propagated rustflags already carry their own choice, which the override follows.
+  let symbol_mangling_version = options.symbol_mangling_version.or_else(|| {
+    find_codegen_flag(rustflags, "symbol-mangling-version")
+      .and_then(|v| SymbolManglingVersion::parse(&v))
+      .filter(|v| *v != SymbolManglingVersion::Legacy && !options.propagate_rustflags)
+  });
+  if let Some(v) = symbol_mangling_version {
+    // `-C symbol-mangling-version` was `-Z symbol-mangling-version` before 1.59.
+    if version < Version::new(1, 59, 0) {
+      flags.push(RustcFlag::Unstable {
+        key: "symbol-mangling-version".into(),
+        value: Some(v.as_str().into()),
+      });
+    } else {
+      flags.push(RustcFlag::codegen("symbol-mangling-version", v.as_str()));
+    }
+  }
+
+  let flag_units = flag_units(target_unit, all_units, options);
+
+  // Cargo emits feature cfgs in sorted order (`Unit::features` comes out of the resolver
+  // sorted), and metadata hashes depend on that order, so sort explicitly rather than
+  // relying on that invariant holding in every cargo version.
+  let mut features = flag_units
+    .iter()
+    .map(|unit| unit.features.iter().map(|f| f.as_str()))
+    .flatten()
+    .collect::<Vec<_>>();
+  features.sort_unstable();
+  features.dedup();
+  flags.extend(
+    features
+      .into_iter()
+      .map(|feature| RustcFlag::Cfg(format!("feature=\"{}\"", feature))),
+  );
+
+  // The same metadata hash and file name suffix as cargo, so that the artifacts rustc
+  // writes are the ones cargo would, and dependents' externs resolve to them.
+  let metadata = cx.files().metadata(target_unit);
+  flags.push(RustcFlag::codegen("metadata", metadata.to_string()));
+  if cx.files().use_extra_filename(target_unit) {
+    flags.push(RustcFlag::codegen("extra-filename", format!("-{}", metadata)));
+  }
+  flags.push(RustcFlag::Raw("--out-dir".into()));
+  flags.push(RustcFlag::Raw(format!(
+    "{}",
+    cx.files().out_dir(target_unit).display()
+  )));
+
+  if options.cargo_compat == CargoCompat::CheckCfg {
+    let mut declared = target_unit
+      .pkg
+      .summary()
+      .features()
+      .keys()
+      .map(|feature| format!("\"{}\"", feature))
+      .collect::<Vec<_>>();
+    declared.sort();
+    flags.push(RustcFlag::Raw("--check-cfg".into()));
+    flags.push(RustcFlag::Raw("cfg(docsrs)".into()));
+    flags.push(RustcFlag::Raw("--check-cfg".into()));
+    flags.push(RustcFlag::Raw(format!(
+      "cfg(feature, values({}))",
+      declared.join(", ")
+    )));
+    // Only local packages' `[lints]` matter, as dependencies' lints are capped.
+    if target_unit.is_local() {
+      let (manifest, ws_manifest) = manifests();
+      deferred.push((flags.len(), Deferred::CheckCfgs(manifest, ws_manifest)));
+    }
+  }
+
+  // These include the package's own lib for its bins, tests and examples, since their
+  // units depend on the lib unit just like on any other dependency.
+  //
+  // Modifiers such as `noprelude:` or `nounused:` are kept verbatim in `RustcFlag::Extern`,
+  // but rustc only accepts them alongside `-Z unstable-options`, as cargo passes.
+  let mut extern_flags = Vec::new();
+  let mut unstable_opts = false;
+  for unit in &flag_units {
+    let (args, unit_unstable_opts) =
+      timings::timed(Phase::Externs, || cargo_compat::extern_args(cx, unit))?;
+    unstable_opts |= unit_unstable_opts;
+    for mut flag in RustcFlag::parse_args(args) {
+      if options.absolute_externs_only {
+        if let RustcFlag::Extern {
+          path: Some(path), ..
+        } = &mut flag
+        {
+          if path.is_relative() {
+            *path = env::current_dir()?.join(&*path);
+          }
+        }
+      }
+      // A crate name is externed once. The first unit is the target unit itself, so its
+      // artifact wins over another unit's (e.g. an rlib over an rmeta).
+      let duplicate = extern_flags.iter().any(|existing| match (existing, &flag) {
+        (RustcFlag::Extern { name: a, .. }, RustcFlag::Extern { name: b, .. }) => a == b,
+        (existing, flag) => existing == flag,
+      });
+      if !duplicate {
+        extern_flags.push(flag);
+      }
+    }
+  }
+
+  // Like cargo, make the compiler's `proc_macro` crate available to proc-macros without
+  // an `extern crate`. It comes from the sysroot, so it has no path (or `ExternDep`).
+  if target_unit.target.proc_macro() {
+    extern_flags.push(RustcFlag::Extern {
+      name: "proc_macro".into(),
+      path: None,
+      modifiers: Vec::new(),
+    });
+  }
+
+  // The unit behind each extern name, for describing the externs.
+  let mut extern_units = HashMap::new();
+  for unit in &flag_units {
+    for dep in cx.unit_deps(unit) {
+      extern_units
+        .entry(dep.extern_crate_name.to_string())
+        .or_insert_with(|| dep.unit.clone());
+    }
+  }
+
+  // Doctests link against the library they document, which cargo normally externs
+  // through the doctest unit's dependency on it. Make sure it's there regardless.
+  if target_unit.mode.is_doc_test() {
+    let crate_name = target_unit.target.crate_name();
+    let has_self_extern = extern_flags
+      .iter()
+      .any(|flag| matches!(flag, RustcFlag::Extern { name, .. } if *name == crate_name));
+    let lib_unit = all_units.iter().find(|unit| {
+      unit.pkg == target_unit.pkg
+        && unit.target.is_lib()
+        && !unit.mode.is_any_test()
+        && !unit.mode.is_doc()
+        && !unit.mode.is_run_custom_build()
+    });
+    if let (false, Some(lib_unit)) = (has_self_extern, lib_unit) {
+      if let Some(path) = cargo_compat::linkable_output(cx, lib_unit)? {
+        extern_units.insert(crate_name.clone(), lib_unit.clone());
+        extern_flags.push(RustcFlag::Extern {
+          name: crate_name,
+          path: Some(path),
+          modifiers: Vec::new(),
+        });
+      }
+    }
+  }
+
+  // Cargo's order follows the unit graph; sort so the flags are canonical.
+  extern_flags.sort_by_key(|flag| match flag {
+    RustcFlag::Extern { name, .. } => Some(name.clone()),
+    _ => None,
+  });
+  let externs = extern_flags
+    .iter()
+    .filter_map(|flag| match flag {
+      RustcFlag::Extern { name, path, .. } => Some((name, path)),
+      _ => None,
+    })
+    .filter_map(|(name, path)| {
+      let unit = extern_units.get(name)?;
+      let crate_name = unit.target.crate_name();
+      Some(ExternDep {
+        rename: Some(name.clone()).filter(|name| *name != crate_name),
+        name: crate_name,
+        rmeta_path: path.clone(),
+        is_proc_macro: unit.target.proc_macro(),
+        pkg_id: package_id_string(&unit.pkg),
+      })
+    })
+    .collect();
+
+  if options.check_externs_built {
+    for flag in &extern_flags {
+      if let RustcFlag::Extern {
+        name,
+        path: Some(path),
+        ..
+      } = flag
+      {
+        if !path.exists() {
+          bail!(
+            "Dependency `{}` has not been built, or the target directory is stale: {} does \
+             not exist. Run `cargo check` (or `cargo build`) first",
+            name,
+            path.display()
+          );
+        }
+      }
+    }
+  }
+
+  if unstable_opts && !unstable_edition {
+    flags.push(RustcFlag::Unstable {
+      key: "unstable-options".into(),
+      value: None,
+    });
+  }
+  flags.extend(extern_flags);
+
+  // Cargo wraps the workspace wrapper in the general one.
+  let rustc = cx.bcx.rustc();
+  let mut wrappers = rustc.wrapper.iter().cloned().collect::<Vec<_>>();
+  if cx.bcx.ws.is_member(&target_unit.pkg) {
+    wrappers.extend(rustc.workspace_wrapper.iter().cloned());
+  }
+  let rustc_path = rustc.path.clone();
+
+  let mut env = prepared_unit_env(cx, target_unit, all_units, options)?;
+  // `-L` paths may already be there with `all_search_paths`.
+  for flag in build_script_flags(cx, target_unit, all_units, options) {
+    if !matches!(flag, RustcFlag::LibSearch { .. }) || !flags.contains(&flag) {
+      flags.push(flag);
+    }
+  }
+  flags.extend(options.extra_args.iter().cloned().map(RustcFlag::Raw));
+  if unstable_edition && options.bootstrap_unstable_editions {
+    env.insert("RUSTC_BOOTSTRAP".into(), "1".into());
+  }
+
+  let flags = RustcFlags {
+    schema_version: RustcFlags::SCHEMA_VERSION,
+    flags,
+    crate_root: crate_root.to_path_buf(),
+    env: env.into_iter().collect(),
+    origin: FlagsOrigin::Cargo,
+    package_id: Some(package_id_string(&target_unit.pkg)),
+    profile: Some(profile_info(&target_unit.profile)),
+    externs,
+    rustc: rustc_path,
+    wrappers,
+    file_overrides: crate::crate_file_overrides(options, crate_root),
+    inputs: None,
+    package_env: Some(PackageEnv::new(&target_unit.pkg)),
+    timings: None,
+  };
+  Ok(PlannedFlags {
+    flags,
+    deferred,
+    inputs: if options.collect_inputs {
+      Some(input_files(cx, target_unit))
+    } else {
+      None
+    },
+  })
+}
+
+// Where a unit's `UnitInputs` are read from: its dep-info file, whose entries are
+// relative to the workspace root (where cargo runs rustc), and its build script's output,
+// whose `rerun-if-changed` paths are relative to the package root.
+struct InputFiles {
+  dep_info: PathBuf,
+  ws_root: PathBuf,
+  build_script: Option<(PathBuf, PathBuf)>,
+}
+
+fn input_files(cx: &Context, target_unit: &Unit) -> InputFiles {
+  let build_script = cx.find_build_script_unit(target_unit).map(|build_unit| {
+    let raw_output = cx.files().build_script_run_dir(&build_unit).join("output");
+    (raw_output, target_unit.pkg.root().to_path_buf())
+  });
+  InputFiles {
+    dep_info: cargo_compat::dep_info_path(cx, target_unit),
+    ws_root: cx.bcx.ws.root().to_path_buf(),
+    build_script,
+  }
+}
+
+impl InputFiles {
+  fn read(self) -> UnitInputs {
+    let mut inputs = UnitInputs {
+      sources: parse_dep_info(&self.dep_info)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|file| self.ws_root.join(file))
+        .collect(),
+      env: dep_info::parse_env_deps(&self.dep_info).unwrap_or_default(),
+      ..UnitInputs::default()
+    };
+
+    if let Some((raw_output, pkg_root)) = self.build_script {
+      let raw_output = fs::read(&raw_output).unwrap_or_default();
+      inputs.build_script_inputs = recorded_values(&raw_output, "rerun-if-changed")
+        .iter()
+        .map(|path| pkg_root.join(path))
+        .collect();
+      if inputs.build_script_inputs.is_empty() {
+        inputs.build_script_inputs.push(pkg_root);
+      }
+      inputs
+        .env
+        .extend(recorded_values(&raw_output, "rerun-if-env-changed"));
+    }
+
+    inputs.env.sort();
+    inputs.env.dedup();
+    inputs
+  }
+}
+
+// The environment rustc needs for `target_unit`, running its build script if it has one
+// (and `options` allows it).
+pub(crate) fn unit_env(
+  cx: &mut Context,
+  target_unit: &Unit,
+  all_units: &[Unit],
+  options: &FlagsOptions,
+) -> Result<HashMap<String, String>> {
+  run_prerequisites(cx, &[target_unit], all_units, options)?;
+  prepared_unit_env(cx, target_unit, all_units, options)
+}
+
+fn prepared_unit_env(
+  cx: &Context,
+  target_unit: &Unit,
+  all_units: &[Unit],
+  options: &FlagsOptions,
+) -> Result<HashMap<String, String>> {
+  let pkg = &target_unit.pkg;
+  let mut env = PackageEnv::new(pkg).vars().into_iter().collect::<HashMap<_, _>>();
+  // The crate name comes from the target (e.g. a renamed `[lib] name`), while the
+  // `CARGO_PKG_*` variables always describe the package.
+  env.insert("CARGO_CRATE_NAME".into(), target_unit.target.crate_name());
+  env.insert(
+    "CARGO".into(),
+    format!("{}", cx.bcx.config.cargo_exe()?.display()),
+  );
+  // Like cargo, only build script runs get `NUM_JOBS`, not rustc. The build scripts run
+  // here get it from cargo itself, following the `jobs` option.
+  if target_unit.mode == CompileMode::RunCustomBuild {
+    env.insert("NUM_JOBS".into(), cx.bcx.jobs().to_string());
+  }
+  if target_unit.target.is_bin() {
+    env.insert("CARGO_BIN_NAME".into(), target_unit.target.name().to_string());
+  }
+  if cx.is_primary_package(target_unit) {
+    env.insert("CARGO_PRIMARY_PACKAGE".into(), "1".into());
+  }
+  // A scratch directory for integration tests and benchmarks. Cargo guarantees that it
+  // exists, so it's created here too; this is the only write of `prepared_unit_flags`.
+  if target_unit.target.is_test() || target_unit.target.is_bench() {
+    let tmp = cx.files().layout(CompileKind::Host).prepare_tmp()?;
+    env.insert("CARGO_TARGET_TMPDIR".into(), format!("{}", tmp.display()));
+  }
+
+  env.extend(prerequisite_env(cx, target_unit, options)?);
+  if options.dep_links_env {
+    env.extend(dep_links_env(cx, target_unit, all_units));
+  }
+
+  Ok(env)
+}
+
+// The `DEP_<LINKS>_<KEY>` variables cargo gives a build script for the metadata
+// (`cargo:KEY=VALUE`) printed by the scripts of the `links` packages it links with. They're
+// keyed by the links name, as cargo only allows one package per name in the graph.
+fn dep_links_env(cx: &Context, target_unit: &Unit, all_units: &[Unit]) -> HashMap<String, String> {
+  let mut env = HashMap::new();
+  let scripts = match cx.build_scripts.get(target_unit) {
+    Some(scripts) => scripts,
+    None => return env,
+  };
+  for (pkg_id, meta) in &scripts.to_link {
+    if *pkg_id == target_unit.pkg.package_id() {
+      continue;
+    }
+    let run_unit = all_units
+      .iter()
+      .find(|unit| unit.mode.is_run_custom_build() && cx.files().metadata(unit) == *meta);
+    let run_unit = match run_unit {
+      Some(run_unit) => run_unit,
+      None => continue,
+    };
+    let links = match run_unit.pkg.manifest().links() {
+      Some(links) => links,
+      None => continue,
+    };
+    let raw_output = match fs::read(cx.files().build_script_run_dir(run_unit).join("output")) {
+      Ok(raw_output) => raw_output,
+      Err(_) => continue,
+    };
+    let prefix = format!("DEP_{}_", links.to_uppercase().replace('-', "_"));
+    for (key, value) in recorded_metadata(&raw_output) {
+      env.insert(
+        format!("{}{}", prefix, key.to_uppercase().replace('-', "_")),
+        value,
+      );
+    }
+  }
+  env
+}
+
+// The `cargo:KEY=VALUE` (or `cargo::metadata=KEY=VALUE`) pairs in a build script's output
+// that aren't instructions to cargo.
+fn recorded_metadata(raw_output: &[u8]) -> Vec<(String, String)> {
+  raw_output
+    .split(|b| *b == b'\n')
+    .filter_map(|line| std::str::from_utf8(line).ok())
+    .map(|line| line.trim_end_matches('\r'))
+    .filter_map(|line| match line.strip_prefix("cargo::") {
+      Some(instruction) => instruction.strip_prefix("metadata=")?.split_once('='),
+      None => {
+        let (key, value) = line.strip_prefix("cargo:")?.split_once('=')?;
+        let reserved =
+          key.starts_with("rustc-") || key.starts_with("rerun-if-") || key == "warning";
+        Some((key, value)).filter(|_| !reserved)
+      }
+    })
+    .map(|(key, value)| (key.to_string(), value.to_string()))
+    .collect()
+}
+
+// The environment provided by what the target unit needs before it compiles: its build
+// script run (which `run_prerequisites` did) and its artifact dependencies, which are
+// built here.
+fn prerequisite_env(
+  cx: &Context,
+  target_unit: &Unit,
+  options: &FlagsOptions,
+) -> Result<HashMap<String, String>> {
+  let pkg = &target_unit.pkg;
+  let mut env = HashMap::new();
+  let build_script = cx
+    .find_build_script_metadata(target_unit)
+    .zip(cx.find_build_script_unit(target_unit));
+  if let Some((target_meta, build_unit)) = build_script {
+    let raw_output = cx.files().build_script_run_dir(&build_unit).join("output");
+    if options.build_scripts != BuildScriptMode::Skip {
+      env.insert(
+        "OUT_DIR".into(),
+        format!("{}", cx.files().build_script_out_dir(&build_unit).display()),
+      );
+    }
+
+    // Cargo resolves `[target.'cfg(..)'.dependencies]` before any build script runs, so
+    // cfgs set by `cargo:rustc-cfg` never gate dependencies, and skipping the script
+    // doesn't change the externs either.
+    match options.build_scripts {
+      BuildScriptMode::Skip => {
+        cx.bcx.config.shell().warn(format!(
+          "not running the build script for {}, so neither `OUT_DIR` nor anything it sets \
+           is included",
+          pkg.name()
+        ))?;
+      }
+      BuildScriptMode::UseCached => match fs::read(&raw_output) {
+        Ok(raw_output) => {
+          env.extend(recorded_env(&raw_output));
+          warn_non_utf8_env(cx, pkg, &raw_output)?;
+        }
+        Err(_) => {
+          cx.bcx.config.shell().warn(format!(
+            "the build script for {} has not run in this target directory, so nothing it \
+             sets is included",
+            pkg.name()
+          ))?;
+        }
+      },
+      BuildScriptMode::Run => {
+        let outputs = cx.build_script_outputs.lock().unwrap();
+        if let Some(output) = outputs.get(target_meta) {
+          env.extend(output.env.clone().into_iter());
+        }
+        drop(outputs);
+
+        if let Ok(raw_output) = fs::read(&raw_output) {
+          warn_non_utf8_env(cx, pkg, &raw_output)?;
+        }
+      }
+    }
+  }
+
+  if options.build_scripts == BuildScriptMode::Run {
+    let target = options.target.as_deref();
+    env.extend(artifacts::artifact_env(cx.bcx.ws, target_unit, target, options.jobs)?);
+  } else if artifacts::has_artifact_deps(target_unit) {
+    cx.bcx.config.shell().warn(format!(
+      "not building the artifact dependencies of {}, so no `CARGO_BIN_*` or \
+       `CARGO_CDYLIB_*` variables are included",
+      pkg.name()
+    ))?;
+  }
+
+  Ok(env)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn metadata_leaves_out_instructions() {
+    let raw_output = b"cargo:rustc-link-search=native=/out\r\ncargo:include=/out/include\n\
+      cargo:rerun-if-changed=build.rs\ncargo:warning=hi\ncargo::metadata=lib-version=1.2\n\
+      cargo::rustc-cfg=foo\n";
+    assert_eq!(
+      recorded_metadata(raw_output),
+      [
+        ("include".to_string(), "/out/include".to_string()),
+        ("lib-version".to_string(), "1.2".to_string())
+      ]
+    );
+  }
+
+  #[test]
+  fn recorded_values_reads_both_spellings() {
+    let output = b"cargo:rustc-link-search=native=/a\ncargo::rustc-link-search=/b\r\nwarning: x\n";
+    assert_eq!(
+      recorded_values(output, "rustc-link-search"),
+      vec!["native=/a".to_string(), "/b".to_string()]
+    );
+  }
+
+  #[test]
+  fn link_search_kinds_are_kept() {
+    let flags = recorded_values(b"cargo:rustc-link-search=native=/a", "rustc-link-search")
+      .into_iter()
+      .flat_map(|path| RustcFlag::parse_args(vec!["-L".into(), path.into()]))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      flags,
+      vec![RustcFlag::LibSearch {
+        kind: Some("native".into()),
+        path: "/a".into(),
+      }]
+    );
+  }
+}
@@ -0,0 +1,83 @@
+// A caller-facing copy of cargo's unit graph, detached from the `Context` it came from.
+
+use crate::unit_flags;
+use cargo::core::compiler::{CompileKind, Context, Unit};
+use std::collections::HashMap;
+
+/// The units cargo would compile, with their dependency edges.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnitGraph {
+  /// Dependencies come before the units that depend on them.
+  pub units: Vec<UnitNode>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnitNode {
+  /// Formatted like [`crate::RustcFlags::package_id`].
+  pub pkg_id: String,
+  pub target_name: String,
+  /// `lib`, `proc-macro`, `bin`, `build-script`, etc.
+  pub target_kind: String,
+  /// `host`, or the target triple.
+  pub compile_kind: String,
+  /// Cargo's compile mode, e.g. `Check { test: false }` or `RunCustomBuild`.
+  pub mode: String,
+  /// The features enabled on the package for this unit, sorted.
+  pub features: Vec<String>,
+  pub deps: Vec<UnitEdge>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnitEdge {
+  /// Index of the dependency in [`UnitGraph::units`].
+  pub unit: usize,
+  /// The name the dependency is externed under, after any renaming.
+  pub extern_name: String,
+}
+
+// `units` must be in dependency order, as `units::collect_units` returns them.
+pub(crate) fn unit_graph(cx: &Context, units: &[Unit]) -> UnitGraph {
+  let index = units
+    .iter()
+    .enumerate()
+    .map(|(i, unit)| (unit, i))
+    .collect::<HashMap<_, _>>();
+
+  let units = units
+    .iter()
+    .map(|unit| {
+      let compile_kind = match unit.kind {
+        CompileKind::Host => "host".to_string(),
+        CompileKind::Target(target) => target.rustc_target().to_string(),
+      };
+      let mut features = unit
+        .features
+        .iter()
+        .map(|feature| feature.to_string())
+        .collect::<Vec<_>>();
+      features.sort();
+      UnitNode {
+        pkg_id: unit_flags::package_id_string(&unit.pkg),
+        target_name: unit.target.name().to_string(),
+        target_kind: unit.target.kind().description().to_string(),
+        compile_kind,
+        mode: format!("{:?}", unit.mode),
+        features,
+        deps: cx
+          .unit_deps(unit)
+          .iter()
+          .filter_map(|dep| {
+            Some(UnitEdge {
+              unit: *index.get(&dep.unit)?,
+              extern_name: dep.extern_crate_name.to_string(),
+            })
+          })
+          .collect(),
+      }
+    })
+    .collect();
+  UnitGraph { units }
+}
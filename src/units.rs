@@ -0,0 +1,427 @@
+// Walking the unit graph and finding the unit that owns a source file.
+
+use crate::{
+  cargo_compat, manifest, parse_dep_info, GenerateFlagsError, TargetKind, TargetSelector,
+};
+use anyhow::{bail, Context as AnyhowContext, Result};
+use cargo::{
+  core::{
+    compiler::{Context, Unit},
+    Package, PackageIdSpec, Workspace,
+  },
+  util::interning::InternedString,
+};
+use std::{
+  collections::HashSet,
+  path::{Path, PathBuf},
+};
+
+// All units reachable from `roots`, each exactly once, with dependencies before the units
+// that depend on them. Iterative so that deep graphs can't overflow the stack.
+pub(crate) fn collect_units(cx: &Context, roots: &[Unit]) -> Vec<Unit> {
+  let mut visited = HashSet::new();
+  let mut units = Vec::new();
+
+  // The flag records whether the unit's dependencies have already been pushed.
+  let mut stack = roots
+    .iter()
+    .rev()
+    .map(|root| (root.clone(), false))
+    .collect::<Vec<_>>();
+  while let Some((unit, expanded)) = stack.pop() {
+    if expanded {
+      units.push(unit);
+      continue;
+    }
+    if !visited.insert(unit.clone()) {
+      continue;
+    }
+
+    stack.push((unit.clone(), true));
+    for dep in cx.unit_deps(&unit).iter().rev() {
+      if !visited.contains(&dep.unit) {
+        stack.push((dep.unit.clone(), false));
+      }
+    }
+  }
+
+  units
+}
+
+// Units that could be compiling `source_path`.
+// A file in one workspace member can also sit below the src dir of a sibling (e.g. a
+// member nested inside another). When the manifest that was loaded belongs to a package,
+// prefer that package's units over other members'.
+fn scope_to_current_package<'a>(matches: Vec<&'a Unit>, workspace: &Workspace) -> Vec<&'a Unit> {
+  let current = match workspace.current_opt() {
+    Some(current) => current.package_id(),
+    None => return matches,
+  };
+  if !matches.iter().any(|unit| unit.pkg.package_id() == current) {
+    return matches;
+  }
+
+  let is_sibling = |unit: &Unit| {
+    unit.pkg.package_id() != current
+      && workspace
+        .members()
+        .any(|member| member.package_id() == unit.pkg.package_id())
+  };
+  matches.into_iter().filter(|unit| !is_sibling(unit)).collect()
+}
+
+// The crate root of `unit`'s target. Only metabuild build scripts, which cargo generates,
+// have none.
+pub(crate) fn crate_root(unit: &Unit) -> Result<&Path> {
+  match unit.target.src_path().path() {
+    Some(path) => Ok(path),
+    None => bail!("{} has no crate root file", unit.target.description_named()),
+  }
+}
+
+// Source paths are compared canonically, i.e. with symlinks resolved on both sides.
+fn canonical_src_path(unit: &Unit) -> Option<PathBuf> {
+  let src_path = unit.target.src_path().path()?;
+  Some(crate::canonicalize(src_path).unwrap_or_else(|_| src_path.to_path_buf()))
+}
+
+// Whether the dep-info file rustc wrote when it last compiled `unit` lists `source_path`.
+// Cargo runs rustc from the workspace root, so relative entries are relative to it.
+fn dep_info_mentions(cx: &Context, unit: &Unit, source_path: &Path) -> bool {
+  let dep_info = cargo_compat::dep_info_path(cx, unit);
+  let files = match parse_dep_info(&dep_info) {
+    Ok(files) => files,
+    Err(_) => return false,
+  };
+  files.iter().any(|file| {
+    let file = cx.bcx.ws.root().join(file);
+    crate::canonicalize(&file).map_or(false, |file| file == source_path)
+  })
+}
+
+pub(crate) fn candidate_units<'a>(
+  cx: &Context,
+  all_units: &'a [Unit],
+  source_path: &Path,
+  package_id_spec: Option<&PackageIdSpec>,
+  on_candidate: Option<&dyn Fn(&Unit)>,
+) -> Vec<&'a Unit> {
+  let matches = all_units
+    .iter()
+    .filter(|root| {
+      let unit_src_path = match canonical_src_path(root) {
+        Some(unit_src_path) => unit_src_path,
+        None => return false,
+      };
+
+      // Running a build script isn't a compilation. Compiling one is, but its crate root
+      // sits at the package root, so only match the script file itself, which is the
+      // target's `src_path` even when `build = "..."` renames it. Its unit is a
+      // host unit, so the externs and `-L` path point at host artifacts, including any
+      // `[build-dependencies]` proc-macros.
+      if root.mode.is_run_custom_build() {
+        return false;
+      }
+      if root.target.is_custom_build() {
+        return unit_src_path == source_path;
+      }
+
+      let in_src_dir = match unit_src_path.parent() {
+        Some(src_dir) => source_path.ancestors().any(|ancestor| ancestor == src_dir),
+        None => false,
+      };
+
+      // Files generated by the build script (e.g. for `include!(concat!(env!("OUT_DIR"),
+      // ..))`) belong to every unit that gets that `OUT_DIR`.
+      let in_out_dir = || {
+        cx.find_build_script_unit(root).map_or(false, |build_unit| {
+          let out_dir = cx.files().build_script_out_dir(&build_unit);
+          let out_dir = crate::canonicalize(&out_dir).unwrap_or(out_dir);
+          source_path.starts_with(out_dir)
+        })
+      };
+
+      in_src_dir || in_out_dir()
+    })
+    .filter(|unit| match package_id_spec {
+      Some(spec) => spec.matches(unit.pkg.package_id()),
+      None => true,
+    })
+    .collect::<Vec<_>>();
+
+  // Modules can live anywhere with `#[path = ".."]` or `include!`, so as a last resort
+  // ask rustc's dep-info from an earlier build which crates read the file.
+  let matches = if matches.is_empty() {
+    all_units
+      .iter()
+      .filter(|unit| !unit.mode.is_run_custom_build() && !unit.target.is_custom_build())
+      .filter(|unit| match package_id_spec {
+        Some(spec) => spec.matches(unit.pkg.package_id()),
+        None => true,
+      })
+      .filter(|unit| dep_info_mentions(cx, unit, source_path))
+      .collect()
+  } else {
+    matches
+  };
+  let matches = scope_to_current_package(matches, cx.bcx.ws);
+  if let Some(on_candidate) = on_candidate {
+    for unit in &matches {
+      on_candidate(unit);
+    }
+  }
+  matches
+}
+
+pub(crate) fn find_target_unit<'a>(
+  cx: &Context,
+  all_units: &'a [Unit],
+  source_path: &Path,
+  package_id_spec: Option<&PackageIdSpec>,
+  on_candidate: Option<&dyn Fn(&Unit)>,
+  priority: &[TargetKind],
+) -> Result<&'a Unit> {
+  let matches = candidate_units(
+    cx,
+    all_units,
+    source_path,
+    package_id_spec,
+    on_candidate,
+  );
+  if matches.is_empty() {
+    // The package's features as resolved, which its other units were built with.
+    let owner = owning_member(cx.bcx.ws, source_path).map(|pkg| pkg.package_id());
+    let active = all_units
+      .iter()
+      .filter(|unit| Some(unit.pkg.package_id()) == owner)
+      .flat_map(|unit| unit.features.iter().copied())
+      .collect::<Vec<_>>();
+    if let Some((target, required)) = target_missing_features(cx.bcx.ws, source_path, &active) {
+      return Err(
+        GenerateFlagsError::UnitNotFound {
+          path: source_path.to_path_buf(),
+          package: package_id_spec.map(|spec| spec.to_string()),
+          hint: Some(format!(
+            "{} requires the features {}, which are not enabled; enable them, or set \
+             `FlagsOptions::enable_required_features`",
+            target,
+            required.join(", ")
+          )),
+        }
+        .into(),
+      );
+    }
+  }
+  select_unit(matches, source_path, package_id_spec, priority)
+}
+
+// The workspace member whose package directory most closely encloses `source_path`.
+pub(crate) fn owning_member<'a>(
+  workspace: &'a Workspace,
+  source_path: &Path,
+) -> Option<&'a Package> {
+  workspace
+    .members()
+    .filter(|pkg| source_path.starts_with(pkg.root()))
+    .max_by_key(|pkg| pkg.root().components().count())
+}
+
+// The required features of the target `source_path` belongs to that aren't in `active`,
+// with the target's description. Cargo leaves targets out of the unit graph unless all
+// their `required-features` are enabled. Only the package owning the file is searched: a
+// target whose crate root is the file wins, and otherwise the target with the deepest src
+// dir containing it, unless a target without required features shares that dir (like the
+// lib next to a `src/main.rs` that needs features).
+pub(crate) fn target_missing_features(
+  workspace: &Workspace,
+  source_path: &Path,
+  active: &[InternedString],
+) -> Option<(String, Vec<String>)> {
+  let pkg = owning_member(workspace, source_path)?;
+  let targets = pkg
+    .targets()
+    .iter()
+    .filter_map(|target| {
+      let src_path = target.src_path().path()?;
+      let src_path = crate::canonicalize(src_path).unwrap_or_else(|_| src_path.to_path_buf());
+      Some((target, src_path))
+    })
+    .collect::<Vec<_>>();
+
+  let target = match targets.iter().find(|(_, src_path)| src_path == source_path) {
+    Some((target, _)) => *target,
+    None => {
+      let containing = targets
+        .iter()
+        .filter_map(|(target, src_path)| {
+          let src_dir = src_path.parent()?;
+          source_path
+            .starts_with(src_dir)
+            .then(|| (*target, src_dir.components().count()))
+        })
+        .collect::<Vec<_>>();
+      let depth = containing.iter().map(|(_, depth)| *depth).max()?;
+      let deepest = containing
+        .into_iter()
+        .filter(|(_, d)| *d == depth)
+        .map(|(target, _)| target)
+        .collect::<Vec<_>>();
+      if deepest.iter().any(|target| target.required_features().is_none()) {
+        return None;
+      }
+      deepest[0]
+    }
+  };
+
+  let missing = target
+    .required_features()?
+    .iter()
+    .filter(|feature| !active.iter().any(|active| active.as_str() == feature.as_str()))
+    .cloned()
+    .collect::<Vec<_>>();
+  if missing.is_empty() {
+    return None;
+  }
+  Some((
+    format!("{} `{}`", target.kind().description(), target.name()),
+    missing,
+  ))
+}
+
+// The unit for `selector` among those that could be compiling `source_path`, so that a
+// file shared by several targets (e.g. a module under `tests/common`) can be checked as
+// part of a specific one.
+pub(crate) fn find_selected_unit<'a>(
+  cx: &Context,
+  all_units: &'a [Unit],
+  source_path: &Path,
+  selector: &TargetSelector,
+) -> Result<&'a Unit> {
+  let matches = candidate_units(cx, all_units, source_path, None, None)
+    .into_iter()
+    .filter(|unit| selector.matches(&unit.target))
+    .collect::<Vec<_>>();
+  if matches.is_empty() {
+    return Err(
+      GenerateFlagsError::NotInSelectedTarget {
+        path: source_path.to_path_buf(),
+        selector: format!("{:?}", selector),
+      }
+      .into(),
+    );
+  }
+  select_unit(matches, source_path, None, TargetKind::DEFAULT_PRIORITY)
+}
+
+pub(crate) fn select_unit<'a>(
+  matches: Vec<&'a Unit>,
+  source_path: &Path,
+  package_id_spec: Option<&PackageIdSpec>,
+  priority: &[TargetKind],
+) -> Result<&'a Unit> {
+  Ok(match matches.len() {
+    0 => {
+      return Err(
+        GenerateFlagsError::UnitNotFound {
+          path: source_path.to_path_buf(),
+          package: package_id_spec.map(|spec| spec.to_string()),
+          hint: match package_id_spec {
+            Some(_) => None,
+            None => manifest::autodiscovery_hint(source_path),
+          },
+        }
+        .into(),
+      )
+    }
+    1 => matches[0],
+    _ => {
+      // Crate roots sharing a directory (e.g. two examples, or `lib.rs` and `main.rs`)
+      // all match by directory, so the target whose root is the file itself comes first.
+      let exact = matches
+        .iter()
+        .copied()
+        .filter(|unit| canonical_src_path(unit).as_deref() == Some(source_path))
+        .collect::<Vec<_>>();
+      let matches = if exact.is_empty() { matches } else { exact };
+      if matches.len() == 1 {
+        return Ok(matches[0]);
+      }
+
+      let described = matches
+        .iter()
+        .map(|unit| format!("{} `{}`", unit.target.kind().description(), unit.target.name()))
+        .collect::<Vec<_>>();
+      let ambiguous = || GenerateFlagsError::AmbiguousUnit {
+        path: source_path.to_path_buf(),
+        candidates: described.clone(),
+      };
+      for kind in priority {
+        let of_kind = matches
+          .iter()
+          .copied()
+          .filter(|unit| kind.matches(&unit.target))
+          .collect::<Vec<_>>();
+        if of_kind.is_empty() {
+          continue;
+        }
+        // When cross-compiling, a lib used both by build scripts or proc-macros and by
+        // the target is built once per kind. The target build is what the source is
+        // normally edited for, so it wins over the host build; otherwise the first lib
+        // does. Two bins (or tests, ...) are different crates, so neither wins.
+        let same_target = of_kind.iter().all(|unit| unit.target == of_kind[0].target);
+        if *kind != TargetKind::Lib && !same_target {
+          return Err(ambiguous().into());
+        }
+        let unit = of_kind.iter().find(|unit| !unit.kind.is_host()).copied();
+        return Ok(unit.unwrap_or(of_kind[0]));
+      }
+      return Err(ambiguous().into());
+    }
+  })
+}
+
+// The unit `cargo run` would pick for the package at `manifest_path`: its `default-run`
+// bin, else its only bin, else its lib.
+pub(crate) fn find_default_unit<'a>(
+  all_units: &'a [Unit],
+  manifest_path: &Path,
+) -> Result<&'a Unit> {
+  let package_units = all_units
+    .iter()
+    .filter(|unit| unit.pkg.manifest_path() == manifest_path)
+    .filter(|unit| !unit.mode.is_run_custom_build() && !unit.target.is_custom_build())
+    .collect::<Vec<_>>();
+  let bins = package_units
+    .iter()
+    .copied()
+    .filter(|unit| unit.target.is_bin())
+    .collect::<Vec<_>>();
+
+  if let Some(pkg) = package_units.first().map(|unit| &unit.pkg) {
+    if let Some(default_run) = pkg.manifest().default_run() {
+      return bins
+        .into_iter()
+        .find(|unit| unit.target.name() == default_run)
+        .with_context(|| {
+          format!(
+            "{} sets `default-run = \"{}\"`, but there is no bin target with that name",
+            manifest_path.display(),
+            default_run
+          )
+        });
+    }
+  }
+
+  if bins.len() == 1 {
+    return Ok(bins[0]);
+  }
+  package_units
+    .into_iter()
+    .find(|unit| unit.target.is_lib())
+    .with_context(|| {
+      format!(
+        "Could not pick a default target for {}: it has several bins and no `default-run`",
+        manifest_path.display()
+      )
+    })
+}
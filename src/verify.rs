@@ -0,0 +1,298 @@
+//! Checks generated flags against the rustc command cargo actually runs, to catch flags
+//! this crate gets wrong or misses.
+//!
+//! [`against_cargo`] generates the flags for a file, makes cargo rebuild that unit by
+//! removing its fingerprint, runs `cargo check -v` (or `cargo build -v` in build mode) and
+//! compares the `Running` line for the unit with the generated arguments. Arguments are
+//! compared as a set, with options joined to their values (`--cfg feature="std"`), so
+//! order doesn't matter.
+
+use crate::{flags::TAKES_VALUE, generate, CompileMode, FlagsOptions, RustcFlag, RustcFlags};
+use anyhow::{bail, Context as AnyhowContext, Result};
+use std::{
+  env, fs,
+  path::{Path, PathBuf},
+  process::Command,
+};
+
+/// How the command cargo ran differs from the generated one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FlagsDiff {
+  /// The arguments of cargo's rustc command, after the program.
+  pub cargo_args: Vec<String>,
+  /// Arguments cargo passed that the generated flags lack.
+  pub missing: Vec<String>,
+  /// Generated arguments cargo didn't pass.
+  pub extra: Vec<String>,
+}
+
+impl FlagsDiff {
+  pub fn is_empty(&self) -> bool {
+    self.missing.is_empty() && self.extra.is_empty()
+  }
+}
+
+// Arguments that differ by design: cargo always asks for JSON diagnostics, and the
+// generated flags name the sysroot explicitly.
+fn ignored(item: &str) -> bool {
+  ["--error-format", "--json", "--color", "--diagnostic-width", "--sysroot"]
+    .iter()
+    .any(|prefix| item.starts_with(prefix))
+}
+
+// Groups `args` into options with their values, dropping what's `ignored`.
+fn items(args: &[String]) -> Vec<String> {
+  let mut items = Vec::new();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    let item = match args.as_slice().first() {
+      Some(value) if TAKES_VALUE.contains(&arg.as_str()) => {
+        args.next();
+        format!("{} {}", arg, value)
+      }
+      _ => arg.clone(),
+    };
+    if !ignored(&item) {
+      items.push(item);
+    }
+  }
+  items
+}
+
+// Splits a command line as cargo quotes it in its `Running` lines: arguments are
+// separated by spaces and quoted with `'...'` (`'\''` for a quote) or `"..."` as needed.
+fn split_command(line: &str) -> Vec<String> {
+  let mut args = Vec::new();
+  let mut arg = String::new();
+  let mut in_arg = false;
+  let mut chars = line.chars();
+  while let Some(c) = chars.next() {
+    match c {
+      ' ' => {
+        if in_arg {
+          args.push(std::mem::take(&mut arg));
+          in_arg = false;
+        }
+        continue;
+      }
+      '\'' => arg.extend(chars.by_ref().take_while(|c| *c != '\'')),
+      '"' => arg.extend(chars.by_ref().take_while(|c| *c != '"')),
+      '\\' => arg.extend(chars.next()),
+      c => arg.push(c),
+    }
+    in_arg = true;
+  }
+  if in_arg {
+    args.push(arg);
+  }
+  args
+}
+
+// The arguments of the `Running` line in cargo's `-v` output that compiles the crate
+// `flags` were generated for: the one with its crate name and root.
+fn running_args(stderr: &str, flags: &RustcFlags) -> Option<Vec<String>> {
+  let crate_name = flags.flags.iter().find_map(|flag| match flag {
+    RustcFlag::CrateName(name) => Some(name.as_str()),
+    _ => None,
+  })?;
+  stderr
+    .lines()
+    .filter_map(|line| line.trim().strip_prefix("Running `")?.strip_suffix('`'))
+    .map(split_command)
+    .map(|args| {
+      // Newer cargos print the environment before the program.
+      args
+        .into_iter()
+        .skip_while(|arg| !arg.starts_with('-') && arg.contains('='))
+        .skip(1)
+        .collect::<Vec<_>>()
+    })
+    .find(|args| {
+      let named = args
+        .windows(2)
+        .any(|pair| pair[0] == "--crate-name" && pair[1] == crate_name);
+      named && args.iter().any(|arg| flags.crate_root.ends_with(arg))
+    })
+}
+
+// Where cargo keeps the fingerprint of the unit whose outputs go to `out_dir`, named
+// after the package and the unit's `-C metadata` hash.
+fn fingerprint_dir(flags: &RustcFlags, out_dir: &Path) -> Option<PathBuf> {
+  let metadata = flags.flags.iter().find_map(|flag| match flag {
+    RustcFlag::Codegen { key, value } if key == "metadata" => value.as_deref(),
+    _ => None,
+  })?;
+  let package = flags.package_env.as_ref()?;
+  let dir = out_dir
+    .parent()?
+    .join(".fingerprint")
+    .join(format!("{}-{}", package.name, metadata));
+  Some(dir)
+}
+
+/// Generates the flags for `source_path` with `options` and diffs them against the rustc
+/// command cargo runs for the same unit. Only the features, target, profile and target
+/// directory in `options` are passed on to cargo, so options that change the generated
+/// flags in ways cargo can't (e.g. `extra_args`) show up in the diff.
+pub fn against_cargo(source_path: impl AsRef<Path>, options: &FlagsOptions) -> Result<FlagsDiff> {
+  let source_path = source_path.as_ref();
+  let flags = generate(source_path, options)?;
+  if flags.package_id.is_none() {
+    bail!("{} isn't part of a cargo package", source_path.display());
+  }
+
+  let command_line = crate::equivalent_cargo_command(source_path, options.features.clone())?;
+  let mut args = command_line.split(' ').skip(1).map(str::to_string).collect::<Vec<_>>();
+  if !matches!(options.mode, CompileMode::Check { .. }) {
+    args[0] = "build".into();
+  }
+  if let Some(target) = &options.target {
+    args.extend(vec!["--target".into(), target.clone()]);
+  }
+  if let Some(profile) = &options.profile {
+    args.extend(vec!["--profile".into(), profile.clone()]);
+  }
+  if let Some(target_dir) = &options.target_dir {
+    args.extend(vec!["--target-dir".into(), format!("{}", target_dir.display())]);
+  }
+
+  // Cargo only prints the command of units it rebuilds.
+  let out_dir = flags.flags.windows(2).find_map(|pair| match pair {
+    [RustcFlag::Raw(option), RustcFlag::Raw(dir)] if option == "--out-dir" => {
+      Some(PathBuf::from(dir))
+    }
+    _ => None,
+  });
+  if let Some(dir) = out_dir.and_then(|out_dir| fingerprint_dir(&flags, &out_dir)) {
+    if dir.exists() {
+      fs::remove_dir_all(&dir).with_context(|| format!("Could not remove {}", dir.display()))?;
+    }
+  }
+
+  let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+  let mut command = Command::new(cargo);
+  command.args(&args);
+  if let Some(dir) = source_path.parent() {
+    command.current_dir(dir);
+  }
+  let output = command
+    .output()
+    .with_context(|| format!("Could not run {:?}", command))?;
+  let stderr = String::from_utf8_lossy(&output.stderr);
+  let cargo_args = match running_args(&stderr, &flags) {
+    Some(cargo_args) => cargo_args,
+    None => bail!(
+      "`{}` didn't compile {}:\n{}",
+      command_line,
+      source_path.display(),
+      stderr.trim_end()
+    ),
+  };
+
+  Ok(diff(cargo_args, &flags))
+}
+
+// Compares cargo's rustc arguments with `flags`. Cargo passes the crate root relative to
+// the workspace root.
+pub(crate) fn diff(cargo_args: Vec<String>, flags: &RustcFlags) -> FlagsDiff {
+  let cargo_items = items(&cargo_args)
+    .into_iter()
+    .map(|item| {
+      if !item.starts_with('-') && flags.crate_root.ends_with(&item) {
+        format!("{}", flags.crate_root.display())
+      } else {
+        item
+      }
+    })
+    .collect::<Vec<_>>();
+  let generated_items = items(&flags.to_args()[1..]);
+  FlagsDiff {
+    missing: cargo_items
+      .iter()
+      .filter(|item| !generated_items.contains(item))
+      .cloned()
+      .collect(),
+    extra: generated_items
+      .iter()
+      .filter(|item| !cargo_items.contains(item))
+      .cloned()
+      .collect(),
+    cargo_args,
+  }
+}
+
+// The arguments of a `FlagsDiff` item, e.g. `["--cfg", "test"]` for `--cfg test`.
+pub(crate) fn item_args(item: &str) -> Vec<String> {
+  match item.split_once(' ') {
+    Some((option, value)) if TAKES_VALUE.contains(&option) => {
+      vec![option.to_string(), value.to_string()]
+    }
+    _ => vec![item.to_string()],
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn strings(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+  }
+
+  #[test]
+  fn commands_are_split_like_cargo_quotes_them() {
+    assert_eq!(
+      split_command(r#"rustc --crate-name basic --cfg 'feature="foo"' src/lib.rs"#),
+      strings(&["rustc", "--crate-name", "basic", "--cfg", r#"feature="foo""#, "src/lib.rs"])
+    );
+    assert_eq!(split_command(r"a\ b  c"), strings(&["a b", "c"]));
+    assert_eq!(split_command(r#"-L "dir with spaces""#), strings(&["-L", "dir with spaces"]));
+  }
+
+  #[test]
+  fn options_are_joined_to_their_values() {
+    let args = strings(&[
+      "--crate-name",
+      "basic",
+      "--error-format=json",
+      "--json=diagnostic-rendered-ansi",
+      "-C",
+      "opt-level=3",
+      "--sysroot",
+      "/sysroot",
+      "--test",
+      "src/lib.rs",
+    ]);
+    assert_eq!(
+      items(&args),
+      strings(&["--crate-name basic", "-C opt-level=3", "--test", "src/lib.rs"])
+    );
+  }
+
+  #[test]
+  fn the_running_line_of_the_crate_is_found() {
+    let flags = RustcFlags {
+      schema_version: RustcFlags::SCHEMA_VERSION,
+      flags: vec![RustcFlag::CrateName("basic".into())],
+      crate_root: PathBuf::from("/ws/src/lib.rs"),
+      env: Default::default(),
+      origin: crate::FlagsOrigin::Cargo,
+      package_id: None,
+      profile: None,
+      externs: Vec::new(),
+      rustc: PathBuf::from("rustc"),
+      wrappers: Vec::new(),
+      inputs: None,
+      file_overrides: Default::default(),
+      package_env: None,
+      timings: None,
+    };
+    let stderr = "   Compiling basic v0.1.0\n     \
+      Running `rustc --crate-name build_script_build build.rs`\n     \
+      Running `CARGO=/bin/cargo rustc --crate-name basic --edition=2018 src/lib.rs`\n";
+    assert_eq!(
+      running_args(stderr, &flags).unwrap(),
+      strings(&["--crate-name", "basic", "--edition=2018", "src/lib.rs"])
+    );
+  }
+}
@@ -0,0 +1,254 @@
+//! Helpers for tools that cargo runs as its `RUSTC_WORKSPACE_WRAPPER` (or
+//! `RUSTC_WRAPPER`), which start from the rustc command cargo passes them rather than
+//! from a source file.
+//!
+//! [`Invocation::from_env`] parses the wrapper's own arguments and environment.
+//! [`Invocation::generate`] finds the unit the command compiles and generates its flags,
+//! with the features, target and mode taken from the command, and
+//! [`Invocation::merge`] gives the command to run instead: cargo's, plus whatever the
+//! generated flags add, like [`FlagsOptions::extra_args`].
+
+use crate::{
+  flags, generate,
+  verify::{self, FlagsDiff},
+  CompileMode, FlagsOptions, RustcFlag, RustcFlags,
+};
+use anyhow::{bail, Result};
+use cargo::core::resolver::CliFeatures;
+use std::{
+  collections::BTreeMap,
+  env,
+  ffi::OsString,
+  path::{Path, PathBuf},
+};
+
+/// A rustc command as cargo passes it to a wrapper.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+  /// The rustc cargo would have run itself, the wrapper's first argument.
+  pub rustc: PathBuf,
+  /// The arguments after it.
+  pub args: Vec<String>,
+  /// The directory cargo runs rustc in, which relative paths in `args` are relative to.
+  pub cwd: PathBuf,
+  /// The environment cargo runs rustc with.
+  pub env: BTreeMap<String, String>,
+  pub crate_name: Option<String>,
+  /// The crate root, made absolute. `None` when cargo is only querying rustc, e.g. with
+  /// `-vV` or `--print`.
+  pub crate_root: Option<PathBuf>,
+  pub target: Option<String>,
+  /// Whether the crate is compiled as a test harness (`--test`).
+  pub test: bool,
+  /// The features enabled by `--cfg feature="..."`.
+  pub features: Vec<String>,
+}
+
+/// The command to run in place of cargo's.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Merged {
+  /// Cargo's arguments, followed by the generated ones it didn't pass.
+  pub args: Vec<String>,
+  /// Generated environment variables that cargo didn't set, or set differently.
+  pub env: BTreeMap<String, String>,
+}
+
+impl Invocation {
+  /// Parses the arguments a wrapper is given after its own path: the rustc, then its
+  /// arguments. `cwd` and `env` are those of the wrapper's process.
+  pub fn new(
+    args: impl IntoIterator<Item = OsString>,
+    cwd: impl AsRef<Path>,
+    env: BTreeMap<String, String>,
+  ) -> Result<Invocation> {
+    let mut args = args.into_iter().map(|arg| match arg.into_string() {
+      Ok(arg) => Ok(arg),
+      Err(arg) => bail!("rustc argument {:?} isn't UTF-8", arg),
+    });
+    let rustc = match args.next() {
+      Some(rustc) => PathBuf::from(rustc?),
+      None => bail!("A rustc wrapper needs the rustc to run as its first argument"),
+    };
+    let args = args.collect::<Result<Vec<_>>>()?;
+    let cwd = cwd.as_ref().to_path_buf();
+
+    let mut invocation = Invocation {
+      rustc,
+      args: Vec::new(),
+      cwd: cwd.clone(),
+      env,
+      crate_name: None,
+      crate_root: None,
+      target: None,
+      test: false,
+      features: Vec::new(),
+    };
+    for (arg, value) in flags::option_values(&args) {
+      match (arg, value) {
+        ("--crate-name", name) => invocation.crate_name = name.map(str::to_string),
+        ("--target", target) => invocation.target = target.map(str::to_string),
+        ("--test", _) => invocation.test = true,
+        ("--cfg", Some(cfg)) => {
+          let feature = cfg
+            .strip_prefix("feature=\"")
+            .and_then(|cfg| cfg.strip_suffix('"'));
+          if let Some(feature) = feature {
+            invocation.features.push(feature.to_string());
+          }
+        }
+        // Option values have been paired with their options, so what remains that isn't
+        // an option is the crate root.
+        (_, None) if !arg.starts_with('-') => invocation.crate_root = Some(cwd.join(arg)),
+        _ => {}
+      }
+    }
+    invocation.args = args;
+    Ok(invocation)
+  }
+
+  /// The invocation of the running wrapper, from its arguments, working directory and
+  /// environment. Variables that aren't UTF-8 are left out.
+  pub fn from_env() -> Result<Invocation> {
+    let env = env::vars_os()
+      .filter_map(|(k, v)| Some((k.into_string().ok()?, v.into_string().ok()?)))
+      .collect();
+    Invocation::new(env::args_os().skip(1), env::current_dir()?, env)
+  }
+
+  /// Whether cargo is compiling a crate the user asked for (`CARGO_PRIMARY_PACKAGE`),
+  /// rather than a dependency.
+  pub fn is_primary(&self) -> bool {
+    self.env.contains_key("CARGO_PRIMARY_PACKAGE")
+  }
+
+  /// Whether cargo is only querying rustc, so there's no crate to generate flags for.
+  pub fn is_query(&self) -> bool {
+    self.crate_root.is_none()
+  }
+
+  /// `options`, changed to match the command: its package (from `CARGO_PKG_NAME` and
+  /// `CARGO_PKG_VERSION`), features, target, and mode (test, build or check, going by
+  /// `--test` and `--emit`). Features are only taken for primary packages, as cargo
+  /// doesn't allow selecting features of other packages.
+  pub fn options(&self, mut options: FlagsOptions) -> Result<FlagsOptions> {
+    if let (Some(name), Some(version)) =
+      (self.env.get("CARGO_PKG_NAME"), self.env.get("CARGO_PKG_VERSION"))
+    {
+      options.package = Some(format!("{}:{}", name, version));
+    }
+    if self.is_primary() {
+      options.features = CliFeatures::from_command_line(&self.features, false, false)?;
+    }
+    if self.target.is_some() {
+      options.target = self.target.clone();
+    }
+    let links = self
+      .args
+      .iter()
+      .filter_map(|arg| arg.strip_prefix("--emit="))
+      .any(|emit| emit.split(',').any(|kind| kind.starts_with("link")));
+    options.mode = if self.test {
+      CompileMode::Test
+    } else if links {
+      CompileMode::Build
+    } else {
+      CompileMode::Check { test: false }
+    };
+    Ok(options)
+  }
+
+  /// Generates the flags of the unit the command compiles, with [`Invocation::options`].
+  /// Fails if the unit found has a different crate name than the command.
+  pub fn generate(&self, options: FlagsOptions) -> Result<RustcFlags> {
+    let crate_root = match &self.crate_root {
+      Some(crate_root) => crate_root,
+      None => bail!("`{}` doesn't compile a crate", self.args.join(" ")),
+    };
+    let flags = generate(crate_root, &self.options(options)?)?;
+    let crate_name = flags.flags.iter().find_map(|flag| match flag {
+      RustcFlag::CrateName(name) => Some(name),
+      _ => None,
+    });
+    if let (Some(expected), Some(found)) = (&self.crate_name, crate_name) {
+      if expected != found {
+        bail!(
+          "{} was matched to crate `{}`, but cargo is compiling `{}`",
+          crate_root.display(),
+          found,
+          expected
+        );
+      }
+    }
+    Ok(flags)
+  }
+
+  /// How the command differs from `flags`, compared like [`verify::against_cargo`] does.
+  pub fn validate(&self, flags: &RustcFlags) -> FlagsDiff {
+    verify::diff(self.args.clone(), flags)
+  }
+
+  /// Cargo's command with what `flags` add to it. Arguments cargo passed that `flags` lack
+  /// are kept, since cargo knows best; see [`Invocation::validate`] to find them.
+  pub fn merge(&self, flags: &RustcFlags) -> Merged {
+    let mut args = self.args.clone();
+    for item in self.validate(flags).extra {
+      args.extend(verify::item_args(&item));
+    }
+    let env = flags
+      .env
+      .iter()
+      .filter(|(k, v)| self.env.get(k.as_str()) != Some(*v))
+      .map(|(k, v)| (k.clone(), v.clone()))
+      .collect();
+    Merged { args, env }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn invocation(args: &[&str]) -> Invocation {
+    let args = std::iter::once("rustc").chain(args.iter().copied()).map(OsString::from);
+    Invocation::new(args, "/ws", BTreeMap::new()).unwrap()
+  }
+
+  #[test]
+  fn parses_cargos_arguments() {
+    let invocation = invocation(&[
+      "--crate-name",
+      "foo",
+      "--edition=2018",
+      "src/lib.rs",
+      "--cfg",
+      "feature=\"std\"",
+      "--cfg=feature=\"alloc\"",
+      "--target",
+      "wasm32-unknown-unknown",
+      "--test",
+    ]);
+    assert_eq!(invocation.rustc, PathBuf::from("rustc"));
+    assert_eq!(invocation.crate_name.as_deref(), Some("foo"));
+    assert_eq!(invocation.crate_root, Some(PathBuf::from("/ws/src/lib.rs")));
+    assert_eq!(invocation.target.as_deref(), Some("wasm32-unknown-unknown"));
+    assert!(invocation.test);
+    assert_eq!(invocation.features, vec!["std".to_string(), "alloc".to_string()]);
+  }
+
+  #[test]
+  fn option_values_are_not_crate_roots() {
+    let invocation = invocation(&["-o", "out.rs", "--out-dir", "dir.rs", "main.rs"]);
+    assert_eq!(invocation.crate_root, Some(PathBuf::from("/ws/main.rs")));
+  }
+
+  #[test]
+  fn queries_have_no_crate_root() {
+    assert!(invocation(&["-vV"]).is_query());
+    assert!(invocation(&["-", "--print", "cfg", "--crate-type", "lib"]).is_query());
+  }
+
+  #[test]
+  fn needs_a_rustc() {
+    assert!(Invocation::new(Vec::new(), "/ws", BTreeMap::new()).is_err());
+  }
+}
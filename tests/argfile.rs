@@ -0,0 +1,37 @@
+// Passing the flags through a rustc response file.
+
+mod common;
+
+use generate_rustc_flags::{generate, generate_rustc_flags_with, FlagsOptions, RustcFlag};
+use std::{fs, process::Command};
+
+#[test]
+fn rustc_accepts_the_argfile() {
+  let ws = common::fixture("basic", "argfile");
+  let argfile = ws.join("flags.args");
+  let options = FlagsOptions {
+    argfile: Some(argfile.clone()),
+    ..FlagsOptions::default()
+  };
+  let (flags, args) = common::in_dir(&ws, || {
+    let flags = generate(ws.join("src/lib.rs"), &FlagsOptions::default()).unwrap();
+    (flags, generate_rustc_flags_with(ws.join("src/lib.rs"), &options).unwrap())
+  });
+  assert_eq!(args, ["rustc".to_string(), format!("@{}", argfile.display())]);
+  let contents = fs::read_to_string(&argfile).unwrap();
+  assert_eq!(contents.lines().collect::<Vec<_>>(), flags.to_args()[1..]);
+
+  let status = Command::new(&args[0]).args(&args[1..]).current_dir(&ws).status().unwrap();
+  assert!(status.success());
+}
+
+#[test]
+fn newlines_are_rejected() {
+  let ws = common::fixture("basic", "argfile_newline");
+  let mut flags =
+    common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &FlagsOptions::default()).unwrap());
+  flags.flags.push(RustcFlag::Cfg("a\nb".to_string()));
+  let err = flags.write_argfile(ws.join("flags.args")).unwrap_err();
+  assert!(err.to_string().contains("contains a newline"), "{}", err);
+  assert!(!ws.join("flags.args").exists());
+}
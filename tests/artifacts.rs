@@ -0,0 +1,31 @@
+// The metadata hash and output paths that name a unit's artifacts, as cargo passes them.
+
+mod common;
+
+use generate_rustc_flags::{generate, CompileMode, FlagsOptions};
+use std::{fs, process::Command};
+
+#[test]
+fn rustc_writes_the_artifact_cargo_would() {
+  let ws = common::fixture("basic", "artifacts");
+  let options = FlagsOptions {
+    mode: CompileMode::Build,
+    ..FlagsOptions::default()
+  };
+  let args = generate(ws.join("src/lib.rs"), &options).unwrap().to_args();
+  let codegen = common::values(&args, "-C");
+  let metadata = codegen
+    .iter()
+    .find_map(|value| value.strip_prefix("metadata="))
+    .unwrap();
+  assert!(codegen.contains(&format!("extra-filename=-{}", metadata)), "{:?}", codegen);
+  let incremental = ws.join("target/debug/incremental");
+  assert!(codegen.contains(&format!("incremental={}", incremental.display())), "{:?}", codegen);
+  let deps = ws.join("target/debug/deps");
+  assert_eq!(common::values(&args, "--out-dir"), [format!("{}", deps.display())]);
+
+  let status = Command::new(&args[0]).args(&args[1..]).current_dir(&ws).status().unwrap();
+  assert!(status.success());
+  let rlib = deps.join(format!("libbasic-{}.rlib", metadata));
+  assert!(fs::metadata(&rlib).is_ok(), "{}", rlib.display());
+}
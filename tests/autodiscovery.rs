@@ -0,0 +1,26 @@
+// Files that would be targets if the package didn't disable target autodiscovery.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions};
+
+#[test]
+fn undeclared_bins_explain_autobins() {
+  let ws = common::fixture("autobins", "autodiscovery_autobins");
+  let options = FlagsOptions::default();
+  let err = common::in_dir(&ws, || generate(ws.join("src/bin/foo.rs"), &options).unwrap_err());
+  let message = err.to_string();
+  assert!(message.starts_with("Could not find unit for path"), "{}", message);
+  assert!(message.contains("sets `autobins = false`"), "{}", message);
+  assert!(message.contains("[[bin]]"), "{}", message);
+}
+
+#[test]
+fn other_files_get_the_plain_error() {
+  let ws = common::fixture("autobins", "autodiscovery_other");
+  std::fs::create_dir_all(ws.join("scripts")).unwrap();
+  std::fs::write(ws.join("scripts/gen.rs"), "fn main() {}\n").unwrap();
+  let options = FlagsOptions::default();
+  let err = common::in_dir(&ws, || generate(ws.join("scripts/gen.rs"), &options).unwrap_err());
+  assert!(!err.to_string().contains("autobins"), "{}", err);
+}
@@ -0,0 +1,31 @@
+// Artifact dependencies, which are built first so their paths can be set in the env.
+
+mod common;
+
+use generate_rustc_flags::{generate, BuildScriptMode, FlagsOptions};
+use std::path::Path;
+
+#[test]
+fn bin_artifacts_are_built_and_named() {
+  let ws = common::fixture("bindeps", "bindeps_built");
+  let flags = generate(ws.join("src/lib.rs"), &FlagsOptions::default()).unwrap();
+  let tool = &flags.env["CARGO_BIN_FILE_TOOL"];
+  assert!(Path::new(tool).is_file(), "{}", tool);
+  assert_eq!(&flags.env["CARGO_BIN_FILE_TOOL_tool"], tool);
+  assert!(flags.env.contains_key("CARGO_BIN_DIR_TOOL"));
+
+  let output = flags.command().current_dir(&ws).output().unwrap();
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn artifacts_are_only_built_when_running_build_scripts() {
+  let ws = common::fixture("bindeps", "bindeps_skipped");
+  let options = FlagsOptions {
+    build_scripts: BuildScriptMode::Skip,
+    ..FlagsOptions::default()
+  };
+  let flags = generate(ws.join("src/lib.rs"), &options).unwrap();
+  assert!(!flags.env.contains_key("CARGO_BIN_FILE_TOOL"));
+  assert!(!ws.join("target/artifact").exists());
+}
@@ -0,0 +1,173 @@
+// Environment variables set by build scripts.
+
+mod common;
+
+use generate_rustc_flags::{
+  generate, refresh_env, requires_build_script, BuildScriptMode, CliFeatures, CompileMode,
+  FlagsOptions, RustcFlag,
+};
+use std::{fs, path::Path};
+
+#[test]
+fn non_utf8_values_are_left_out() {
+  let ws = common::fixture("build_env", "build_env_non_utf8");
+  let options = FlagsOptions::default();
+  let flags = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap());
+  assert_eq!(flags.env.get("UTF8").map(String::as_str), Some("ok"));
+  assert!(!flags.env.contains_key("NOT_UTF8"));
+}
+
+#[test]
+fn test_mode_works_without_running_build_scripts() {
+  let ws = common::fixture("build_env", "build_env_skipped");
+  let options = FlagsOptions {
+    mode: CompileMode::Check { test: true },
+    build_scripts: BuildScriptMode::Skip,
+    ..FlagsOptions::default()
+  };
+  let flags = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap());
+  assert!(flags.flags.contains(&RustcFlag::Raw("--test".into())));
+  assert!(!flags.env.contains_key("UTF8"));
+  assert!(!flags.env.contains_key("OUT_DIR"));
+  assert!(!has_run(&ws));
+}
+
+// Whether a build script has run in the fixture's target directory.
+fn has_run(ws: &Path) -> bool {
+  let build_dirs = fs::read_dir(ws.join("target/debug/build")).into_iter().flatten();
+  build_dirs.into_iter().any(|dir| dir.unwrap().path().join("output").exists())
+}
+
+#[test]
+fn cached_outputs_are_read_without_running_anything() {
+  let ws = common::fixture("build_env", "build_env_cached");
+  let cached = FlagsOptions {
+    build_scripts: BuildScriptMode::UseCached,
+    ..FlagsOptions::default()
+  };
+  let flags = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &cached).unwrap());
+  assert!(!flags.env.contains_key("UTF8"));
+  assert!(!has_run(&ws));
+
+  let options = FlagsOptions::default();
+  let ran = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap());
+  let flags = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &cached).unwrap());
+  assert_eq!(flags.env["UTF8"], "ok");
+  assert_eq!(flags.env["OUT_DIR"], ran.env["OUT_DIR"]);
+  assert!(!flags.env.contains_key("NOT_UTF8"));
+}
+
+#[test]
+fn refreshing_follows_a_moved_target_dir() {
+  let ws = common::fixture("build_env", "build_env_refresh");
+  let options = FlagsOptions::default();
+  let flags = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap());
+  assert!(flags.env["OUT_DIR"].starts_with(&ws.join("target").display().to_string()));
+
+  let moved = ws.join("moved-target");
+  fs::create_dir_all(ws.join(".cargo")).unwrap();
+  fs::write(
+    ws.join(".cargo/config.toml"),
+    format!("[build]\ntarget-dir = \"{}\"\n", moved.display()),
+  )
+  .unwrap();
+  let env = common::in_dir(&ws, || refresh_env(&flags, &options).unwrap());
+  assert!(env["OUT_DIR"].starts_with(&moved.display().to_string()), "{}", env["OUT_DIR"]);
+  assert_eq!(env["UTF8"], "ok");
+}
+
+#[test]
+fn build_scripts_are_detected_without_running_them() {
+  let ws = common::fixture("build_env", "build_env_requires");
+  let requires = common::in_dir(&ws, || {
+    requires_build_script(ws.join("src/lib.rs"), CliFeatures::new_all(false)).unwrap()
+  });
+  assert!(requires);
+  assert!(!has_run(&ws));
+
+  let ws = common::fixture("basic", "build_env_not_required");
+  let requires = common::in_dir(&ws, || {
+    requires_build_script(ws.join("src/lib.rs"), CliFeatures::new_all(false)).unwrap()
+  });
+  assert!(!requires);
+}
+
+#[test]
+fn links_are_passed_on() {
+  let ws = common::fixture("build_env", "build_env_links");
+  let options = FlagsOptions::default();
+  let flags = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap());
+  assert!(!flags.env.contains_key("CARGO_MANIFEST_LINKS"));
+
+  let manifest = fs::read_to_string(ws.join("Cargo.toml")).unwrap();
+  let manifest = manifest.replace("[package]\n", "[package]\nlinks = \"foo\"\n");
+  fs::write(ws.join("Cargo.toml"), manifest).unwrap();
+  let flags = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap());
+  assert_eq!(flags.env["CARGO_MANIFEST_LINKS"], "foo");
+}
+
+#[test]
+fn build_script_cfgs_never_gate_dependencies() {
+  let externs = |build_scripts: BuildScriptMode| {
+    let ws = common::fixture("build_cfg", &format!("build_cfg_{:?}", build_scripts));
+    let options = FlagsOptions {
+      build_scripts,
+      ..FlagsOptions::default()
+    };
+    let flags = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap());
+    flags
+      .flags
+      .into_iter()
+      .filter(|flag| matches!(flag, RustcFlag::Extern { .. }))
+      .count()
+  };
+  assert_eq!(externs(BuildScriptMode::Run), 0);
+  assert_eq!(externs(BuildScriptMode::Skip), 0);
+}
+
+#[test]
+fn build_scripts_see_the_job_count() {
+  let ws = common::fixture("build_env", "build_env_jobs");
+  let options = FlagsOptions {
+    jobs: Some(3),
+    ..FlagsOptions::default()
+  };
+  let flags = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap());
+  assert_eq!(flags.env["BUILD_NUM_JOBS"], "3");
+  // Only the build script's own environment has it, not rustc's.
+  assert!(!flags.env.contains_key("NUM_JOBS"));
+}
+
+#[test]
+fn build_script_cfgs_are_passed_to_the_crate() {
+  let cfgs = |build_scripts: BuildScriptMode| {
+    let ws = common::fixture("build_cfg", &format!("build_cfg_passed_{:?}", build_scripts));
+    let options = FlagsOptions {
+      build_scripts,
+      ..FlagsOptions::default()
+    };
+    let flags = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap());
+    flags.flags.contains(&RustcFlag::Cfg("gate".into()))
+  };
+  assert!(cfgs(BuildScriptMode::Run));
+  assert!(!cfgs(BuildScriptMode::Skip));
+}
+
+#[test]
+fn build_script_link_flags_are_passed_to_the_crate() {
+  let ws = common::fixture("basic", "build_env_link_flags");
+  let native = ws.join("native");
+  fs::write(
+    ws.join("build.rs"),
+    format!(
+      "fn main() {{\n  println!(\"cargo:rustc-link-search=native={}\");\n  \
+       println!(\"cargo:rustc-link-lib=static=foo\");\n}}\n",
+      native.display()
+    ),
+  )
+  .unwrap();
+  let options = FlagsOptions::default();
+  let args = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap().to_args());
+  assert!(common::values(&args, "-L").contains(&format!("native={}", native.display())));
+  assert_eq!(common::values(&args, "-l"), ["static=foo"]);
+}
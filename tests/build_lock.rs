@@ -0,0 +1,26 @@
+// Not waiting for another cargo's build lock.
+
+mod common;
+
+use fs2::FileExt;
+use generate_rustc_flags::{generate, FlagsOptions};
+use std::fs::File;
+
+#[test]
+fn a_held_lock_fails_fast() {
+  let ws = common::fixture("basic", "build_lock");
+  let options = FlagsOptions {
+    wait_for_build_lock: false,
+    ..FlagsOptions::default()
+  };
+  generate(ws.join("src/lib.rs"), &options).unwrap();
+
+  // Stands in for a concurrent `cargo build`.
+  let lock = File::open(ws.join("target/debug/.cargo-lock")).unwrap();
+  lock.lock_exclusive().unwrap();
+  let err = generate(ws.join("src/lib.rs"), &options).unwrap_err();
+  assert!(err.to_string().contains("is locked by another cargo process"), "{}", err);
+
+  lock.unlock().unwrap();
+  generate(ws.join("src/lib.rs"), &options).unwrap();
+}
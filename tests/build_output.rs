@@ -0,0 +1,36 @@
+// Output of the compilations run for build scripts, which is kept off the terminal and
+// shows up in errors instead.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions, GenerateFlagsError};
+use std::fs;
+
+#[test]
+fn compiler_errors_are_in_the_error() {
+  let ws = common::fixture("build_env", "build_output_compile_error");
+  fs::write(ws.join("build.rs"), "fn main() {\n  let x: u32 = \"\";\n}\n").unwrap();
+  let err = generate(ws.join("src/lib.rs"), &FlagsOptions::default()).unwrap_err();
+  let message = format!("{:#}", err);
+  assert!(message.contains("Output of compiling build-script `build-script-build`"), "{}", message);
+  assert!(message.contains("mismatched types"), "{}", message);
+}
+
+#[test]
+fn failing_build_scripts_name_the_package() {
+  let ws = common::fixture("build_env", "build_output_panic");
+  fs::write(ws.join("build.rs"), "fn main() {\n  panic!(\"no luck\");\n}\n").unwrap();
+  let err = generate(ws.join("src/lib.rs"), &FlagsOptions::default()).unwrap_err();
+  let failed = err
+    .chain()
+    .find_map(|cause| cause.downcast_ref::<GenerateFlagsError>())
+    .cloned();
+  assert_eq!(
+    failed,
+    Some(GenerateFlagsError::BuildScriptFailed {
+      package: "build_env".into()
+    })
+  );
+  // Cargo includes what the script printed.
+  assert!(format!("{:#}", err).contains("no luck"), "{:#}", err);
+}
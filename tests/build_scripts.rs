@@ -0,0 +1,55 @@
+// Compiling packages' build scripts, and the `OUT_DIR` they fill.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions, RustcFlag};
+use std::path::Path;
+
+#[test]
+fn build_dependency_proc_macros_are_host_artifacts() {
+  let ws = common::fixture("build_deps", "build_scripts_proc_macro");
+  let options = FlagsOptions::default();
+  let flags = common::in_dir(&ws, || generate(ws.join("build.rs"), &options).unwrap());
+  assert!(flags.flags.contains(&RustcFlag::CrateName("build_script_build".into())));
+
+  let mac = flags
+    .flags
+    .iter()
+    .find_map(|flag| match flag {
+      RustcFlag::Extern { name, path, .. } if name == "mac" => path.clone(),
+      _ => None,
+    })
+    .unwrap();
+  // A proc-macro is a dylib in the host's deps directory, which `-L` names.
+  assert_eq!(mac.extension().unwrap(), std::env::consts::DLL_EXTENSION);
+  let search_paths = flags
+    .flags
+    .iter()
+    .filter_map(|flag| match flag {
+      RustcFlag::LibSearch { path, .. } => Some(path.clone()),
+      _ => None,
+    })
+    .collect::<Vec<_>>();
+  assert!(search_paths.iter().any(|dir| mac.parent() == Some(dir.as_path())));
+}
+
+#[test]
+fn custom_build_script_paths_set_out_dir() {
+  let ws = common::fixture("custom_build", "build_scripts_out_dir");
+  let options = FlagsOptions::default();
+  let flags = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap());
+  let out_dir = Path::new(&flags.env["OUT_DIR"]);
+  assert!(out_dir.join("answer.rs").is_file(), "{}", out_dir.display());
+
+  let output = flags.command().current_dir(&ws).output().unwrap();
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn custom_build_scripts_are_their_own_crate_root() {
+  let ws = common::fixture("custom_build", "build_scripts_crate_root");
+  let options = FlagsOptions::default();
+  let flags = common::in_dir(&ws, || generate(ws.join("custom_build.rs"), &options).unwrap());
+  assert!(flags.flags.contains(&RustcFlag::CrateName("build_script_custom_build".into())));
+  assert!(!flags.env.contains_key("OUT_DIR"));
+}
@@ -0,0 +1,76 @@
+// `FlagCache` hits, misses and invalidation, observed through the entries it stores.
+
+#![cfg(feature = "cache")]
+
+mod common;
+
+use generate_rustc_flags::{cache::FlagCache, FlagsOptions};
+use std::fs;
+use std::path::Path;
+
+fn entries(ws: &Path) -> usize {
+  fs::read_dir(ws.join("target/.rustc-flags")).map_or(0, |dir| dir.count())
+}
+
+// The first generation writes the lockfile, which is part of the key, so the second one
+// is the first that can hit.
+fn warmed(ws: &Path, options: FlagsOptions) -> FlagCache {
+  let cache = FlagCache::new(options);
+  cache.generate(ws.join("src/lib.rs")).unwrap();
+  cache.generate(ws.join("src/lib.rs")).unwrap();
+  cache
+}
+
+#[test]
+fn same_key_is_a_hit() {
+  let ws = common::fixture("basic", "cache_hit");
+  let cache = warmed(&ws, FlagsOptions::default());
+  let before = entries(&ws);
+  let first = cache.generate(ws.join("src/lib.rs")).unwrap();
+  let second = cache.generate(ws.join("src/lib.rs")).unwrap();
+  assert_eq!(entries(&ws), before);
+  assert_eq!(first.to_args(), second.to_args());
+}
+
+#[test]
+fn every_option_is_part_of_the_key() {
+  let ws = common::fixture("basic", "cache_options");
+  warmed(&ws, FlagsOptions::default());
+  let before = entries(&ws);
+  let options = FlagsOptions {
+    extra_args: vec!["--cfg=extra".into()],
+    ..FlagsOptions::default()
+  };
+  let flags = FlagCache::new(options).generate(ws.join("src/lib.rs")).unwrap();
+  assert_eq!(entries(&ws), before + 1);
+  assert!(flags.to_args().contains(&"--cfg=extra".to_string()));
+}
+
+#[test]
+fn manifest_and_lockfile_edits_invalidate() {
+  let ws = common::fixture("basic", "cache_invalidation");
+  let cache = warmed(&ws, FlagsOptions::default());
+  let before = entries(&ws);
+
+  let manifest = fs::read_to_string(ws.join("Cargo.toml")).unwrap();
+  fs::write(ws.join("Cargo.toml"), manifest.replace("0.1.0", "0.2.0")).unwrap();
+  let flags = cache.generate(ws.join("src/lib.rs")).unwrap();
+  assert!(entries(&ws) > before);
+  assert_eq!(flags.env["CARGO_PKG_VERSION"], "0.2.0");
+  // Settles the lockfile cargo rewrote for the new version.
+  cache.generate(ws.join("src/lib.rs")).unwrap();
+  let before = entries(&ws);
+
+  let lockfile = fs::read_to_string(ws.join("Cargo.lock")).unwrap();
+  fs::write(ws.join("Cargo.lock"), format!("{}\n", lockfile)).unwrap();
+  cache.generate(ws.join("src/lib.rs")).unwrap();
+  assert!(entries(&ws) > before);
+}
+
+#[test]
+fn clear_removes_entries() {
+  let ws = common::fixture("basic", "cache_clear");
+  let cache = warmed(&ws, FlagsOptions::default());
+  cache.clear(ws.join("src/lib.rs")).unwrap();
+  assert_eq!(entries(&ws), 0);
+}
@@ -0,0 +1,63 @@
+// Deterministic flags, and the cache keys derived from them.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions, FlagsOrigin, RustcFlag, RustcFlags};
+use std::{collections::BTreeMap, path::PathBuf};
+
+#[test]
+fn repeated_generation_gives_the_same_key() {
+  let ws = common::fixture("deps", "canonical_repeated");
+  let options = FlagsOptions::default();
+  let first = common::in_dir(&ws, || generate(ws.join("src/main.rs"), &options).unwrap());
+  let second = common::in_dir(&ws, || generate(ws.join("src/main.rs"), &options).unwrap());
+  assert_eq!(first.canonical_string(), second.canonical_string());
+  assert_eq!(first.fingerprint(), second.fingerprint());
+
+  let externs = first
+    .flags
+    .iter()
+    .filter_map(|flag| match flag {
+      RustcFlag::Extern { name, .. } => Some(name.as_str()),
+      _ => None,
+    })
+    .collect::<Vec<_>>();
+  assert_eq!(externs, ["app", "unused", "used"]);
+  let names = first.externs.iter().map(|dep| dep.name.as_str()).collect::<Vec<_>>();
+  assert_eq!(names, externs);
+}
+
+#[test]
+fn the_key_covers_the_environment() {
+  let ws = common::fixture("basic", "canonical_env");
+  let options = FlagsOptions::default();
+  let flags = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap());
+  let canonical = flags.canonical_string();
+  assert!(canonical.contains("\nCARGO_PKG_NAME=basic\n"), "{}", canonical);
+
+  let mut changed = flags.clone();
+  changed.env.insert("CARGO_PKG_NAME".into(), "other".into());
+  assert_ne!(changed.fingerprint(), flags.fingerprint());
+}
+
+#[test]
+fn fingerprints_are_fnv1a() {
+  let flags = RustcFlags {
+    schema_version: RustcFlags::SCHEMA_VERSION,
+    flags: Vec::new(),
+    crate_root: PathBuf::from("lib.rs"),
+    env: BTreeMap::new(),
+    origin: FlagsOrigin::Fallback,
+    package_id: None,
+    profile: None,
+    externs: Vec::new(),
+    rustc: PathBuf::from("a"),
+    wrappers: Vec::new(),
+    inputs: None,
+    file_overrides: BTreeMap::new(),
+    package_env: None,
+    timings: None,
+  };
+  assert_eq!(flags.canonical_string(), "a");
+  assert_eq!(flags.fingerprint(), 0xaf63dc4c8601ec8c);
+}
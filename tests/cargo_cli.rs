@@ -0,0 +1,61 @@
+// The backend that runs the cargo binary, checked against the linked cargo.
+
+#![cfg(feature = "cargo-cli")]
+
+mod common;
+
+use generate_rustc_flags::{cargo_cli, generate, FlagsOptions, RustcFlag};
+
+// The flags both backends reconstruct, rendered.
+fn comparable(flags: &[RustcFlag]) -> Vec<String> {
+  let flags = flags
+    .iter()
+    .filter(|flag| {
+      matches!(
+        flag,
+        RustcFlag::CrateName(_)
+          | RustcFlag::CrateType(_)
+          | RustcFlag::Edition(_)
+          | RustcFlag::Cfg(_)
+          | RustcFlag::Input(_)
+      )
+    })
+    .cloned()
+    .collect::<Vec<_>>();
+  let mut args = flags.iter().flat_map(RustcFlag::render).collect::<Vec<_>>();
+  args.sort();
+  args
+}
+
+#[test]
+fn the_backends_agree() {
+  let ws = common::fixture("deps", "cargo_cli_deps");
+  let options = FlagsOptions::default();
+  let linked = generate(ws.join("src/main.rs"), &options).unwrap();
+  let cli = cargo_cli::generate(ws.join("src/main.rs"), &options).unwrap();
+
+  assert_eq!(comparable(&cli.flags), comparable(&linked.flags));
+  let names = |flags: &generate_rustc_flags::RustcFlags| {
+    let mut names = flags.externs.iter().map(|dep| dep.name.clone()).collect::<Vec<_>>();
+    names.sort();
+    names
+  };
+  assert_eq!(names(&cli), names(&linked));
+  assert_eq!(cli.package_env, linked.package_env);
+  assert_eq!(cli.env["CARGO_CRATE_NAME"], "app");
+
+  let output = cli.command().current_dir(&ws).output().unwrap();
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn build_script_outputs_are_applied() {
+  let ws = common::fixture("build_cfg", "cargo_cli_build_cfg");
+  let options = FlagsOptions::default();
+  let linked = generate(ws.join("src/lib.rs"), &options).unwrap();
+  let cli = cargo_cli::generate(ws.join("src/lib.rs"), &options).unwrap();
+  assert_eq!(comparable(&cli.flags), comparable(&linked.flags));
+  assert!(cli.flags.contains(&RustcFlag::Cfg("gate".into())));
+  // From the cargo binary's run, which needn't hash like the linked cargo.
+  assert!(std::path::Path::new(&cli.env["OUT_DIR"]).is_dir());
+}
@@ -0,0 +1,75 @@
+// Reproducing the `--check-cfg` flags of newer cargos.
+
+mod common;
+
+use generate_rustc_flags::{cargo_version, generate, CargoCompat, FlagsOptions};
+
+fn check_cfgs(cargo_compat: CargoCompat, test: &str) -> Vec<String> {
+  let ws = common::fixture("basic", test);
+  let options = FlagsOptions {
+    cargo_compat,
+    ..FlagsOptions::default()
+  };
+  let args = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap().to_args());
+  common::values(&args, "--check-cfg")
+}
+
+#[test]
+fn check_cfg_follows_the_compatibility_mode() {
+  assert_eq!(check_cfgs(CargoCompat::Linked, "cargo_compat_linked"), Vec::<String>::new());
+  assert_eq!(
+    check_cfgs(CargoCompat::CheckCfg, "cargo_compat_check_cfg"),
+    ["cfg(docsrs)", r#"cfg(feature, values("bar", "default", "foo"))"#]
+  );
+}
+
+#[test]
+fn the_modeled_cargo_version_is_exposed() {
+  let version = cargo_version();
+  let words = version.split_whitespace();
+  assert!(words.into_iter().any(|word| semver::Version::parse(word).is_ok()), "{}", version);
+}
+
+fn append(manifest: &std::path::Path, toml: &str) {
+  use std::io::Write;
+  let mut manifest = std::fs::OpenOptions::new().append(true).open(manifest).unwrap();
+  manifest.write_all(toml.as_bytes()).unwrap();
+}
+
+#[test]
+fn declared_check_cfgs_follow_the_features() {
+  let ws = common::fixture("basic", "cargo_compat_lints");
+  append(
+    &ws.join("Cargo.toml"),
+    "\n[lints.rust]\nunexpected_cfgs = { level = \"warn\", check-cfg = ['cfg(tokio_unstable)'] }\n",
+  );
+  let options = FlagsOptions {
+    cargo_compat: CargoCompat::CheckCfg,
+    ..FlagsOptions::default()
+  };
+  let args = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap().to_args());
+  assert_eq!(
+    common::values(&args, "--check-cfg"),
+    [
+      "cfg(docsrs)",
+      r#"cfg(feature, values("bar", "default", "foo"))"#,
+      "cfg(tokio_unstable)"
+    ]
+  );
+  assert!(args.contains(&"--warn=unexpected_cfgs".to_string()), "{:?}", args);
+}
+
+#[test]
+fn malformed_check_cfgs_are_an_error() {
+  let ws = common::fixture("basic", "cargo_compat_lints_malformed");
+  append(
+    &ws.join("Cargo.toml"),
+    "\n[lints.rust]\nunexpected_cfgs = { level = \"warn\", check-cfg = 'cfg(foo)' }\n",
+  );
+  let options = FlagsOptions {
+    cargo_compat: CargoCompat::CheckCfg,
+    ..FlagsOptions::default()
+  };
+  let err = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap_err());
+  assert!(format!("{:#}", err).contains("must be an array"), "{:#}", err);
+}
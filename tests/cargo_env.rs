@@ -0,0 +1,69 @@
+// The environment cargo gives rustc, which crates read with `env!`.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions};
+use std::{fs, io::Write, path::Path};
+
+fn append(manifest: &Path, toml: &str) {
+  let mut manifest = fs::OpenOptions::new().append(true).open(manifest).unwrap();
+  manifest.write_all(toml.as_bytes()).unwrap();
+}
+
+#[test]
+fn package_metadata_is_set_even_when_missing() {
+  let ws = common::fixture("basic", "cargo_env_missing");
+  let flags = generate(ws.join("src/lib.rs"), &FlagsOptions::default()).unwrap();
+  for var in ["CARGO_PKG_AUTHORS", "CARGO_PKG_DESCRIPTION", "CARGO_PKG_RUST_VERSION"] {
+    assert_eq!(flags.env[var], "", "{}", var);
+  }
+  assert_eq!(flags.env["CARGO_PRIMARY_PACKAGE"], "1");
+  assert!(Path::new(&flags.env["CARGO"]).is_absolute(), "{}", flags.env["CARGO"]);
+  assert!(!flags.env.contains_key("CARGO_BIN_NAME"));
+  assert!(!flags.env.contains_key("CARGO_TARGET_TMPDIR"));
+}
+
+#[test]
+fn package_metadata_comes_from_the_manifest() {
+  let ws = common::fixture("basic", "cargo_env_metadata");
+  let manifest = fs::read_to_string(ws.join("Cargo.toml")).unwrap().replace(
+    "edition = \"2018\"\n",
+    "edition = \"2018\"\nauthors = [\"A\", \"B\"]\ndescription = \"Basic\"\nlicense = \"MIT\"\n\
+     rust-version = \"1.56\"\n",
+  );
+  fs::write(ws.join("Cargo.toml"), manifest).unwrap();
+  fs::write(
+    ws.join("src/lib.rs"),
+    "pub const ALL: [&str; 4] = [env!(\"CARGO_PKG_AUTHORS\"), env!(\"CARGO_PKG_DESCRIPTION\"), \
+     env!(\"CARGO_PKG_LICENSE\"), env!(\"CARGO_PRIMARY_PACKAGE\")];\n",
+  )
+  .unwrap();
+
+  let flags = generate(ws.join("src/lib.rs"), &FlagsOptions::default()).unwrap();
+  assert_eq!(flags.env["CARGO_PKG_AUTHORS"], "A:B");
+  assert_eq!(flags.env["CARGO_PKG_DESCRIPTION"], "Basic");
+  assert_eq!(flags.env["CARGO_PKG_LICENSE"], "MIT");
+  assert_eq!(flags.env["CARGO_PKG_RUST_VERSION"], "1.56");
+  let output = flags.command().current_dir(&ws).output().unwrap();
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn bins_get_their_name() {
+  let ws = common::fixture("basic", "cargo_env_bin");
+  append(&ws.join("Cargo.toml"), "\n[[bin]]\nname = \"tool\"\npath = \"src/main.rs\"\n");
+  let flags = generate(ws.join("src/main.rs"), &FlagsOptions::default()).unwrap();
+  assert_eq!(flags.env["CARGO_BIN_NAME"], "tool");
+  assert_eq!(flags.env["CARGO_CRATE_NAME"], "tool");
+}
+
+#[test]
+fn tests_and_benches_get_a_scratch_directory() {
+  let ws = common::fixture("dev_deps", "cargo_env_tmpdir");
+  for file in ["tests/it.rs", "benches/speed.rs"] {
+    let flags = generate(ws.join(file), &FlagsOptions::default()).unwrap();
+    let tmp = Path::new(&flags.env["CARGO_TARGET_TMPDIR"]);
+    assert_eq!(tmp, ws.join("target/tmp"), "{}", file);
+    assert!(tmp.is_dir(), "{}", tmp.display());
+  }
+}
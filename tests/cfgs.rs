@@ -0,0 +1,53 @@
+// Feature cfgs come in the same order as in the rustc command of a real `cargo check`.
+
+mod common;
+
+use generate_rustc_flags::{generate, parse_features_str, FlagsOptions};
+use std::{env, process::Command};
+
+fn feature_cfgs(args: impl IntoIterator<Item = String>) -> Vec<String> {
+  let mut cfgs = Vec::new();
+  let mut args = args.into_iter();
+  while let Some(arg) = args.next() {
+    if arg == "--cfg" {
+      cfgs.extend(args.next().filter(|cfg| cfg.starts_with("feature=")));
+    }
+  }
+  cfgs
+}
+
+#[test]
+fn feature_cfgs_are_sorted_like_cargos() {
+  let ws = common::fixture("basic", "cfgs_sorted");
+  let options = FlagsOptions {
+    features: parse_features_str("foo bar").unwrap(),
+    ..FlagsOptions::default()
+  };
+  let flags = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap());
+  let generated = feature_cfgs(flags.to_args());
+  assert_eq!(
+    generated,
+    ["feature=\"bar\"", "feature=\"default\"", "feature=\"foo\""]
+  );
+
+  let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+  let output = Command::new(cargo)
+    .args(&["check", "-v", "--lib", "--features", "foo bar", "--target-dir"])
+    .arg(ws.join("cargo-target"))
+    .current_dir(&ws)
+    .output()
+    .unwrap();
+  let stderr = String::from_utf8_lossy(&output.stderr);
+  assert!(output.status.success(), "{}", stderr);
+  // Cargo prints the command as `Running `rustc --crate-name basic ...``, quoting the
+  // arguments for the shell.
+  let command = stderr
+    .lines()
+    .find(|line| line.contains("Running") && line.contains("--crate-name basic"))
+    .unwrap();
+  let words = command
+    .split_whitespace()
+    .map(|word| word.trim_matches(|c| c == '\'' || c == '`').replace("\\\"", "\""))
+    .collect::<Vec<_>>();
+  assert_eq!(feature_cfgs(words), generated);
+}
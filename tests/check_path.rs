@@ -0,0 +1,26 @@
+// Generating flags and running rustc with them in one call.
+
+mod common;
+
+use generate_rustc_flags::{check_path, CliFeatures};
+use std::fs;
+
+#[test]
+fn a_trivial_crate_checks() {
+  let ws = common::fixture("basic", "check_path_ok");
+  let output = common::in_dir(&ws, || {
+    check_path(ws.join("src/lib.rs"), CliFeatures::new_all(false)).unwrap()
+  });
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn type_errors_fail_the_check() {
+  let ws = common::fixture("basic", "check_path_error");
+  fs::write(ws.join("src/util.rs"), "pub fn half() -> u32 {\n  \"21\"\n}\n").unwrap();
+  let output = common::in_dir(&ws, || {
+    check_path(ws.join("src/lib.rs"), CliFeatures::new_all(false)).unwrap()
+  });
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("mismatched types"));
+}
@@ -0,0 +1,33 @@
+// The `cargo-flags` binary.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use generate_rustc_flags::FlagsBuilder;
+use std::process::Command;
+
+fn cargo_flags(args: &[&str]) -> std::process::Output {
+  Command::new(env!("CARGO_BIN_EXE_cargo-flags")).args(args).output().unwrap()
+}
+
+#[test]
+fn prints_the_flags_as_json() {
+  let ws = common::fixture("basic", "cli_json");
+  let file = ws.join("src/lib.rs");
+  let output = cargo_flags(&["flags", "--file", file.to_str().unwrap(), "--features", "bar"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+  let json = serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap();
+  let expected = FlagsBuilder::new().features(vec!["bar"]).generate(&file).unwrap();
+  assert_eq!(json["args"], serde_json::json!(expected.to_args()));
+  assert_eq!(json["env"]["CARGO_PKG_NAME"], "basic");
+}
+
+#[test]
+fn unknown_arguments_are_an_error() {
+  let output = cargo_flags(&["--frobnicate"]);
+  assert!(!output.status.success());
+  let stderr = String::from_utf8_lossy(&output.stderr);
+  assert!(stderr.contains("Unknown argument --frobnicate"), "{}", stderr);
+}
@@ -0,0 +1,86 @@
+// Helpers shared by the integration tests. Each test gets its own copy of a fixture
+// workspace from `tests/fixtures`, so that lockfiles and build outputs never land in the
+// source tree and tests running in parallel don't share a target directory.
+
+#![allow(dead_code)]
+
+use lazy_static::lazy_static;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+
+lazy_static! {
+  static ref CWD_LOCK: Mutex<()> = Mutex::new(());
+}
+
+fn copy_dir(from: &Path, to: &Path) {
+  fs::create_dir_all(to).unwrap();
+  for entry in fs::read_dir(from).unwrap() {
+    let entry = entry.unwrap();
+    let to = to.join(entry.file_name());
+    if entry.file_type().unwrap().is_dir() {
+      copy_dir(&entry.path(), &to);
+    } else {
+      fs::copy(entry.path(), to).unwrap();
+    }
+  }
+}
+
+/// A fresh copy of `tests/fixtures/<name>`, in a directory only `test` uses.
+pub fn fixture(name: &str, test: &str) -> PathBuf {
+  let from = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name);
+  let to = Path::new(env!("CARGO_TARGET_TMPDIR")).join(test).join(name);
+  if to.exists() {
+    fs::remove_dir_all(&to).unwrap();
+  }
+  copy_dir(&from, &to);
+  // Units are matched against canonical source paths.
+  to.canonicalize().unwrap()
+}
+
+/// Runs `f` with `dir` as the current directory, which is where cargo looks for its
+/// config. The tests of a binary share it, so they take turns.
+pub fn in_dir<T>(dir: &Path, f: impl FnOnce() -> T) -> T {
+  let _guard = CWD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+  env::set_current_dir(dir).unwrap();
+  f()
+}
+
+/// The values `args` passes with `option`, in order, for both `option value` and
+/// `option=value`.
+pub fn values(args: &[String], option: &str) -> Vec<String> {
+  let prefix = format!("{}=", option);
+  let mut values = Vec::new();
+  let mut iter = args.iter();
+  while let Some(arg) = iter.next() {
+    if arg == option {
+      values.extend(iter.next().cloned());
+    } else if let Some(value) = arg.strip_prefix(&prefix) {
+      values.push(value.to_string());
+    } else if option.len() == 2 && option.starts_with('-') && arg.starts_with(option) {
+      // Short options glued to their value, like `-Copt-level=3`.
+      values.push(arg[2..].to_string());
+    }
+  }
+  values
+}
+
+/// Runs a generated command line (`rustc` and its arguments) in `dir`, failing the test
+/// with rustc's diagnostics if it doesn't succeed. Returns what rustc printed to stderr.
+pub fn run(args: &[String], dir: &Path) -> String {
+  let output = Command::new(&args[0]).args(&args[1..]).current_dir(dir).output().unwrap();
+  let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+  assert!(output.status.success(), "{}", stderr);
+  stderr
+}
+
+/// The host's target triple, as rustc reports it.
+pub fn host_triple() -> String {
+  let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+  let output = Command::new(rustc).arg("-vV").output().unwrap();
+  let stdout = String::from_utf8(output.stdout).unwrap();
+  let host = stdout.lines().find_map(|line| line.strip_prefix("host: "));
+  host.unwrap().to_string()
+}
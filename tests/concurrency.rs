@@ -0,0 +1,117 @@
+// Concurrent calls that set the process environment, share the toolchain caches or run
+// on a thread pool.
+
+mod common;
+
+use generate_rustc_flags::{generate, generate_rustc_flags_with, FlagsOptions, ToolchainInfo};
+use std::{env, path::PathBuf, thread};
+
+#[test]
+fn concurrent_calls_set_a_consistent_environment() {
+  // Each source finds its own workspace, so the threads don't share a current directory.
+  let sources = [
+    common::fixture("basic", "concurrency_basic").join("src/lib.rs"),
+    common::fixture("deps", "concurrency_deps").join("src/lib.rs"),
+  ];
+  let options = FlagsOptions::default();
+  let expected = sources
+    .iter()
+    .map(|source| generate(source, &options).unwrap())
+    .collect::<Vec<_>>();
+
+  let threads = sources
+    .iter()
+    .cloned()
+    .map(|source: PathBuf| {
+      thread::spawn(move || {
+        let options = FlagsOptions::default();
+        (0..4)
+          .map(|_| generate_rustc_flags_with(&source, &options).unwrap())
+          .collect::<Vec<_>>()
+      })
+    })
+    .collect::<Vec<_>>();
+  for (thread, expected) in threads.into_iter().zip(&expected) {
+    for args in thread.join().unwrap() {
+      assert_eq!(args, expected.to_args());
+    }
+  }
+
+  // Whichever call ran last set all of its variables, without any from the other.
+  let last = expected
+    .iter()
+    .find(|flags| flags.env["CARGO_PKG_NAME"] == env::var("CARGO_PKG_NAME").unwrap())
+    .unwrap();
+  for (key, value) in &last.env {
+    assert_eq!(&env::var(key).unwrap(), value, "{}", key);
+  }
+}
+
+#[test]
+fn concurrent_toolchain_queries_agree() {
+  let dirs = [
+    common::fixture("basic", "concurrency_toolchain_basic"),
+    common::fixture("deps", "concurrency_toolchain_deps"),
+  ];
+  let threads = dirs
+    .iter()
+    .cloned()
+    .map(|dir: PathBuf| {
+      thread::spawn(move || {
+        let options = FlagsOptions::default();
+        (0..4)
+          .map(|_| {
+            let info = ToolchainInfo::query(&options, &dir).unwrap();
+            let flags = generate(dir.join("src/lib.rs"), &options).unwrap();
+            (info, common::values(&flags.to_args(), "--sysroot"))
+          })
+          .collect::<Vec<_>>()
+      })
+    })
+    .collect::<Vec<_>>();
+
+  let expected = ToolchainInfo::query(&FlagsOptions::default(), &dirs[0]).unwrap();
+  for thread in threads {
+    for (info, sysroot) in thread.join().unwrap() {
+      assert_eq!(info, expected);
+      assert_eq!(sysroot, [expected.sysroot.display().to_string()]);
+    }
+  }
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn concurrent_parallel_generation_keeps_workspaces_apart() {
+  use generate_rustc_flags::{generate_all_parallel, generate_all_rustc_flags};
+
+  let manifests = [
+    common::fixture("basic", "concurrency_parallel_basic").join("Cargo.toml"),
+    common::fixture("deps", "concurrency_parallel_deps").join("Cargo.toml"),
+  ];
+  let options = FlagsOptions::default();
+  let expected = manifests
+    .iter()
+    .map(|manifest| generate_all_rustc_flags(manifest, &options).unwrap())
+    .collect::<Vec<_>>();
+
+  let threads = manifests
+    .iter()
+    .cloned()
+    .map(|manifest: PathBuf| {
+      thread::spawn(move || {
+        let options = FlagsOptions::default();
+        (0..4)
+          .map(|_| generate_all_parallel(&manifest, &options).unwrap())
+          .collect::<Vec<_>>()
+      })
+    })
+    .collect::<Vec<_>>();
+  for (thread, expected) in threads.into_iter().zip(&expected) {
+    for all in thread.join().unwrap() {
+      assert_eq!(all.len(), expected.len());
+      for (id, flags) in expected {
+        assert_eq!(all[id].to_args(), flags.to_args(), "{:?}", id);
+      }
+    }
+  }
+}
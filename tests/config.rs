@@ -0,0 +1,55 @@
+// Configuring cargo from the options, or with a caller's own `Config`.
+
+mod common;
+
+use cargo::util::config::Config;
+use generate_rustc_flags::{generate, generate_with_config, FlagsOptions, RustcFlag, RustcFlags};
+use std::fs;
+
+fn has_cfg(flags: &RustcFlags, cfg: &str) -> bool {
+  let args = flags.to_args();
+  args.windows(2).any(|pair| pair[0] == "--cfg" && pair[1] == cfg)
+}
+
+#[test]
+fn config_overrides_apply() {
+  let ws = common::fixture("basic", "config_overrides");
+  let options = FlagsOptions {
+    config_overrides: vec!["build.rustflags=[\"--cfg\", \"from_override\"]".into()],
+    ..FlagsOptions::default()
+  };
+  let flags = generate(ws.join("src/lib.rs"), &options).unwrap();
+  assert!(has_cfg(&flags, "from_override"), "{:?}", flags.to_args());
+}
+
+#[test]
+fn the_cargo_home_config_is_read() {
+  let ws = common::fixture("basic", "config_cargo_home");
+  let cargo_home = ws.join("cargo-home");
+  fs::create_dir_all(&cargo_home).unwrap();
+  fs::write(
+    cargo_home.join("config.toml"),
+    "[build]\nrustflags = [\"--cfg\", \"from_home\"]\n",
+  )
+  .unwrap();
+  let options = FlagsOptions {
+    cargo_home: Some(cargo_home),
+    ..FlagsOptions::default()
+  };
+  let flags = generate(ws.join("src/lib.rs"), &options).unwrap();
+  assert!(has_cfg(&flags, "from_home"), "{:?}", flags.to_args());
+}
+
+#[test]
+fn a_callers_config_is_used_as_is() {
+  let ws = common::fixture("basic", "config_callers");
+  let config = Config::default().unwrap();
+  // Ignored, since only the caller's config applies.
+  let options = FlagsOptions {
+    config_overrides: vec!["build.rustflags=[\"--cfg\", \"from_override\"]".into()],
+    ..FlagsOptions::default()
+  };
+  let flags = generate_with_config(&config, ws.join("src/lib.rs"), &options).unwrap();
+  assert!(flags.flags.contains(&RustcFlag::CrateName("basic".into())));
+  assert!(!has_cfg(&flags, "from_override"));
+}
@@ -0,0 +1,15 @@
+// A `[lib] name` that differs from the package name.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions};
+
+#[test]
+fn the_crate_is_named_after_the_lib() {
+  let ws = common::fixture("renamed_lib", "renamed_lib");
+  let options = FlagsOptions::default();
+  let flags = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap());
+  assert_eq!(common::values(&flags.to_args(), "--crate-name"), ["foo_lib"]);
+  assert_eq!(flags.env["CARGO_CRATE_NAME"], "foo_lib");
+  assert_eq!(flags.env["CARGO_PKG_NAME"], "renamed-lib");
+}
@@ -0,0 +1,22 @@
+// Targets with several crate types are built by one rustc invocation.
+
+mod common;
+
+use generate_rustc_flags::{generate, CompileMode, FlagsOptions};
+use std::{fs::OpenOptions, io::Write, process::Command};
+
+#[test]
+fn every_crate_type_is_passed() {
+  let ws = common::fixture("basic", "crate_types");
+  let mut manifest = OpenOptions::new().append(true).open(ws.join("Cargo.toml")).unwrap();
+  manifest.write_all(b"\n[lib]\ncrate-type = [\"rlib\", \"staticlib\"]\n").unwrap();
+  let options = FlagsOptions {
+    mode: CompileMode::Build,
+    ..FlagsOptions::default()
+  };
+  let args = generate(ws.join("src/lib.rs"), &options).unwrap().to_args();
+  assert_eq!(common::values(&args, "--crate-type"), ["rlib", "staticlib"]);
+
+  let status = Command::new(&args[0]).args(&args[1..]).current_dir(&ws).status().unwrap();
+  assert!(status.success());
+}
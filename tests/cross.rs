@@ -0,0 +1,69 @@
+// A lib built for both the host and the target when cross-compiling. An explicit
+// `build.target` separates the two even when it names the host.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions, RustcFlag};
+use std::{fs, path::PathBuf};
+
+// A copy of the fixture that builds for an explicit target, which is the host. Tests load
+// the root manifest, whose build script needs `shared` for the host.
+fn cross_fixture(test: &str) -> (PathBuf, String) {
+  let ws = common::fixture("cross", test);
+  let host = common::host_triple();
+  fs::create_dir_all(ws.join(".cargo")).unwrap();
+  fs::write(ws.join(".cargo/config.toml"), format!("[build]\ntarget = \"{}\"\n", host)).unwrap();
+  (ws, host)
+}
+
+#[test]
+fn the_target_build_wins_over_the_host_build() {
+  let (ws, host) = cross_fixture("cross_lib_tie");
+  let options = FlagsOptions {
+    manifest_path: Some(ws.join("Cargo.toml")),
+    ..FlagsOptions::default()
+  };
+  let flags = common::in_dir(&ws, || generate(ws.join("shared/src/lib.rs"), &options).unwrap());
+  let target_deps = ws.join("target").join(&host).join("debug/deps");
+  assert_eq!(
+    common::values(&flags.to_args(), "-L"),
+    [format!("dependency={}", target_deps.display())]
+  );
+}
+
+#[test]
+fn whole_package_externs_only_target_artifacts() {
+  let (ws, host) = cross_fixture("cross_whole_package");
+  let options = FlagsOptions {
+    manifest_path: Some(ws.join("Cargo.toml")),
+    whole_package: true,
+    ..FlagsOptions::default()
+  };
+  let flags = common::in_dir(&ws, || generate(ws.join("shared/src/lib.rs"), &options).unwrap());
+  // The host build of `shared` (for the build script) externs a host `leaf`, which is left
+  // out.
+  let leaf = flags
+    .flags
+    .iter()
+    .filter_map(|flag| match flag {
+      RustcFlag::Extern { name, path, .. } if name == "leaf" => path.clone(),
+      _ => None,
+    })
+    .collect::<Vec<_>>();
+  assert_eq!(leaf.len(), 1, "{:?}", leaf);
+  assert!(leaf[0].starts_with(ws.join("target").join(&host)), "{:?}", leaf);
+}
+
+#[test]
+fn the_target_option_is_passed_on() {
+  let ws = common::fixture("cross", "cross_target_option");
+  let host = common::host_triple();
+  let options = FlagsOptions {
+    target: Some(host.clone()),
+    ..FlagsOptions::default()
+  };
+  let args = generate(ws.join("src/lib.rs"), &options).unwrap().to_args();
+  assert_eq!(common::values(&args, "--target"), [host.clone()]);
+  let target_deps = ws.join("target").join(&host).join("debug/deps");
+  assert_eq!(common::values(&args, "-L"), [format!("dependency={}", target_deps.display())]);
+}
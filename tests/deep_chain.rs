@@ -0,0 +1,71 @@
+// A long chain of path dependencies, whose unit graph is walked without recursion.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions, RustcFlag};
+use std::{fs, path::Path};
+
+const DEPTH: usize = 300;
+
+// `c0` depends on `c1`, which depends on `c2`, and so on down to `c<DEPTH - 1>`.
+fn write_chain(ws: &Path) {
+  if ws.exists() {
+    fs::remove_dir_all(ws).unwrap();
+  }
+  for i in 0..DEPTH {
+    let dir = ws.join(format!("c{}", i));
+    fs::create_dir_all(dir.join("src")).unwrap();
+    let mut manifest = format!(
+      "[package]\nname = \"c{}\"\nversion = \"0.1.0\"\nedition = \"2018\"\n",
+      i
+    );
+    if i + 1 < DEPTH {
+      manifest.push_str(&format!("\n[dependencies]\nc{0} = {{ path = \"../c{0}\" }}\n", i + 1));
+    }
+    fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+    fs::write(dir.join("src/lib.rs"), "").unwrap();
+  }
+  fs::write(ws.join("Cargo.toml"), "[workspace]\nmembers = [\"c0\"]\n").unwrap();
+}
+
+fn crate_name(flags: &generate_rustc_flags::RustcFlags) -> String {
+  flags
+    .flags
+    .iter()
+    .find_map(|flag| match flag {
+      RustcFlag::CrateName(name) => Some(name.clone()),
+      _ => None,
+    })
+    .unwrap()
+}
+
+#[test]
+fn deep_dependency_chains_dont_overflow() {
+  let ws = Path::new(env!("CARGO_TARGET_TMPDIR")).join("deep_chain");
+  write_chain(&ws);
+  let ws = ws.canonicalize().unwrap();
+  let options = FlagsOptions {
+    manifest_path: Some(ws.join("Cargo.toml")),
+    // Only the unit graph is under test, not building 300 crates.
+    build_missing_deps: false,
+    ..FlagsOptions::default()
+  };
+
+  let externs = |flags: &generate_rustc_flags::RustcFlags| {
+    flags
+      .flags
+      .iter()
+      .filter_map(|flag| match flag {
+        RustcFlag::Extern { name, .. } => Some(name.clone()),
+        _ => None,
+      })
+      .collect::<Vec<_>>()
+  };
+  let top = generate(ws.join("c0/src/lib.rs"), &options).unwrap();
+  let bottom = generate(ws.join(format!("c{}/src/lib.rs", DEPTH - 1)), &options).unwrap();
+  assert_eq!(externs(&top), ["c1"]);
+
+  // The bottom of the chain is only reachable through every unit above it.
+  assert_eq!(crate_name(&bottom), format!("c{}", DEPTH - 1));
+  assert!(externs(&bottom).is_empty());
+}
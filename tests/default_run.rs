@@ -0,0 +1,27 @@
+// Pointing at a package's manifest instead of a source file.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions};
+use std::fs;
+
+#[test]
+fn the_default_run_bin_is_picked() {
+  let ws = common::fixture("default_run", "default_run");
+  let options = FlagsOptions::default();
+  let flags = common::in_dir(&ws, || generate(ws.join("Cargo.toml"), &options).unwrap());
+  let args = flags.to_args();
+  assert_eq!(common::values(&args, "--crate-name"), ["second"]);
+  assert_eq!(common::values(&args, "--crate-type"), ["bin"]);
+}
+
+#[test]
+fn a_missing_default_run_bin_is_an_error() {
+  let ws = common::fixture("default_run", "default_run_missing");
+  let manifest = fs::read_to_string(ws.join("Cargo.toml")).unwrap();
+  fs::write(ws.join("Cargo.toml"), manifest.replace("\"second\"", "\"third\"")).unwrap();
+  let options = FlagsOptions::default();
+  let err = common::in_dir(&ws, || generate(ws.join("Cargo.toml"), &options).unwrap_err());
+  let message = format!("{:#}", err);
+  assert!(message.contains("default-run") && message.contains("third"), "{}", message);
+}
@@ -0,0 +1,26 @@
+// Files outside every target's directory, found through rustc's dep-info.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions, RustcFlag};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+
+#[test]
+fn path_modules_are_found_once_built() {
+  let ws = common::fixture("basic", "dep_info_path_module");
+  fs::create_dir_all(ws.join("outside")).unwrap();
+  fs::write(ws.join("outside/extra.rs"), "pub fn extra() {}\n").unwrap();
+  let mut lib = OpenOptions::new().append(true).open(ws.join("src/lib.rs")).unwrap();
+  lib.write_all(b"\n#[path = \"../outside/extra.rs\"]\npub mod extra;\n").unwrap();
+
+  let options = FlagsOptions::default();
+  let extra = ws.join("outside/extra.rs");
+  assert!(generate(&extra, &options).is_err());
+
+  // A check of the lib writes the dep-info listing the module.
+  let args = generate(ws.join("src/lib.rs"), &options).unwrap().to_args();
+  common::run(&args, &ws);
+  let flags = generate(&extra, &options).unwrap();
+  assert!(flags.flags.contains(&RustcFlag::CrateName("basic".into())));
+}
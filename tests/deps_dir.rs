@@ -0,0 +1,18 @@
+// Looking up the deps directory without generating the flags.
+
+mod common;
+
+use generate_rustc_flags::{deps_dir_for, generate, CliFeatures, FlagsOptions};
+
+#[test]
+fn matches_the_search_path_in_the_flags() {
+  let ws = common::fixture("basic", "deps_dir");
+  let (deps_dir, flags) = common::in_dir(&ws, || {
+    let deps_dir = deps_dir_for(ws.join("src/lib.rs"), CliFeatures::new_all(false)).unwrap();
+    (deps_dir, generate(ws.join("src/lib.rs"), &FlagsOptions::default()).unwrap())
+  });
+  assert_eq!(
+    common::values(&flags.to_args(), "-L"),
+    [format!("dependency={}", deps_dir.display())]
+  );
+}
@@ -0,0 +1,72 @@
+// Targets that can use dev-dependencies get their externs.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions, RustcFlag, RustcFlags};
+
+fn generate_in(test: &str, file: &str) -> RustcFlags {
+  let ws = common::fixture("dev_deps", test);
+  common::in_dir(&ws, || generate(ws.join(file), &FlagsOptions::default()).unwrap())
+}
+
+fn extern_names(flags: &RustcFlags) -> Vec<String> {
+  let mut names = flags
+    .flags
+    .iter()
+    .filter_map(|flag| match flag {
+      RustcFlag::Extern { name, .. } => Some(name.clone()),
+      _ => None,
+    })
+    .collect::<Vec<_>>();
+  names.sort();
+  names
+}
+
+#[test]
+fn examples_extern_dev_dependencies() {
+  let flags = generate_in("dev_deps_example", "examples/demo.rs");
+  assert!(flags.flags.contains(&RustcFlag::CrateName("demo".into())));
+  assert_eq!(extern_names(&flags), ["dev_deps", "devdep"]);
+}
+
+#[test]
+fn examples_in_one_directory_get_their_own_targets() {
+  let flags = generate_in("dev_deps_other_example", "examples/other.rs");
+  assert!(flags.flags.contains(&RustcFlag::CrateName("other".into())));
+  assert!(!flags.flags.contains(&RustcFlag::CrateName("demo".into())));
+}
+
+#[test]
+fn integration_tests_extern_dev_dependencies() {
+  let flags = generate_in("dev_deps_test", "tests/it.rs");
+  assert!(flags.flags.contains(&RustcFlag::CrateName("it".into())));
+  assert!(flags.flags.contains(&RustcFlag::Raw("--test".into())));
+  assert_eq!(extern_names(&flags), ["dev_deps", "devdep"]);
+}
+
+#[test]
+fn benches_extern_dev_dependencies() {
+  let flags = generate_in("dev_deps_bench", "benches/speed.rs");
+  assert!(flags.flags.contains(&RustcFlag::CrateName("speed".into())));
+  assert_eq!(extern_names(&flags), ["dev_deps", "devdep"]);
+}
+
+#[test]
+fn the_lib_does_not() {
+  let flags = generate_in("dev_deps_lib", "src/lib.rs");
+  assert_eq!(extern_names(&flags), Vec::<String>::new());
+}
+
+#[test]
+fn the_test_option_externs_dev_dependencies_for_the_lib() {
+  let ws = common::fixture("dev_deps", "dev_deps_test_option");
+  let options = FlagsOptions {
+    test: true,
+    // The integration tests would add the lib's non-test unit.
+    lib_only: true,
+    ..FlagsOptions::default()
+  };
+  let flags = generate(ws.join("src/lib.rs"), &options).unwrap();
+  assert_eq!(extern_names(&flags), ["devdep"]);
+  assert!(flags.flags.contains(&RustcFlag::Raw("--test".into())));
+}
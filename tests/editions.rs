@@ -0,0 +1,78 @@
+// Editions the detected rustc hasn't stabilized, using a 2021-edition crate and older
+// `rustc_version`s in place of a real old toolchain.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions, RustcFlags};
+use semver::Version;
+
+fn flags(test: &str, options: &FlagsOptions) -> anyhow::Result<RustcFlags> {
+  let ws = common::fixture("edition2021", test);
+  common::in_dir(&ws, || generate(ws.join("src/lib.rs"), options))
+}
+
+fn old_rustc(version: &str) -> FlagsOptions {
+  FlagsOptions {
+    rustc_version: Some(Version::parse(version).unwrap()),
+    ..FlagsOptions::default()
+  }
+}
+
+#[test]
+fn stable_toolchains_error_with_guidance() {
+  let err = flags("edition_unstable", &old_rustc("1.50.0")).unwrap_err();
+  assert!(err.to_string().contains("Edition 2021 is not stable on rustc 1.50.0"), "{}", err);
+  assert!(err.to_string().contains("bootstrap_unstable_editions"), "{}", err);
+}
+
+#[test]
+fn bootstrapping_is_opt_in() {
+  let options = FlagsOptions {
+    bootstrap_unstable_editions: true,
+    ..old_rustc("1.50.0")
+  };
+  let flags = flags("edition_bootstrap", &options).unwrap();
+  assert_eq!(flags.env.get("RUSTC_BOOTSTRAP").map(String::as_str), Some("1"));
+  assert_eq!(common::values(&flags.to_args(), "-Z"), ["unstable-options"]);
+}
+
+#[test]
+fn nightlies_only_need_unstable_options() {
+  let flags = flags("edition_nightly", &old_rustc("1.50.0-nightly")).unwrap();
+  assert_eq!(flags.env.get("RUSTC_BOOTSTRAP"), None);
+  assert_eq!(common::values(&flags.to_args(), "-Z"), ["unstable-options"]);
+}
+
+#[test]
+fn stable_editions_are_left_alone() {
+  let flags = flags("edition_stable", &old_rustc("1.56.0")).unwrap();
+  assert_eq!(common::values(&flags.to_args(), "-Z"), Vec::<String>::new());
+}
+
+fn migrating(rustc_version: &str) -> FlagsOptions {
+  FlagsOptions {
+    edition_migration: true,
+    ..old_rustc(rustc_version)
+  }
+}
+
+fn force_warns(flags: &RustcFlags) -> Vec<String> {
+  common::values(&flags.to_args(), "--force-warn")
+}
+
+#[test]
+fn migration_lints_cover_the_next_edition() {
+  let stable = flags("edition_migration", &migrating("1.85.0")).unwrap();
+  assert_eq!(force_warns(&stable), ["rust-2024-compatibility"]);
+  // The crate stays on its current edition.
+  assert_eq!(common::values(&stable.to_args(), "--edition"), ["2021"]);
+
+  let nightly = flags("edition_migration_nightly", &migrating("1.80.0-nightly")).unwrap();
+  assert_eq!(force_warns(&nightly), ["rust-2024-compatibility"]);
+}
+
+#[test]
+fn migrating_to_an_unstable_edition_needs_a_nightly() {
+  let err = flags("edition_migration_stable", &migrating("1.80.0")).unwrap_err();
+  assert!(err.to_string().contains("needs a nightly rustc"), "{}", err);
+}
@@ -0,0 +1,54 @@
+// Choosing what rustc emits, and passing arguments of the caller's own.
+
+mod common;
+
+use generate_rustc_flags::{generate, Emit, FallbackMode, FlagsOptions};
+
+#[test]
+fn the_chosen_outputs_replace_the_modes() {
+  let ws = common::fixture("basic", "emit_chosen");
+  let options = FlagsOptions {
+    emit: Some(vec![Emit::Metadata, Emit::Link]),
+    ..FlagsOptions::default()
+  };
+  let args = generate(ws.join("src/lib.rs"), &options).unwrap().to_args();
+  assert_eq!(common::values(&args, "--emit"), ["metadata,link"]);
+  common::run(&args, &ws);
+}
+
+#[test]
+fn metadata_out_still_applies() {
+  let ws = common::fixture("basic", "emit_metadata_out");
+  let out = ws.join("libbasic.rmeta");
+  let options = FlagsOptions {
+    emit: Some(vec![Emit::Metadata]),
+    metadata_out: Some(out.clone()),
+    ..FlagsOptions::default()
+  };
+  let args = generate(ws.join("src/lib.rs"), &options).unwrap().to_args();
+  assert_eq!(common::values(&args, "--emit"), [format!("metadata={}", out.display())]);
+}
+
+#[test]
+fn extra_args_come_last() {
+  let ws = common::fixture("basic", "emit_extra_args");
+  let options = FlagsOptions {
+    extra_args: vec!["--cfg".into(), "from_caller".into()],
+    ..FlagsOptions::default()
+  };
+  let args = generate(ws.join("src/lib.rs"), &options).unwrap().to_args();
+  assert_eq!(args[args.len() - 2..], ["--cfg", "from_caller"]);
+  common::run(&args, &ws);
+}
+
+#[test]
+fn extra_args_apply_without_a_package() {
+  let ws = common::fixture("basic", "emit_extra_args_fallback");
+  let options = FlagsOptions {
+    fallback: FallbackMode::Always,
+    extra_args: vec!["--cfg".into(), "from_caller".into()],
+    ..FlagsOptions::default()
+  };
+  let args = generate(ws.join("src/lib.rs"), &options).unwrap().to_args();
+  assert_eq!(args[args.len() - 2..], ["--cfg", "from_caller"]);
+}
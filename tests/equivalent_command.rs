@@ -0,0 +1,18 @@
+// The cargo invocation a generation emulates.
+
+mod common;
+
+use generate_rustc_flags::{equivalent_cargo_command, parse_features_str, CliFeatures};
+
+#[test]
+fn names_the_package_target_and_features() {
+  let ws = common::fixture("basic", "equivalent_command");
+  let (lib, bin) = common::in_dir(&ws, || {
+    let features = CliFeatures::from_command_line(&["bar".to_string()], false, false).unwrap();
+    let lib = equivalent_cargo_command(ws.join("src/lib.rs"), features).unwrap();
+    let features = parse_features_str("foo bar").unwrap();
+    (lib, equivalent_cargo_command(ws.join("src/main.rs"), features).unwrap())
+  });
+  assert_eq!(lib, "cargo check -p basic --lib --no-default-features --features bar -v");
+  assert_eq!(bin, "cargo check -p basic --bin basic --features bar,foo -v");
+}
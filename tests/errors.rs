@@ -0,0 +1,72 @@
+// Errors callers can match on to recover, e.g. by picking a target.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions, GenerateFlagsError, TargetSelector};
+
+fn error(file: std::path::PathBuf, options: &FlagsOptions) -> GenerateFlagsError {
+  let err = generate(file, options).unwrap_err();
+  err
+    .chain()
+    .find_map(|cause| cause.downcast_ref::<GenerateFlagsError>())
+    .cloned()
+    .unwrap_or_else(|| panic!("{:#}", err))
+}
+
+#[test]
+fn ambiguous_files_name_the_candidates() {
+  let ws = common::fixture("multi_bin", "errors_ambiguous");
+  let file = ws.join("src/bin/common/mod.rs");
+  match error(file.clone(), &FlagsOptions::default()) {
+    GenerateFlagsError::AmbiguousUnit { path, mut candidates } => {
+      assert_eq!(path, file);
+      candidates.sort();
+      assert_eq!(candidates, ["bin `a`", "bin `b`"]);
+    }
+    other => panic!("{:?}", other),
+  }
+}
+
+#[test]
+fn files_outside_the_selected_target_say_so() {
+  let ws = common::fixture("multi_bin", "errors_selected");
+  let options = FlagsOptions {
+    target_selector: Some(TargetSelector::Bin("b".into())),
+    ..FlagsOptions::default()
+  };
+  let err = error(ws.join("src/bin/a.rs"), &options);
+  assert!(matches!(err, GenerateFlagsError::NotInSelectedTarget { .. }), "{:?}", err);
+}
+
+#[test]
+fn undeclared_targets_carry_the_hint() {
+  let ws = common::fixture("autobins", "errors_not_found");
+  let file = ws.join("src/bin/foo.rs");
+  match error(file.clone(), &FlagsOptions::default()) {
+    GenerateFlagsError::UnitNotFound {
+      path,
+      package: None,
+      hint: Some(hint),
+    } => {
+      assert_eq!(path, file);
+      assert!(hint.contains("autobins"), "{}", hint);
+    }
+    other => panic!("{:?}", other),
+  }
+}
+
+#[test]
+fn broken_workspaces_fail_to_load() {
+  let ws = common::fixture("broken_workspace", "errors_workspace");
+  let options = FlagsOptions {
+    tolerant_manifest: true,
+    ..FlagsOptions::default()
+  };
+  let err = error(ws.join("src/lib.rs"), &options);
+  assert_eq!(
+    err,
+    GenerateFlagsError::WorkspaceLoad {
+      manifest_path: ws.join("Cargo.toml"),
+    }
+  );
+}
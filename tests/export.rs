@@ -0,0 +1,65 @@
+// Exporting a workspace as a `rust-project.json`.
+
+mod common;
+
+use generate_rustc_flags::{export, FlagsOptions};
+
+#[test]
+fn crates_come_after_their_dependencies() {
+  let ws = common::fixture("deps", "export_deps");
+  let project = export::rust_project(ws.join("Cargo.toml"), &FlagsOptions::default()).unwrap();
+  for (i, krate) in project.crates.iter().enumerate() {
+    assert!(krate.deps.iter().all(|dep| dep.krate < i), "{:?}", krate);
+  }
+
+  let app = project
+    .crates
+    .iter()
+    .find(|krate| krate.root_module == ws.join("src/main.rs"))
+    .unwrap();
+  assert!(app.is_workspace_member);
+  let mut deps = app.deps.iter().map(|dep| dep.name.as_str()).collect::<Vec<_>>();
+  deps.sort();
+  assert_eq!(deps, ["app", "unused", "used"]);
+  let used = app.deps.iter().find(|dep| dep.name == "used").unwrap();
+  assert!(!project.crates[used.krate].is_workspace_member);
+  assert_eq!(project.crates[used.krate].edition, "2018");
+}
+
+#[test]
+fn features_and_env_are_included() {
+  let ws = common::fixture("basic", "export_basic");
+  let project = export::rust_project(ws.join("Cargo.toml"), &FlagsOptions::default()).unwrap();
+  let lib = project
+    .crates
+    .iter()
+    .find(|krate| krate.root_module == ws.join("src/lib.rs"))
+    .unwrap();
+  assert!(lib.cfg.contains(&"feature=\"foo\"".to_string()), "{:?}", lib.cfg);
+  assert_eq!(lib.env["CARGO_PKG_NAME"], "basic");
+  assert_eq!(lib.target, None);
+  assert!(!lib.is_proc_macro);
+}
+
+#[test]
+fn proc_macros_are_marked() {
+  let ws = common::fixture("build_deps", "export_proc_macro");
+  let project = export::rust_project(ws.join("Cargo.toml"), &FlagsOptions::default()).unwrap();
+  let mac = project.crates.iter().find(|krate| krate.display_name == "mac").unwrap();
+  assert!(mac.is_proc_macro);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn the_json_names_deps_by_crate() {
+  let ws = common::fixture("deps", "export_json");
+  let project = export::rust_project(ws.join("Cargo.toml"), &FlagsOptions::default()).unwrap();
+  let json = serde_json::to_value(&project).unwrap();
+  let app = json["crates"]
+    .as_array()
+    .unwrap()
+    .iter()
+    .find(|krate| krate["display_name"] == "app" && !krate["deps"].as_array().unwrap().is_empty())
+    .unwrap();
+  assert!(app["deps"][0]["crate"].is_u64(), "{}", app);
+}
@@ -0,0 +1,30 @@
+// The structured description of each `--extern`.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions};
+
+#[test]
+fn renamed_dependencies_keep_their_crate_name() {
+  let ws = common::fixture("versions", "extern_deps_renamed");
+  let flags = generate(ws.join("src/lib.rs"), &FlagsOptions::default()).unwrap();
+  let mut externs = flags.externs.clone();
+  externs.sort_by(|a, b| a.rename.cmp(&b.rename));
+  let described = externs
+    .iter()
+    .map(|dep| (dep.name.as_str(), dep.rename.as_deref().unwrap()))
+    .collect::<Vec<_>>();
+  assert_eq!(described, [("shared", "shared1"), ("shared", "shared2")]);
+  assert!(externs[0].pkg_id.starts_with("shared 1.0.0 (path+file://"), "{}", externs[0].pkg_id);
+  assert!(externs[1].pkg_id.starts_with("shared 2.0.0 (path+file://"), "{}", externs[1].pkg_id);
+  assert!(externs.iter().all(|dep| !dep.is_proc_macro));
+}
+
+#[test]
+fn proc_macros_are_marked() {
+  let ws = common::fixture("build_deps", "extern_deps_proc_macro");
+  let flags = generate(ws.join("build.rs"), &FlagsOptions::default()).unwrap();
+  let mac = flags.externs.iter().find(|dep| dep.name == "mac").unwrap();
+  assert!(mac.is_proc_macro);
+  assert_eq!(mac.rename, None);
+}
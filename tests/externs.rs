@@ -0,0 +1,233 @@
+// Which dependencies the `--extern`s name, and how.
+
+mod common;
+
+use generate_rustc_flags::{
+  generate, parse_features_str, resolved_features, CliFeatures, FlagsOptions, RustcFlag, RustcFlags,
+};
+use std::path::Path;
+
+fn generate_in(ws: &Path, file: &str, options: &FlagsOptions) -> RustcFlags {
+  common::in_dir(ws, || generate(ws.join(file), options).unwrap())
+}
+
+fn externs(flags: &RustcFlags) -> Vec<(String, Vec<String>)> {
+  let mut externs = flags
+    .flags
+    .iter()
+    .filter_map(|flag| match flag {
+      RustcFlag::Extern {
+        name, modifiers, ..
+      } => Some((name.clone(), modifiers.clone())),
+      _ => None,
+    })
+    .collect::<Vec<_>>();
+  externs.sort();
+  externs
+}
+
+#[test]
+fn unused_dependencies_are_linted_like_cargo() {
+  let ws = common::fixture("deps", "externs_unused");
+  let flags = generate_in(&ws, "src/lib.rs", &FlagsOptions::default());
+  // The linked cargo puts no modifiers on regular dependencies, so the lint sees both, and
+  // no `-Z unstable-options` is needed.
+  let no_modifiers = Vec::<String>::new();
+  assert_eq!(
+    externs(&flags),
+    [("unused".to_string(), no_modifiers.clone()), ("used".to_string(), no_modifiers)]
+  );
+  let args = flags.to_args();
+  assert!(common::values(&args, "--warn").contains(&"unused_crate_dependencies".to_string()));
+  assert!(!common::values(&args, "-Z").contains(&"unstable-options".to_string()));
+
+  // The dependencies were built for the externs, so rustc gets as far as the lint.
+  let stderr = common::run(&args, &ws);
+  assert!(stderr.contains("`unused`"), "{}", stderr);
+  assert!(!stderr.contains("`used`"), "{}", stderr);
+}
+
+#[test]
+fn missing_dependencies_are_built() {
+  let ws = common::fixture("deps", "externs_built");
+  let flags = generate_in(&ws, "src/lib.rs", &FlagsOptions::default());
+  let paths = flags
+    .flags
+    .iter()
+    .filter_map(|flag| match flag {
+      RustcFlag::Extern { path, .. } => path.clone(),
+      _ => None,
+    })
+    .collect::<Vec<_>>();
+  assert_eq!(paths.len(), 2);
+  assert!(paths.iter().all(|path| path.is_file()), "{:?}", paths);
+}
+
+fn extern_names(source: &str, whole_package: bool) -> Vec<String> {
+  let name = format!("externs_whole_{}_{}", source.replace('/', "_"), whole_package);
+  let ws = common::fixture("deps", &name);
+  let options = FlagsOptions {
+    whole_package,
+    ..FlagsOptions::default()
+  };
+  let flags = generate_in(&ws, source, &options);
+  externs(&flags).into_iter().map(|(name, _)| name).collect()
+}
+
+#[test]
+fn whole_package_unions_the_targets_externs() {
+  // Only the bin depends on the package's own lib.
+  assert_eq!(extern_names("src/lib.rs", false), ["unused", "used"]);
+  assert_eq!(extern_names("src/lib.rs", true), ["app", "unused", "used"]);
+  assert_eq!(extern_names("src/main.rs", false), ["app", "unused", "used"]);
+  assert_eq!(extern_names("src/main.rs", true), ["app", "unused", "used"]);
+}
+
+#[test]
+fn absolute_externs_only_drops_the_deps_directory() {
+  let ws = common::fixture("deps", "externs_absolute_only");
+  let options = FlagsOptions {
+    absolute_externs_only: true,
+    ..FlagsOptions::default()
+  };
+  let flags = generate_in(&ws, "src/lib.rs", &options);
+  assert!(!flags.flags.iter().any(|flag| matches!(flag, RustcFlag::LibSearch { .. })));
+  let paths = flags
+    .flags
+    .iter()
+    .filter_map(|flag| match flag {
+      RustcFlag::Extern { path, .. } => path.clone(),
+      _ => None,
+    })
+    .collect::<Vec<_>>();
+  assert_eq!(paths.len(), 2);
+  assert!(paths.iter().all(|path| path.is_absolute()), "{:?}", paths);
+
+  let default = generate_in(&ws, "src/lib.rs", &FlagsOptions::default());
+  assert!(default.flags.iter().any(|flag| matches!(flag, RustcFlag::LibSearch { .. })));
+}
+
+#[test]
+fn platform_dependencies_follow_the_host() {
+  let ws = common::fixture("platform", "externs_platform");
+  let flags = generate_in(&ws, "src/lib.rs", &FlagsOptions::default());
+  let names = externs(&flags).into_iter().map(|(name, _)| name).collect::<Vec<_>>();
+  let expected: &[&str] = if cfg!(unix) {
+    &["unixdep"]
+  } else if cfg!(windows) {
+    &["windep"]
+  } else {
+    &[]
+  };
+  assert_eq!(names, expected);
+}
+
+fn optional_externs(features: &str, test: &str) -> Vec<String> {
+  let ws = common::fixture("optional", test);
+  let options = FlagsOptions {
+    features: parse_features_str(features).unwrap(),
+    ..FlagsOptions::default()
+  };
+  let flags = generate_in(&ws, "src/lib.rs", &options);
+  externs(&flags).into_iter().map(|(name, _)| name).collect()
+}
+
+#[test]
+fn optional_dependencies_are_externed_when_activated() {
+  let none = Vec::<String>::new();
+  assert_eq!(optional_externs("", "externs_optional_off"), none);
+  // A weak feature alone doesn't activate the dependency.
+  assert_eq!(optional_externs("weak", "externs_optional_weak"), none);
+  assert_eq!(optional_externs("weak opt", "externs_optional_weak_opt"), ["opt"]);
+  assert_eq!(optional_externs("strong", "externs_optional_strong"), ["opt"]);
+}
+
+#[test]
+fn renamed_optional_dependencies_are_externed_under_their_alias() {
+  assert_eq!(optional_externs("", "externs_renamed_off"), Vec::<String>::new());
+
+  let ws = common::fixture("optional", "externs_renamed_on");
+  let options = FlagsOptions {
+    features: parse_features_str("renamed").unwrap(),
+    ..FlagsOptions::default()
+  };
+  let flags = generate_in(&ws, "src/lib.rs", &options);
+  let paths = flags
+    .flags
+    .iter()
+    .filter_map(|flag| match flag {
+      RustcFlag::Extern { name, path, .. } => Some((name.as_str(), path.as_ref()?)),
+      _ => None,
+    })
+    .collect::<Vec<_>>();
+  assert_eq!(paths.len(), 1);
+  let (name, path) = paths[0];
+  assert_eq!(name, "alias");
+  let file_name = path.file_name().unwrap().to_str().unwrap();
+  assert!(file_name.starts_with("librenamed-"), "{}", file_name);
+}
+
+#[test]
+fn unbuilt_dependencies_can_be_reported() {
+  let ws = common::fixture("deps", "externs_unbuilt");
+  let options = FlagsOptions {
+    check_externs_built: true,
+    build_missing_deps: false,
+    ..FlagsOptions::default()
+  };
+  // Nothing has been built in the fresh fixture, as after deleting the artifacts.
+  let err = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap_err());
+  let message = err.to_string();
+  assert!(message.starts_with("Dependency `unused` has not been built"), "{}", message);
+  assert!(message.contains(&ws.join("target/debug/deps").display().to_string()), "{}", message);
+}
+
+#[test]
+fn bins_extern_their_packages_lib() {
+  let ws = common::fixture("deps", "externs_sibling_lib");
+  let bin = generate_in(&ws, "src/main.rs", &FlagsOptions::default());
+  let lib_path = bin
+    .flags
+    .iter()
+    .find_map(|flag| match flag {
+      RustcFlag::Extern {
+        name,
+        path: Some(path),
+        ..
+      } if name == "app" => Some(path.clone()),
+      _ => None,
+    })
+    .unwrap();
+  // The lib's metadata, next to the other dependencies'.
+  let file_name = lib_path.file_name().unwrap().to_str().unwrap();
+  assert!(file_name.starts_with("libapp-") && file_name.ends_with(".rmeta"), "{}", file_name);
+  assert_eq!(lib_path.parent().unwrap(), ws.join("target/debug/deps"));
+}
+
+#[test]
+fn edition_2015_extern_crates_are_externed() {
+  let ws = common::fixture("edition2015", "externs_edition2015");
+  let flags = generate_in(&ws, "src/lib.rs", &FlagsOptions::default());
+  assert_eq!(common::values(&flags.to_args(), "--edition"), ["2015"]);
+  assert_eq!(externs(&flags), [("old_dep".to_string(), Vec::<String>::new())]);
+}
+
+#[test]
+fn path_dependency_externs_are_built_with_their_features() {
+  let ws = common::fixture("dep_features", "externs_dep_features");
+  let bin = generate_in(&ws, "src/main.rs", &FlagsOptions::default());
+  let dep_extern = common::values(&bin.to_args(), "--extern")
+    .into_iter()
+    .find(|value| value.starts_with("dep="))
+    .unwrap();
+  assert!(dep_extern.contains("libdep-") && dep_extern.ends_with(".rmeta"), "{}", dep_extern);
+
+  // That artifact is the dependency's unit in the bin's graph, where it has the feature
+  // the bin enables.
+  let manifest_path = ws.join("Cargo.toml");
+  let features = common::in_dir(&ws, || {
+    resolved_features(manifest_path, CliFeatures::new_all(false)).unwrap()
+  });
+  let (_, dep_features) = features.iter().find(|(id, _)| id.name() == "dep").unwrap();
+  assert_eq!(dep_features, &["x"]);
+}
@@ -0,0 +1,43 @@
+// Flags for one file under several feature combinations.
+
+mod common;
+
+use generate_rustc_flags::{generate, generate_for_feature_matrix, parse_features_str, FlagsOptions};
+
+fn feature_cfgs(args: &[String]) -> Vec<String> {
+  common::values(args, "--cfg")
+    .into_iter()
+    .filter(|cfg| cfg.starts_with("feature="))
+    .collect()
+}
+
+#[test]
+fn each_combination_is_resolved() {
+  let ws = common::fixture("basic", "feature_matrix");
+  let default = parse_features_str("").unwrap();
+  let bar = parse_features_str("bar").unwrap();
+  let mut bare = parse_features_str("bar").unwrap();
+  bare.uses_default_features = false;
+  let combos = [default.clone(), bar.clone(), bare.clone()];
+
+  let options = FlagsOptions::default();
+  let all = generate_for_feature_matrix(ws.join("src/lib.rs"), &combos, &options).unwrap();
+  assert_eq!(all.len(), 3);
+  assert_eq!(
+    feature_cfgs(&all[&default].to_args()),
+    ["feature=\"default\"", "feature=\"foo\""]
+  );
+  assert_eq!(
+    feature_cfgs(&all[&bar].to_args()),
+    ["feature=\"bar\"", "feature=\"default\"", "feature=\"foo\""]
+  );
+  assert_eq!(feature_cfgs(&all[&bare].to_args()), ["feature=\"bar\""]);
+
+  // The same as generating with each combination on its own.
+  let single = FlagsOptions {
+    features: bare.clone(),
+    ..FlagsOptions::default()
+  };
+  let single = generate(ws.join("src/lib.rs"), &single).unwrap();
+  assert_eq!(all[&bare].to_args(), single.to_args());
+}
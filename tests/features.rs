@@ -0,0 +1,37 @@
+// Feature lists in the forms users pass to cargo's `--features`.
+
+use generate_rustc_flags::parse_features_str;
+
+fn parsed(features: &str) -> Vec<String> {
+  let features = parse_features_str(features).unwrap();
+  assert!(features.uses_default_features);
+  assert!(!features.all_features);
+  features.features.iter().map(|feature| feature.to_string()).collect()
+}
+
+#[test]
+fn splits_on_commas() {
+  assert_eq!(parsed("foo,bar"), ["bar", "foo"]);
+}
+
+#[test]
+fn splits_on_whitespace() {
+  assert_eq!(parsed("foo bar\tbaz"), ["bar", "baz", "foo"]);
+}
+
+#[test]
+fn splits_on_mixed_separators() {
+  assert_eq!(parsed(" foo, bar ,,baz "), ["bar", "baz", "foo"]);
+}
+
+#[test]
+fn keeps_dependency_features() {
+  assert_eq!(parsed("serde/derive, foo"), ["foo", "serde/derive"]);
+}
+
+#[test]
+fn matches_cargos_validation() {
+  assert!(parse_features_str("").unwrap().features.is_empty());
+  assert!(parse_features_str("dep:serde").is_err());
+  assert!(parse_features_str("a/b/c").is_err());
+}
@@ -0,0 +1,51 @@
+// Unsaved editor buffers, given as file overrides.
+
+mod common;
+
+use generate_rustc_flags::{generate, FallbackMode, FlagsOptions, RustcFlag};
+use std::collections::HashMap;
+
+fn overriding(files: &[(std::path::PathBuf, &str)]) -> FlagsOptions {
+  FlagsOptions {
+    file_overrides: files
+      .iter()
+      .map(|(path, contents)| (path.clone(), contents.to_string()))
+      .collect::<HashMap<_, _>>(),
+    ..FlagsOptions::default()
+  }
+}
+
+#[test]
+fn unsaved_files_match_their_target() {
+  let ws = common::fixture("basic", "file_overrides_unsaved");
+  let new = ws.join("src/new.rs");
+  let options = overriding(&[(new.clone(), "pub fn new() {}\n")]);
+  let flags = generate(&new, &options).unwrap();
+  assert!(flags.flags.contains(&RustcFlag::CrateName("basic".into())));
+  assert_eq!(flags.crate_root, ws.join("src/lib.rs"));
+  assert_eq!(flags.file_overrides[&new], "pub fn new() {}\n");
+}
+
+#[test]
+fn only_the_crates_own_files_are_returned() {
+  let ws = common::fixture("basic", "file_overrides_scoped");
+  let outside = ws.join("elsewhere.rs");
+  let options = overriding(&[
+    (ws.join("src/util.rs"), "pub fn changed() {}\n"),
+    (outside.clone(), "fn main() {}\n"),
+  ]);
+  let flags = generate(ws.join("src/lib.rs"), &options).unwrap();
+  assert!(flags.file_overrides.contains_key(&ws.join("src/util.rs")));
+  assert!(!flags.file_overrides.contains_key(&outside));
+}
+
+#[test]
+fn fallback_flags_read_the_override() {
+  let ws = common::fixture("basic", "file_overrides_fallback");
+  let file = ws.join("src/util.rs");
+  let mut options = overriding(&[(file.clone(), "fn main() {}\n")]);
+  options.fallback = FallbackMode::Always;
+  // On disk the file has no `main`, so it would be a lib.
+  let args = generate(&file, &options).unwrap().to_args();
+  assert_eq!(common::values(&args, "--crate-type"), ["bin"]);
+}
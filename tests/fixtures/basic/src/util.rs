@@ -0,0 +1,3 @@
+pub fn half() -> u32 {
+  21
+}
@@ -0,0 +1 @@
+pub const TOOL: &str = env!("CARGO_BIN_FILE_TOOL");
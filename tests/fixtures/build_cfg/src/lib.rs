@@ -0,0 +1 @@
+pub fn build_cfg() {}
@@ -0,0 +1,3 @@
+fn main() {
+  assert_eq!(mac::answer!(), 42);
+}
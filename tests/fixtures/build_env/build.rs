@@ -0,0 +1,9 @@
+use std::io::Write;
+
+fn main() {
+  let mut stdout = std::io::stdout();
+  stdout.write_all(b"cargo:rustc-env=NOT_UTF8=\xff\xfe\n").unwrap();
+  stdout.write_all(b"cargo:rustc-env=UTF8=ok\n").unwrap();
+  let jobs = std::env::var("NUM_JOBS").unwrap();
+  writeln!(stdout, "cargo:rustc-env=BUILD_NUM_JOBS={}", jobs).unwrap();
+}
@@ -0,0 +1 @@
+pub const UTF8: &str = env!("UTF8");
@@ -0,0 +1,3 @@
+pub fn answer() -> u32 {
+  include!(concat!(env!("OUT_DIR"), "/answer.rs"))
+}
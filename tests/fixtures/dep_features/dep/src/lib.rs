@@ -0,0 +1,4 @@
+#[cfg(feature = "x")]
+pub fn x() -> u32 {
+  1
+}
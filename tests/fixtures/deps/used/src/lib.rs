@@ -0,0 +1 @@
+pub const VALUE: u32 = 1;
@@ -0,0 +1,3 @@
+fn main() {
+  println!("{}", dev_deps::value() + devdep::VALUE);
+}
@@ -0,0 +1,4 @@
+#[test]
+fn adds_up() {
+  assert_eq!(dev_deps::value() + devdep::VALUE, devdep::VALUE + dev_deps::value());
+}
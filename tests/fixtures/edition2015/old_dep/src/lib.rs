@@ -0,0 +1 @@
+pub const ANSWER: u32 = 42;
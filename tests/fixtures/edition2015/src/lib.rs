@@ -0,0 +1,5 @@
+extern crate old_dep;
+
+pub fn answer() -> u32 {
+  ::old_dep::ANSWER
+}
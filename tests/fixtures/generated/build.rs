@@ -0,0 +1,6 @@
+use std::{env, fs, path::Path};
+
+fn main() {
+  let out_dir = env::var("OUT_DIR").unwrap();
+  fs::write(Path::new(&out_dir).join("generated.rs"), "pub fn generated() {}\n").unwrap();
+}
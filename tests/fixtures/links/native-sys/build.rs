@@ -0,0 +1,8 @@
+use std::env;
+
+fn main() {
+  let out_dir = env::var("OUT_DIR").unwrap();
+  println!("cargo:rustc-link-search=native={}", out_dir);
+  println!("cargo:include={}/include", out_dir);
+  println!("cargo:lib-version=1.2");
+}
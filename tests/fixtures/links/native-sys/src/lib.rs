@@ -0,0 +1 @@
+pub const VERSION: &str = "1.2";
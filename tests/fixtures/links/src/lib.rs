@@ -0,0 +1 @@
+pub use native_sys::VERSION;
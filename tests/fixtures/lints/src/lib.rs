@@ -0,0 +1 @@
+//! Lints.
@@ -0,0 +1,12 @@
+#![feature(never_type)]
+
+pub fn diverge() -> ! {
+  panic!()
+}
+
+pub fn never(x: Result<u32, !>) -> u32 {
+  match x {
+    Ok(x) => x,
+    Err(never) => never,
+  }
+}
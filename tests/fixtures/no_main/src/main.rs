@@ -0,0 +1,6 @@
+#![no_main]
+
+#[no_mangle]
+pub extern "C" fn main(_argc: i32, _argv: *const *const u8) -> i32 {
+  0
+}
@@ -0,0 +1 @@
+pub const PATCHED: bool = true;
@@ -0,0 +1,2 @@
+pub use shared1::VERSION as V1;
+pub use shared2::VERSION as V2;
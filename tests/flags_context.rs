@@ -0,0 +1,26 @@
+// Remembered flags, and dropping them when the manifest changes.
+
+mod common;
+
+use generate_rustc_flags::{FlagsContext, FlagsOptions, RustcFlag};
+use std::fs;
+
+fn has_cfg(flags: &generate_rustc_flags::RustcFlags, cfg: &str) -> bool {
+  flags.flags.contains(&RustcFlag::Cfg(cfg.to_string()))
+}
+
+#[test]
+fn manifest_changes_are_picked_up() {
+  let ws = common::fixture("basic", "flags_context");
+  let mut context = FlagsContext::new(FlagsOptions::default()).unwrap();
+  let first = context.generate(ws.join("src/lib.rs")).unwrap();
+  assert!(has_cfg(&first, "feature=\"foo\""));
+  assert_eq!(context.generate(ws.join("src/lib.rs")).unwrap().to_args(), first.to_args());
+
+  let manifest = fs::read_to_string(ws.join("Cargo.toml")).unwrap();
+  let manifest = manifest.replace("default = [\"foo\"]", "default = [\"bar\"]");
+  fs::write(ws.join("Cargo.toml"), manifest).unwrap();
+  let second = context.generate(ws.join("src/lib.rs")).unwrap();
+  assert!(has_cfg(&second, "feature=\"bar\""));
+  assert!(!has_cfg(&second, "feature=\"foo\""));
+}
@@ -0,0 +1,40 @@
+// Assembling flags for a unit the caller found in cargo's unit graph.
+
+mod common;
+
+use cargo::{
+  core::{
+    compiler::{build_map, lto, Context, UnitInterner},
+    Workspace,
+  },
+  ops::{create_bcx, CompileOptions},
+  util::config::Config,
+};
+use generate_rustc_flags::{flags_for_unit, generate, CompileMode, FlagsOptions};
+
+#[test]
+fn a_unit_from_the_graph_gives_the_same_flags() {
+  let ws = common::fixture("build_env", "flags_for_unit");
+  let options = FlagsOptions::default();
+  let (from_unit, generated) = common::in_dir(&ws, || {
+    // The context holds the target directory lock, so it's gone before `generate` runs.
+    let from_unit = {
+      let config = Config::default().unwrap();
+      let workspace = Workspace::new(&ws.join("Cargo.toml"), &config).unwrap();
+      let mode = CompileMode::Check { test: false };
+      let compile_opts = CompileOptions::new(&config, mode).unwrap();
+      let interner = UnitInterner::new();
+      let bcx = create_bcx(&workspace, &compile_opts, &interner).unwrap();
+      let mut cx = Context::new(&bcx).unwrap();
+      cx.lto = lto::generate(&bcx).unwrap();
+      cx.prepare_units().unwrap();
+      cx.prepare().unwrap();
+      build_map(&mut cx).unwrap();
+
+      let lib = bcx.roots.iter().find(|unit| unit.target.is_lib()).unwrap();
+      flags_for_unit(&mut cx, lib, &options).unwrap()
+    };
+    (from_unit, generate(ws.join("src/lib.rs"), &options).unwrap())
+  });
+  assert_eq!(from_unit, generated.to_args());
+}
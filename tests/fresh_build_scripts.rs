@@ -0,0 +1,46 @@
+// Build scripts whose recorded output cargo's fingerprints consider fresh aren't run again.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions};
+use std::{fs, path::Path};
+
+// The script counts its runs in `OUT_DIR`, which cargo keeps between them.
+const BUILD_RS: &str = r#"use std::io::Write;
+
+fn main() {
+  let runs = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("runs");
+  let mut file = std::fs::OpenOptions::new().create(true).append(true).open(runs).unwrap();
+  writeln!(file, "ran").unwrap();
+  println!("cargo:rerun-if-changed=build.rs");
+  println!("cargo:rustc-env=UTF8=ok");
+}
+"#;
+
+fn runs(out_dir: &str) -> usize {
+  fs::read_to_string(Path::new(out_dir).join("runs")).unwrap().lines().count()
+}
+
+#[test]
+fn fresh_output_is_reused() {
+  let ws = common::fixture("build_env", "fresh_build_scripts_reused");
+  fs::write(ws.join("build.rs"), BUILD_RS).unwrap();
+  let options = FlagsOptions::default();
+  let first = generate(ws.join("src/lib.rs"), &options).unwrap();
+  let second = generate(ws.join("src/lib.rs"), &options).unwrap();
+  assert_eq!(runs(&first.env["OUT_DIR"]), 1);
+  // The environment still comes from the recorded output.
+  assert_eq!(second.env["UTF8"], "ok");
+  assert_eq!(second.env["OUT_DIR"], first.env["OUT_DIR"]);
+}
+
+#[test]
+fn changed_inputs_run_the_script_again() {
+  let ws = common::fixture("build_env", "fresh_build_scripts_changed");
+  fs::write(ws.join("build.rs"), BUILD_RS).unwrap();
+  let options = FlagsOptions::default();
+  let first = generate(ws.join("src/lib.rs"), &options).unwrap();
+  fs::write(ws.join("build.rs"), format!("{}\n// Changed.\n", BUILD_RS)).unwrap();
+  generate(ws.join("src/lib.rs"), &options).unwrap();
+  assert_eq!(runs(&first.env["OUT_DIR"]), 2);
+}
@@ -0,0 +1,51 @@
+// Flags for every root unit of a workspace at once.
+
+mod common;
+
+use generate_rustc_flags::{generate, generate_all_rustc_flags, FlagsOptions};
+
+#[test]
+fn matches_generating_each_target() {
+  let ws = common::fixture("basic", "generate_all");
+  let options = FlagsOptions::default();
+  let all = generate_all_rustc_flags(ws.join("Cargo.toml"), &options).unwrap();
+  let mut kinds = all.keys().map(|id| id.target_kind.as_str()).collect::<Vec<_>>();
+  kinds.sort_unstable();
+  assert_eq!(kinds, ["bin", "lib"]);
+
+  let lib = all.iter().find(|(id, _)| id.target_kind == "lib").unwrap().1;
+  let single = generate(ws.join("src/lib.rs"), &options).unwrap();
+  assert_eq!(lib.to_args(), single.to_args());
+}
+
+#[test]
+fn build_scripts_run_before_the_flags_are_assembled() {
+  let ws = common::fixture("build_env", "generate_all_build_scripts");
+  let options = FlagsOptions::default();
+  let all = generate_all_rustc_flags(ws.join("Cargo.toml"), &options).unwrap();
+  let lib = all.iter().find(|(id, _)| id.target_kind == "lib").unwrap().1;
+  assert_eq!(lib.env["UTF8"], "ok");
+
+  let single = generate(ws.join("src/lib.rs"), &options).unwrap();
+  assert_eq!(lib.to_args(), single.to_args());
+  assert_eq!(lib.env, single.env);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn parallel_matches_serial() {
+  use generate_rustc_flags::generate_all_parallel;
+
+  let ws = common::fixture("basic", "generate_all_parallel");
+  let options = FlagsOptions {
+    collect_inputs: true,
+    ..FlagsOptions::default()
+  };
+  let serial = generate_all_rustc_flags(ws.join("Cargo.toml"), &options).unwrap();
+  let parallel = generate_all_parallel(ws.join("Cargo.toml"), &options).unwrap();
+  assert_eq!(serial.len(), parallel.len());
+  for (id, flags) in &serial {
+    assert_eq!(flags.to_args(), parallel[id].to_args(), "{:?}", id);
+    assert_eq!(flags.inputs, parallel[id].inputs, "{:?}", id);
+  }
+}
@@ -0,0 +1,20 @@
+// Files a build script generates into `OUT_DIR`.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions};
+use std::path::Path;
+
+#[test]
+fn generated_files_belong_to_the_unit() {
+  let ws = common::fixture("generated", "generated_out_dir");
+  let options = FlagsOptions::default();
+  // Generating for the lib runs the build script, which writes the file.
+  let lib_flags = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap());
+  let generated = Path::new(&lib_flags.env["OUT_DIR"]).join("generated.rs");
+  assert!(generated.is_file());
+
+  let flags = common::in_dir(&ws, || generate(&generated, &options).unwrap());
+  assert_eq!(common::values(&flags.to_args(), "--crate-name"), ["generated"]);
+  assert_eq!(flags.env["OUT_DIR"], lib_flags.env["OUT_DIR"]);
+}
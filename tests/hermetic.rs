@@ -0,0 +1,64 @@
+// Generating for a read-only source tree, with every write redirected to a scratch dir.
+
+#![cfg(unix)]
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions};
+use std::{
+  fs,
+  os::unix::fs::PermissionsExt,
+  path::{Path, PathBuf},
+};
+
+// Every path under `dir`, sorted.
+fn tree(dir: &Path) -> Vec<PathBuf> {
+  let mut paths = Vec::new();
+  for entry in fs::read_dir(dir).unwrap() {
+    let path = entry.unwrap().path();
+    if path.is_dir() {
+      paths.extend(tree(&path));
+    }
+    paths.push(path);
+  }
+  paths.sort();
+  paths
+}
+
+fn set_mode(dir: &Path, dir_mode: u32, file_mode: u32) {
+  for path in tree(dir).into_iter().chain(Some(dir.to_path_buf())) {
+    let mode = if path.is_dir() { dir_mode } else { file_mode };
+    fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+  }
+}
+
+#[test]
+fn read_only_trees_are_left_untouched() {
+  // Writing the lockfile is the one thing hermetic mode won't do, so take it from a
+  // normal run.
+  let unlocked = common::fixture("basic", "hermetic_lockfile");
+  let options = FlagsOptions::default();
+  common::in_dir(&unlocked, || generate(unlocked.join("src/lib.rs"), &options).unwrap());
+  let ws = common::fixture("basic", "hermetic");
+  fs::copy(unlocked.join("Cargo.lock"), ws.join("Cargo.lock")).unwrap();
+
+  let before = tree(&ws);
+  set_mode(&ws, 0o555, 0o444);
+  let scratch = Path::new(env!("CARGO_TARGET_TMPDIR")).join("hermetic/scratch");
+  if scratch.exists() {
+    fs::remove_dir_all(&scratch).unwrap();
+  }
+  let options = FlagsOptions {
+    hermetic_dir: Some(scratch.clone()),
+    ..FlagsOptions::default()
+  };
+  let flags = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options));
+  set_mode(&ws, 0o755, 0o644);
+
+  let flags = flags.unwrap();
+  assert_eq!(common::values(&flags.to_args(), "--crate-name"), ["basic"]);
+  let deps = scratch.join("target/debug/deps");
+  assert_eq!(common::values(&flags.to_args(), "-L"), [format!("dependency={}", deps.display())]);
+  // Permissions don't stop root, so also check that nothing was written.
+  assert_eq!(tree(&ws), before);
+}
@@ -0,0 +1,70 @@
+// Where rustc keeps incremental state across driver runs.
+
+mod common;
+
+use generate_rustc_flags::{generate, FallbackMode, FlagsOptions, Incremental};
+use std::path::Path;
+
+fn incremental_dirs(ws: &Path, file: &str, options: &FlagsOptions) -> Vec<String> {
+  let args = generate(ws.join(file), options).unwrap().to_args();
+  common::values(&args, "-C")
+    .into_iter()
+    .filter_map(|value| value.strip_prefix("incremental=").map(str::to_string))
+    .collect()
+}
+
+fn incremental(incremental: Incremental, profile: Option<&str>) -> FlagsOptions {
+  FlagsOptions {
+    incremental,
+    profile: profile.map(str::to_string),
+    ..FlagsOptions::default()
+  }
+}
+
+#[test]
+fn the_profile_decides_by_default() {
+  let ws = common::fixture("basic", "incremental_profile");
+  let cargos = ws.join("target/debug/incremental");
+  assert_eq!(
+    incremental_dirs(&ws, "src/lib.rs", &FlagsOptions::default()),
+    [format!("{}", cargos.display())]
+  );
+  let release = incremental(Incremental::Profile, Some("release"));
+  assert_eq!(incremental_dirs(&ws, "src/lib.rs", &release), Vec::<String>::new());
+}
+
+#[test]
+fn always_overrides_the_profile() {
+  let ws = common::fixture("basic", "incremental_always");
+  let cargos = ws.join("target/release/incremental");
+  let options = incremental(Incremental::Always, Some("release"));
+  assert_eq!(incremental_dirs(&ws, "src/lib.rs", &options), [format!("{}", cargos.display())]);
+}
+
+#[test]
+fn a_chosen_directory_is_used() {
+  let ws = common::fixture("basic", "incremental_dir");
+  let dir = ws.join("driver-incremental");
+  let options = incremental(Incremental::Dir(dir.clone()), Some("release"));
+  assert_eq!(incremental_dirs(&ws, "src/lib.rs", &options), [format!("{}", dir.display())]);
+
+  let options = FlagsOptions {
+    fallback: FallbackMode::Always,
+    ..options
+  };
+  assert_eq!(incremental_dirs(&ws, "src/lib.rs", &options), [format!("{}", dir.display())]);
+}
+
+#[test]
+fn off_disables_it() {
+  let ws = common::fixture("basic", "incremental_off");
+  let options = incremental(Incremental::Off, None);
+  assert_eq!(incremental_dirs(&ws, "src/lib.rs", &options), Vec::<String>::new());
+
+  let options = FlagsOptions {
+    fallback: FallbackMode::Always,
+    ..incremental(Incremental::Always, None)
+  };
+  // Standalone files have no cargo directory.
+  assert_eq!(incremental_dirs(&ws, "src/lib.rs", &options), Vec::<String>::new());
+}
@@ -0,0 +1,48 @@
+// What a unit's compilation depends on, for invalidating results derived from it.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions};
+
+fn collecting() -> FlagsOptions {
+  FlagsOptions {
+    collect_inputs: true,
+    ..FlagsOptions::default()
+  }
+}
+
+#[test]
+fn inputs_are_only_collected_on_request() {
+  let ws = common::fixture("basic", "inputs_off");
+  let flags = generate(ws.join("src/lib.rs"), &FlagsOptions::default()).unwrap();
+  assert_eq!(flags.inputs, None);
+}
+
+#[test]
+fn sources_come_from_the_last_compilation() {
+  let ws = common::fixture("basic", "inputs_sources");
+  let flags = generate(ws.join("src/lib.rs"), &collecting()).unwrap();
+  // Nothing was compiled yet.
+  assert!(flags.inputs.as_ref().unwrap().sources.is_empty());
+  assert!(flags.inputs.as_ref().unwrap().build_script_inputs.is_empty());
+
+  common::run(&flags.to_args(), &ws);
+  let flags = generate(ws.join("src/lib.rs"), &collecting()).unwrap();
+  let sources = flags.inputs.unwrap().sources;
+  assert!(sources.contains(&ws.join("src/lib.rs")), "{:?}", sources);
+  assert!(sources.contains(&ws.join("src/util.rs")), "{:?}", sources);
+}
+
+#[test]
+fn build_scripts_add_their_inputs_and_env() {
+  let ws = common::fixture("build_env", "inputs_build_script");
+  let flags = generate(ws.join("src/lib.rs"), &collecting()).unwrap();
+  let output = flags.command().current_dir(&ws).output().unwrap();
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+  let inputs = generate(ws.join("src/lib.rs"), &collecting()).unwrap().inputs.unwrap();
+  // The script prints no `rerun-if-changed`, so any file in the package counts.
+  assert_eq!(inputs.build_script_inputs, [ws.clone()]);
+  // Read with `env!`.
+  assert!(inputs.env.contains(&"UTF8".to_string()), "{:?}", inputs.env);
+}
@@ -0,0 +1,37 @@
+// `--json=artifacts` and the artifact messages rustc prints with it.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions};
+use std::process::Command;
+
+#[test]
+fn artifact_messages_name_every_output() {
+  let ws = common::fixture("basic", "json_artifacts");
+  let options = FlagsOptions {
+    json_artifacts: true,
+    ..FlagsOptions::default()
+  };
+  let args = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap().to_args());
+  let json = args.iter().position(|arg| arg == "--error-format=json").unwrap();
+  assert_eq!(args[json + 1], "--json=artifacts,diagnostic-rendered-ansi");
+
+  let output = Command::new(&args[0]).args(&args[1..]).current_dir(&ws).output().unwrap();
+  let stderr = String::from_utf8_lossy(&output.stderr);
+  assert!(output.status.success(), "{}", stderr);
+  // Messages look like `{"artifact":"/path/to/libbasic.rmeta","emit":"metadata"}`.
+  let emits = stderr
+    .lines()
+    .filter(|line| line.starts_with("{\"artifact\":"))
+    .filter_map(|line| line.split("\"emit\":\"").nth(1)?.split('"').next())
+    .collect::<Vec<_>>();
+  assert_eq!(emits, ["dep-info", "metadata"]);
+}
+
+#[test]
+fn off_by_default() {
+  let ws = common::fixture("basic", "json_artifacts_default");
+  let options = FlagsOptions::default();
+  let args = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap().to_args());
+  assert!(!args.iter().any(|arg| arg.starts_with("--error-format") || arg.starts_with("--json")));
+}
@@ -0,0 +1,32 @@
+// The flags and environment from the build scripts of `links` packages a unit links with.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions};
+
+#[test]
+fn linked_search_paths_are_passed() {
+  let ws = common::fixture("links", "links_search_paths");
+  let flags = generate(ws.join("src/lib.rs"), &FlagsOptions::default()).unwrap();
+  let native = common::values(&flags.to_args(), "-L")
+    .into_iter()
+    .filter(|path| path.starts_with("native="))
+    .collect::<Vec<_>>();
+  assert_eq!(native.len(), 1, "{:?}", native);
+  assert!(native[0].contains("native-sys-"), "{}", native[0]);
+  assert!(!flags.env.keys().any(|key| key.starts_with("DEP_")), "{:?}", flags.env);
+}
+
+#[test]
+fn dep_links_env_sets_their_metadata() {
+  let ws = common::fixture("links", "links_dep_env");
+  let options = FlagsOptions {
+    dep_links_env: true,
+    ..FlagsOptions::default()
+  };
+  let flags = generate(ws.join("src/lib.rs"), &options).unwrap();
+  assert!(flags.env["DEP_NATIVE_INCLUDE"].ends_with("/include"), "{:?}", flags.env);
+  assert_eq!(flags.env["DEP_NATIVE_LIB_VERSION"], "1.2");
+  assert!(!flags.env.contains_key("DEP_NATIVE_RUSTC_LINK_SEARCH"));
+  common::run(&flags.to_args(), &ws);
+}
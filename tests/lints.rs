@@ -0,0 +1,53 @@
+// Lint level flags from the `[lints]` table, in cargo's order.
+
+mod common;
+
+use generate_rustc_flags::{generate, CargoCompat, FlagsOptions, RustcFlag};
+use std::{fs, io::Write};
+
+// Generates from the package in `package_dir` of the fixture, as cargo would when run there.
+fn lint_args(package_dir: &str, test: &str) -> Vec<String> {
+  let ws = common::fixture("lints", test);
+  let package = ws.join(package_dir);
+  let flags = common::in_dir(&package, || {
+    generate(package.join("src/lib.rs"), &FlagsOptions::default()).unwrap()
+  });
+  flags
+    .flags
+    .iter()
+    .filter(|flag| matches!(flag, RustcFlag::Lint { .. }))
+    .flat_map(RustcFlag::render)
+    .collect()
+}
+
+#[test]
+fn lints_come_in_priority_order() {
+  assert_eq!(
+    lint_args(".", "lints_package"),
+    ["--deny=clippy::all", "--forbid=unsafe_code", "--warn=missing_docs"]
+  );
+}
+
+#[test]
+fn members_inherit_workspace_lints() {
+  assert_eq!(lint_args("member", "lints_inherited"), ["--allow=unused"]);
+}
+
+#[test]
+fn members_inherit_workspace_check_cfgs() {
+  let ws = common::fixture("lints", "lints_inherited_check_cfg");
+  let mut manifest = fs::OpenOptions::new().append(true).open(ws.join("Cargo.toml")).unwrap();
+  manifest
+    .write_all(b"unexpected_cfgs = { level = \"warn\", check-cfg = ['cfg(foo)'] }\n")
+    .unwrap();
+
+  let package = ws.join("member");
+  let options = FlagsOptions {
+    cargo_compat: CargoCompat::CheckCfg,
+    ..FlagsOptions::default()
+  };
+  let args = common::in_dir(&package, || {
+    generate(package.join("src/lib.rs"), &options).unwrap().to_args()
+  });
+  assert!(common::values(&args, "--check-cfg").contains(&"cfg(foo)".to_string()), "{:?}", args);
+}
@@ -0,0 +1,69 @@
+// The `-C symbol-mangling-version` cargo's configuration picks, for a 2021-edition crate.
+
+mod common;
+
+use generate_rustc_flags::{generate_rustc_flags_with, FlagsOptions, SymbolManglingVersion};
+use std::fs;
+use std::path::Path;
+
+fn mangling(ws: &Path, options: &FlagsOptions) -> Vec<String> {
+  let args = common::in_dir(ws, || {
+    generate_rustc_flags_with(ws.join("src/lib.rs"), options).unwrap()
+  });
+  common::values(&args, "-C")
+    .into_iter()
+    .filter(|value| value.starts_with("symbol-mangling-version="))
+    .collect()
+}
+
+#[test]
+fn defaults_to_rustcs_choice() {
+  let ws = common::fixture("edition2021", "mangling_default");
+  assert_eq!(mangling(&ws, &FlagsOptions::default()), Vec::<String>::new());
+}
+
+#[test]
+fn follows_the_option() {
+  let ws = common::fixture("edition2021", "mangling_option");
+  let options = FlagsOptions {
+    symbol_mangling_version: Some(SymbolManglingVersion::V0),
+    ..FlagsOptions::default()
+  };
+  assert_eq!(mangling(&ws, &options), ["symbol-mangling-version=v0"]);
+}
+
+#[test]
+fn follows_rustflags_from_config() {
+  let ws = common::fixture("edition2021", "mangling_rustflags");
+  fs::create_dir_all(ws.join(".cargo")).unwrap();
+  fs::write(
+    ws.join(".cargo/config.toml"),
+    "[build]\nrustflags = [\"-C\", \"symbol-mangling-version=v0\"]\n",
+  )
+  .unwrap();
+  assert_eq!(mangling(&ws, &FlagsOptions::default()), ["symbol-mangling-version=v0"]);
+}
+
+#[test]
+fn older_rustcs_get_the_unstable_spelling() {
+  let ws = common::fixture("edition2021", "mangling_rustc_version");
+  let flags = |version: &str| {
+    let options = FlagsOptions {
+      symbol_mangling_version: Some(SymbolManglingVersion::V0),
+      rustc_version: Some(semver::Version::parse(version).unwrap()),
+      ..FlagsOptions::default()
+    };
+    let args = common::in_dir(&ws, || {
+      generate_rustc_flags_with(ws.join("src/lib.rs"), &options).unwrap()
+    });
+    (common::values(&args, "-Z"), common::values(&args, "-C"))
+  };
+
+  let (unstable, codegen) = flags("1.58.0");
+  assert!(unstable.contains(&"symbol-mangling-version=v0".to_string()));
+  assert!(!codegen.iter().any(|value| value.starts_with("symbol-mangling-version")));
+
+  let (unstable, codegen) = flags("1.59.0");
+  assert!(!unstable.iter().any(|value| value.starts_with("symbol-mangling-version")));
+  assert!(codegen.contains(&"symbol-mangling-version=v0".to_string()));
+}
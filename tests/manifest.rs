@@ -0,0 +1,46 @@
+// Manifests that are broken only in sections that can't affect the flags.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions};
+use std::path::Path;
+
+fn generate_in(ws: &Path, tolerant_manifest: bool) -> anyhow::Result<Vec<String>> {
+  let options = FlagsOptions {
+    tolerant_manifest,
+    ..FlagsOptions::default()
+  };
+  common::in_dir(ws, || generate(ws.join("src/lib.rs"), &options)).map(|flags| flags.to_args())
+}
+
+#[test]
+fn tolerant_loading_skips_broken_metadata() {
+  let ws = common::fixture("broken_metadata", "manifest_tolerant");
+  let args = generate_in(&ws, true).unwrap();
+  assert_eq!(common::values(&args, "--crate-name"), ["broken_metadata"]);
+  // The sections after the broken one are still read.
+  assert!(common::values(&args, "--cfg").contains(&"feature=\"std\"".to_string()));
+}
+
+#[test]
+fn errors_name_the_broken_section() {
+  let ws = common::fixture("broken_metadata", "manifest_strict");
+  let message = format!("{:#}", generate_in(&ws, false).unwrap_err());
+  assert!(
+    message.contains("syntax error in section [package.metadata.docs.rs] (line 8)"),
+    "{}",
+    message
+  );
+}
+
+#[test]
+fn workspace_roots_are_not_loaded_tolerantly() {
+  // Loading the root on its own would drop `member` from the workspace.
+  let ws = common::fixture("broken_workspace", "manifest_workspace");
+  let message = format!("{:#}", generate_in(&ws, true).unwrap_err());
+  assert!(
+    message.contains("syntax error in section [workspace.metadata.release] (line 10)"),
+    "{}",
+    message
+  );
+}
@@ -0,0 +1,25 @@
+// Errors for manifests that don't exist.
+
+mod common;
+
+use generate_rustc_flags::{generate, resolved_features, CliFeatures, FlagsOptions};
+
+#[test]
+fn a_missing_manifest_path_is_named() {
+  let ws = common::fixture("basic", "manifest_path_missing");
+  let manifest_path = ws.join("missing/Cargo.toml");
+  let err = resolved_features(&manifest_path, CliFeatures::new_all(false)).unwrap_err();
+  assert_eq!(err.to_string(), format!("Manifest not found at {}", manifest_path.display()));
+}
+
+#[test]
+fn a_missing_manifest_option_is_named() {
+  let ws = common::fixture("basic", "manifest_path_option");
+  let manifest_path = ws.join("missing/Cargo.toml");
+  let options = FlagsOptions {
+    manifest_path: Some(manifest_path.clone()),
+    ..FlagsOptions::default()
+  };
+  let err = generate(ws.join("src/lib.rs"), &options).unwrap_err();
+  assert_eq!(err.to_string(), format!("Manifest not found at {}", manifest_path.display()));
+}
@@ -0,0 +1,32 @@
+// Which workspace member's unit a file matches.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions, RustcFlag};
+use std::path::{Path, PathBuf};
+
+// The crate the flags for `file` compile, for the package loaded from `manifest_path`.
+fn crate_name(manifest_path: Option<PathBuf>, file: &Path) -> String {
+  let options = FlagsOptions {
+    manifest_path,
+    ..FlagsOptions::default()
+  };
+  let flags = generate(file, &options).unwrap();
+  flags
+    .flags
+    .iter()
+    .find_map(|flag| match flag {
+      RustcFlag::CrateName(name) => Some(name.clone()),
+      _ => None,
+    })
+    .unwrap()
+}
+
+#[test]
+fn the_loaded_package_wins_over_siblings() {
+  let ws = common::fixture("nested", "members_current");
+  let file = ws.join("src/inner/src/lib.rs");
+  assert_eq!(crate_name(Some(ws.join("Cargo.toml")), &file), "outer");
+  // Without a manifest, the nearest one above the file is loaded.
+  assert_eq!(crate_name(None, &file), "inner");
+}
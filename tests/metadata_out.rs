@@ -0,0 +1,39 @@
+// Writing the `.rmeta` to a chosen path, for chaining rustc invocations.
+
+mod common;
+
+use generate_rustc_flags::{generate_rustc_flags_with, FlagsOptions};
+use std::process::Command;
+
+#[test]
+fn metadata_lands_at_the_requested_path() {
+  let ws = common::fixture("basic", "metadata_out");
+  let out = ws.join("out/libbasic.rmeta");
+  std::fs::create_dir_all(out.parent().unwrap()).unwrap();
+  let options = FlagsOptions {
+    metadata_out: Some(out.clone()),
+    ..FlagsOptions::default()
+  };
+  let args = common::in_dir(&ws, || {
+    generate_rustc_flags_with(ws.join("src/lib.rs"), &options).unwrap()
+  });
+  let expected = format!("metadata={}", out.display());
+  assert!(common::values(&args, "--emit")[0].split(',').any(|kind| kind == expected));
+
+  let status = Command::new(&args[0]).args(&args[1..]).current_dir(&ws).status().unwrap();
+  assert!(status.success());
+  assert!(out.is_file());
+}
+
+#[test]
+fn the_directory_must_exist() {
+  let ws = common::fixture("basic", "metadata_out_missing_dir");
+  let options = FlagsOptions {
+    metadata_out: Some(ws.join("missing/libbasic.rmeta")),
+    ..FlagsOptions::default()
+  };
+  let err = common::in_dir(&ws, || {
+    generate_rustc_flags_with(ws.join("src/lib.rs"), &options).unwrap_err()
+  });
+  assert!(format!("{:#}", err).contains("does not exist"), "{:#}", err);
+}
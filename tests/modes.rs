@@ -0,0 +1,80 @@
+// The crate type and test flags of each target kind, in each mode.
+
+mod common;
+
+use generate_rustc_flags::{generate, CompileMode, FlagsOptions};
+
+// (crate types, whether `--test` is passed, whether `--cfg test` is passed)
+fn test_flags(target: &str, options: &FlagsOptions, test: &str) -> (Vec<String>, bool, bool) {
+  let ws = common::fixture("basic", test);
+  let args = common::in_dir(&ws, || generate(ws.join(target), options).unwrap().to_args());
+  (
+    common::values(&args, "--crate-type"),
+    args.iter().any(|arg| arg == "--test"),
+    common::values(&args, "--cfg").iter().any(|cfg| cfg == "test"),
+  )
+}
+
+#[test]
+fn check_mode_keeps_the_crate_type() {
+  assert_eq!(
+    test_flags("src/lib.rs", &FlagsOptions::default(), "modes_check_lib"),
+    (vec!["lib".to_string()], false, false)
+  );
+  // src/main.rs sits next to the lib's root, but is the bin's own.
+  assert_eq!(
+    test_flags("src/main.rs", &FlagsOptions::default(), "modes_check_bin"),
+    (vec!["bin".to_string()], false, false)
+  );
+}
+
+#[test]
+fn test_mode_passes_test_instead_of_the_crate_type() {
+  let options = FlagsOptions {
+    mode: CompileMode::Check { test: true },
+    lib_only: true,
+    ..FlagsOptions::default()
+  };
+  // Test harnesses are their own crate type, which `--test` selects.
+  assert_eq!(
+    test_flags("src/lib.rs", &options, "modes_check_test_lib"),
+    (Vec::new(), true, false)
+  );
+}
+
+fn emit(mode: CompileMode, test: &str) -> Vec<String> {
+  let ws = common::fixture("basic", test);
+  let options = FlagsOptions {
+    mode,
+    ..FlagsOptions::default()
+  };
+  let args = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap().to_args());
+  common::values(&args, "--emit")
+}
+
+#[test]
+fn emit_follows_the_mode() {
+  assert_eq!(emit(CompileMode::Check { test: false }, "modes_emit_check"), ["dep-info,metadata"]);
+  assert_eq!(emit(CompileMode::Build, "modes_emit_build"), ["dep-info,metadata,link"]);
+}
+
+#[test]
+fn the_test_option_compiles_unit_tests() {
+  let options = FlagsOptions {
+    test: true,
+    lib_only: true,
+    ..FlagsOptions::default()
+  };
+  assert_eq!(
+    test_flags("src/lib.rs", &options, "modes_test_option"),
+    (Vec::new(), true, false)
+  );
+  let options = FlagsOptions {
+    mode: CompileMode::Build,
+    ..options
+  };
+  assert_eq!(
+    test_flags("src/lib.rs", &options, "modes_test_option_build"),
+    (Vec::new(), true, false)
+  );
+}
@@ -0,0 +1,27 @@
+// A crate enabling nightly features with `#![feature(..)]` type-checks under the flags.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions};
+use std::{env, process::Command};
+
+fn is_nightly() -> bool {
+  let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+  let output = Command::new(rustc).arg("-vV").output().unwrap();
+  let version = String::from_utf8_lossy(&output.stdout);
+  version.lines().any(|line| line.starts_with("release: ") && line.contains("nightly"))
+}
+
+#[test]
+fn feature_gated_crates_type_check() {
+  let ws = common::fixture("nightly", "nightly_feature");
+  let options = FlagsOptions::default();
+  let flags = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap());
+  let mut command = flags.command();
+  // On other channels, do what a user of the flags would have to.
+  if !is_nightly() {
+    command.env("RUSTC_BOOTSTRAP", "1");
+  }
+  let output = command.current_dir(&ws).output().unwrap();
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
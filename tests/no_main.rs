@@ -0,0 +1,28 @@
+// `#![no_main]` binaries, which have no Rust `main`.
+
+mod common;
+
+use generate_rustc_flags::{generate, FallbackMode, FlagsOptions};
+use std::process::Command;
+
+#[test]
+fn package_bins_type_check() {
+  let ws = common::fixture("no_main", "no_main_package");
+  let options = FlagsOptions::default();
+  let args = common::in_dir(&ws, || generate(ws.join("src/main.rs"), &options).unwrap().to_args());
+  assert_eq!(common::values(&args, "--crate-type"), ["bin"]);
+
+  let status = Command::new(&args[0]).args(&args[1..]).current_dir(&ws).status().unwrap();
+  assert!(status.success());
+}
+
+#[test]
+fn fallback_treats_them_as_bins() {
+  let ws = common::fixture("no_main", "no_main_fallback");
+  let options = FlagsOptions {
+    fallback: FallbackMode::Always,
+    ..FlagsOptions::default()
+  };
+  let args = common::in_dir(&ws, || generate(ws.join("src/main.rs"), &options).unwrap().to_args());
+  assert_eq!(common::values(&args, "--crate-type"), ["bin"]);
+}
@@ -0,0 +1,26 @@
+// Observing the units considered for a source file.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions};
+use std::{cell::RefCell, rc::Rc};
+
+#[test]
+fn every_candidate_is_reported() {
+  let ws = common::fixture("basic", "on_candidate");
+  let candidates = Rc::new(RefCell::new(Vec::new()));
+  let recorded = Rc::clone(&candidates);
+  let options = FlagsOptions {
+    on_candidate: Some(Box::new(move |unit| {
+      let kind = if unit.target.is_lib() { "lib" } else { "bin" };
+      recorded.borrow_mut().push(format!("{} {}", kind, unit.target.name()));
+    })),
+    ..FlagsOptions::default()
+  };
+  // Both the lib and the bin have their crate root in src/, so both could own util.rs.
+  let flags = common::in_dir(&ws, || generate(ws.join("src/util.rs"), &options).unwrap());
+  let mut candidates = candidates.borrow().clone();
+  candidates.sort();
+  assert_eq!(candidates, ["bin basic", "lib basic"]);
+  assert_eq!(common::values(&flags.to_args(), "--crate-type"), ["lib"]);
+}
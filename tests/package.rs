@@ -0,0 +1,42 @@
+// Which member is compiled, in workspaces with and without a root package.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions, RustcFlag};
+
+fn options(ws: &std::path::Path, package: Option<&str>) -> FlagsOptions {
+  FlagsOptions {
+    manifest_path: Some(ws.join("Cargo.toml")),
+    package: package.map(str::to_string),
+    ..FlagsOptions::default()
+  }
+}
+
+#[test]
+fn virtual_workspaces_compile_the_owning_member() {
+  let ws = common::fixture("virtual", "package_owning");
+  for member in ["a", "b"] {
+    let file = ws.join(member).join("src/lib.rs");
+    let flags = generate(&file, &options(&ws, None)).unwrap();
+    assert!(flags.flags.contains(&RustcFlag::CrateName(member.into())), "{:?}", flags.to_args());
+  }
+}
+
+#[test]
+fn the_chosen_package_is_compiled() {
+  let ws = common::fixture("virtual", "package_chosen");
+  let flags = generate(ws.join("a/src/lib.rs"), &options(&ws, Some("a"))).unwrap();
+  assert!(flags.flags.contains(&RustcFlag::CrateName("a".into())));
+  // Another member's files aren't part of it.
+  assert!(generate(ws.join("b/src/lib.rs"), &options(&ws, Some("a"))).is_err());
+}
+
+#[test]
+fn members_are_found_from_subdirectories() {
+  let ws = common::fixture("virtual", "package_subdirectory");
+  std::fs::create_dir_all(ws.join("b/src/nested")).unwrap();
+  std::fs::write(ws.join("b/src/nested/mod.rs"), "").unwrap();
+  std::fs::write(ws.join("b/src/lib.rs"), "pub fn b() {}\nmod nested;\n").unwrap();
+  let flags = generate(ws.join("b/src/nested/mod.rs"), &options(&ws, None)).unwrap();
+  assert!(flags.flags.contains(&RustcFlag::CrateName("b".into())));
+}
@@ -0,0 +1,69 @@
+// The structured `CARGO_PKG_*` part of the environment.
+
+mod common;
+
+use generate_rustc_flags::{generate, FallbackMode, FlagsOptions};
+use std::fs;
+
+#[test]
+fn the_version_is_split_like_cargo() {
+  let ws = common::fixture("basic", "package_env_version");
+  let manifest = fs::read_to_string(ws.join("Cargo.toml")).unwrap().replace(
+    "version = \"0.1.0\"\n",
+    "version = \"1.2.3-beta.1+abc\"\nhomepage = \"https://example.com\"\nreadme = \"README.md\"\n",
+  );
+  fs::write(ws.join("Cargo.toml"), manifest).unwrap();
+
+  let flags = generate(ws.join("src/lib.rs"), &FlagsOptions::default()).unwrap();
+  let package_env = flags.package_env.as_ref().unwrap();
+  assert_eq!(package_env.version, "1.2.3-beta.1+abc");
+  assert_eq!(package_env.version_pre, "beta.1");
+  assert_eq!(package_env.version_build, "abc");
+  assert_eq!(flags.env["CARGO_PKG_VERSION"], "1.2.3-beta.1+abc");
+  assert_eq!(flags.env["CARGO_PKG_VERSION_PRE"], "beta.1");
+  assert_eq!(flags.env["CARGO_PKG_HOMEPAGE"], "https://example.com");
+  assert_eq!(flags.env["CARGO_PKG_README"], "README.md");
+  // Everything in the structured form is in the flat one.
+  for (key, value) in package_env.vars() {
+    assert_eq!(flags.env[&key], value, "{}", key);
+  }
+}
+
+#[test]
+fn set_package_env_replaces_the_old_variables() {
+  let ws = common::fixture("basic", "package_env_set");
+  let mut flags = generate(ws.join("src/lib.rs"), &FlagsOptions::default()).unwrap();
+  assert_eq!(flags.env["CARGO_PKG_VERSION_PRE"], "");
+  assert!(!flags.env.contains_key("CARGO_MANIFEST_LINKS"));
+
+  let mut package_env = flags.package_env.clone().unwrap();
+  package_env.version = "0.2.0".into();
+  package_env.version_minor = 2;
+  package_env.links = Some("z".into());
+  package_env.extra.insert("BUILD_ID".into(), "7".into());
+  flags.set_package_env(package_env.clone());
+  assert_eq!(flags.env["CARGO_PKG_VERSION"], "0.2.0");
+  assert_eq!(flags.env["CARGO_PKG_VERSION_MINOR"], "2");
+  assert_eq!(flags.env["CARGO_MANIFEST_LINKS"], "z");
+  assert_eq!(flags.env["BUILD_ID"], "7");
+  // The rest of the environment is untouched.
+  assert_eq!(flags.env["CARGO_CRATE_NAME"], "basic");
+
+  package_env.links = None;
+  package_env.extra.clear();
+  flags.set_package_env(package_env);
+  assert!(!flags.env.contains_key("CARGO_MANIFEST_LINKS"));
+  assert!(!flags.env.contains_key("BUILD_ID"));
+  assert_eq!(flags.env["CARGO_PKG_VERSION"], "0.2.0");
+}
+
+#[test]
+fn standalone_files_have_no_package_env() {
+  let ws = common::fixture("basic", "package_env_fallback");
+  let options = FlagsOptions {
+    fallback: FallbackMode::Always,
+    ..FlagsOptions::default()
+  };
+  let flags = generate(ws.join("src/lib.rs"), &options).unwrap();
+  assert!(flags.package_env.is_none());
+}
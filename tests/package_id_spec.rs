@@ -0,0 +1,32 @@
+// Picking one of two versions of a dependency that compile the same file.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions, RustcFlags};
+
+fn generate_with_spec(test: &str, spec: &str) -> anyhow::Result<RustcFlags> {
+  let ws = common::fixture("versions", test);
+  // shared-1 is excluded from the workspace; loaded on its own it never sees shared-2.
+  let options = FlagsOptions {
+    manifest_path: Some(ws.join("Cargo.toml")),
+    package_id_spec: Some(spec.to_string()),
+    ..FlagsOptions::default()
+  };
+  generate(ws.join("shared-1/src/lib.rs"), &options)
+}
+
+#[test]
+fn the_spec_selects_the_version() {
+  let flags = generate_with_spec("package_id_spec_1", "shared:1.0.0").unwrap();
+  assert_eq!(flags.env["CARGO_PKG_VERSION"], "1.0.0");
+
+  // shared-2 reaches the file through `..`, which only matches once resolved.
+  let flags = generate_with_spec("package_id_spec_2", "shared:2.0.0").unwrap();
+  assert_eq!(flags.env["CARGO_PKG_VERSION"], "2.0.0");
+}
+
+#[test]
+fn a_spec_matching_no_unit_is_an_error() {
+  let err = generate_with_spec("package_id_spec_missing", "shared:3.0.0").unwrap_err();
+  assert!(err.to_string().contains("in package"), "{}", err);
+}
@@ -0,0 +1,31 @@
+// `[patch]` applies as in a normal build, so the dependency's flags (and the artifact the
+// `--extern` names) come from the patched source.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions, RustcFlag};
+
+#[test]
+fn dependencies_resolve_to_the_patch() {
+  let ws = common::fixture("patch", "patch");
+  let options = FlagsOptions {
+    manifest_path: Some(ws.join("Cargo.toml")),
+    ..FlagsOptions::default()
+  };
+  let (app, local, vendored) = common::in_dir(&ws, || {
+    (
+      generate(ws.join("src/lib.rs"), &options).unwrap(),
+      generate(ws.join("local_foo/src/lib.rs"), &options),
+      generate(ws.join("vendor/foo/src/lib.rs"), &options),
+    )
+  });
+
+  assert!(app
+    .flags
+    .iter()
+    .any(|flag| matches!(flag, RustcFlag::Extern { name, .. } if name == "foo")));
+  let local = local.unwrap();
+  assert_eq!(local.env["CARGO_PKG_VERSION"], "1.0.1");
+  // The registry's copy isn't part of the build at all.
+  assert!(vendored.is_err());
+}
@@ -0,0 +1,24 @@
+// `rustc --print` queries in the crate's configuration.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions};
+
+#[test]
+fn cfg_is_printed_with_the_crates_cfgs() {
+  let ws = common::fixture("basic", "print_cfg");
+  let options = FlagsOptions {
+    print: Some("cfg".to_string()),
+    ..FlagsOptions::default()
+  };
+  let flags = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap());
+  let args = flags.to_args();
+  assert_eq!(common::values(&args, "--print"), ["cfg"]);
+  assert_eq!(common::values(&args, "--emit"), Vec::<String>::new());
+
+  let output = flags.command().current_dir(&ws).output().unwrap();
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  let stdout = String::from_utf8(output.stdout).unwrap();
+  // The default feature of `basic`.
+  assert!(stdout.lines().any(|line| line == "feature=\"foo\""), "{}", stdout);
+}
@@ -0,0 +1,29 @@
+// Proc-macros are compiled for the host, with the compiler's `proc_macro` crate.
+
+mod common;
+
+use cargo::core::compiler::CrateType;
+use generate_rustc_flags::{generate, FlagsOptions, RustcFlag};
+
+#[test]
+fn proc_macros_get_host_flags() {
+  let ws = common::fixture("build_deps", "proc_macro_host");
+  // Even when cross-compiling, the proc-macro itself is built for the host.
+  let options = FlagsOptions {
+    target: Some(common::host_triple()),
+    ..FlagsOptions::default()
+  };
+  let flags = generate(ws.join("mac/src/lib.rs"), &options).unwrap();
+  assert!(flags.flags.contains(&RustcFlag::CrateType(CrateType::ProcMacro)));
+  assert!(flags.flags.contains(&RustcFlag::Extern {
+    name: "proc_macro".into(),
+    path: None,
+    modifiers: Vec::new(),
+  }));
+  let args = flags.to_args();
+  assert!(common::values(&args, "--target").is_empty(), "{:?}", args);
+  let host_deps = ws.join("target/debug/deps");
+  assert_eq!(common::values(&args, "-L"), [format!("dependency={}", host_deps.display())]);
+
+  common::run(&args, &ws);
+}
@@ -0,0 +1,107 @@
+// Codegen flags derived from the `[profile]` settings.
+
+mod common;
+
+use generate_rustc_flags::{generate, CompileMode, FlagsOptions, ResolverOptions};
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::Write;
+
+// The `-C` values for the lib of `basic` with `profile` appended to its manifest.
+fn codegen(test: &str, profile: &str) -> Vec<String> {
+  let ws = common::fixture("basic", test);
+  let mut manifest = OpenOptions::new().append(true).open(ws.join("Cargo.toml")).unwrap();
+  manifest.write_all(profile.as_bytes()).unwrap();
+  let options = FlagsOptions::default();
+  let args = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap().to_args());
+  common::values(&args, "-C")
+}
+
+fn assertion_flags(values: Vec<String>) -> Vec<String> {
+  values
+    .into_iter()
+    .filter(|value| value.starts_with("debug-assertions") || value.starts_with("overflow-checks"))
+    .collect()
+}
+
+#[test]
+fn dev_defaults_are_left_to_rustc() {
+  assert_eq!(assertion_flags(codegen("profile_default", "")), Vec::<String>::new());
+}
+
+#[test]
+fn overflow_checks_can_be_turned_off() {
+  let values = codegen("profile_overflow", "\n[profile.dev]\noverflow-checks = false\n");
+  assert_eq!(assertion_flags(values), ["overflow-checks=off"]);
+}
+
+#[test]
+fn debug_assertions_can_be_turned_off() {
+  // Overflow checks stay on, but rustc would tie them to debug assertions.
+  let values = codegen("profile_debug_assertions", "\n[profile.dev]\ndebug-assertions = false\n");
+  assert_eq!(assertion_flags(values), ["debug-assertions=off", "overflow-checks=on"]);
+}
+
+fn codegen_units(values: Vec<String>) -> Vec<String> {
+  values.into_iter().filter(|value| value.starts_with("codegen-units")).collect()
+}
+
+#[test]
+fn codegen_units_follow_the_profile() {
+  assert_eq!(codegen_units(codegen("profile_codegen_units_default", "")), Vec::<String>::new());
+  let values = codegen("profile_codegen_units", "\n[profile.dev]\ncodegen-units = 1\n");
+  assert_eq!(codegen_units(values), ["codegen-units=1"]);
+  // Flags are generated for the dev profile, so release settings don't apply.
+  let values = codegen("profile_codegen_units_release", "\n[profile.release]\ncodegen-units = 1\n");
+  assert_eq!(codegen_units(values), Vec::<String>::new());
+}
+
+fn strip(mode: CompileMode, test: &str) -> Vec<String> {
+  let ws = common::fixture("basic", test);
+  // The linked cargo only reads `strip` with the (since stabilized) cargo feature.
+  let manifest = fs::read_to_string(ws.join("Cargo.toml")).unwrap();
+  let manifest = format!(
+    "cargo-features = [\"strip\"]\n\n{}\n[profile.dev]\nstrip = \"debuginfo\"\n",
+    manifest
+  );
+  fs::write(ws.join("Cargo.toml"), manifest).unwrap();
+  let options = FlagsOptions {
+    mode,
+    resolver: ResolverOptions {
+      allow_nightly_features: true,
+      ..ResolverOptions::default()
+    },
+    ..FlagsOptions::default()
+  };
+  let args = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap().to_args());
+  let values = common::values(&args, "-C");
+  values.into_iter().filter(|value| value.starts_with("strip")).collect()
+}
+
+#[test]
+fn strip_is_only_passed_to_linked_builds() {
+  assert_eq!(strip(CompileMode::Build, "profile_strip_build"), ["strip=debuginfo"]);
+  let check = CompileMode::Check { test: false };
+  assert_eq!(strip(check, "profile_strip_check"), Vec::<String>::new());
+}
+
+// The `-C opt-level` and `-C debuginfo` values for the lib of `basic` built with `profile`.
+fn optimization(test: &str, profile: Option<&str>) -> Vec<String> {
+  let ws = common::fixture("basic", test);
+  let options = FlagsOptions {
+    profile: profile.map(str::to_string),
+    ..FlagsOptions::default()
+  };
+  let args = generate(ws.join("src/lib.rs"), &options).unwrap().to_args();
+  let values = common::values(&args, "-C");
+  values
+    .into_iter()
+    .filter(|value| value.starts_with("opt-level") || value.starts_with("debuginfo"))
+    .collect()
+}
+
+#[test]
+fn the_selected_profile_sets_optimization() {
+  assert_eq!(optimization("profile_dev", None), ["debuginfo=2"]);
+  assert_eq!(optimization("profile_release", Some("release")), ["opt-level=3"]);
+}
@@ -0,0 +1,50 @@
+// Progress events, and cargo's messages routed through them.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions, ProgressEvent};
+use std::sync::{Arc, Mutex};
+
+fn events(ws: &std::path::Path) -> Vec<ProgressEvent> {
+  let events = Arc::new(Mutex::new(Vec::new()));
+  let sink = events.clone();
+  let options = FlagsOptions {
+    progress: Some(Arc::new(move |event: ProgressEvent| sink.lock().unwrap().push(event))),
+    ..FlagsOptions::default()
+  };
+  generate(ws.join("src/lib.rs"), &options).unwrap();
+  let events = events.lock().unwrap().clone();
+  events
+}
+
+#[test]
+fn build_scripts_are_reported() {
+  let ws = common::fixture("build_env", "progress_build_script");
+  let first = events(&ws);
+  assert_eq!(first[0], ProgressEvent::ResolvingWorkspace);
+  assert!(first.contains(&ProgressEvent::CompilingBuildScript("build_env".into())));
+  assert!(first.contains(&ProgressEvent::RunningBuildScript("build_env".into())));
+  // Cargo's own narration comes as messages.
+  assert!(
+    first
+      .iter()
+      .any(|event| matches!(event, ProgressEvent::Message(line) if line.starts_with("Compiling"))),
+    "{:?}",
+    first
+  );
+
+  // The script's output is fresh now, so it's neither compiled nor run again.
+  let second = events(&ws);
+  assert!(!second.contains(&ProgressEvent::CompilingBuildScript("build_env".into())));
+  assert!(!second.contains(&ProgressEvent::RunningBuildScript("build_env".into())));
+}
+
+#[test]
+fn packages_without_build_scripts_only_resolve() {
+  let ws = common::fixture("basic", "progress_basic");
+  let events = events(&ws);
+  assert_eq!(events[0], ProgressEvent::ResolvingWorkspace);
+  assert!(!events
+    .iter()
+    .any(|event| matches!(event, ProgressEvent::RunningBuildScript(_))));
+}
@@ -0,0 +1,63 @@
+// Flags for the files of dependencies, as the workspace compiles them.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions, RustcFlag, RustcFlags};
+use std::fs;
+
+fn caps_lints(flags: &[RustcFlag]) -> bool {
+  let cap = [RustcFlag::Raw("--cap-lints".into()), RustcFlag::Raw("allow".into())];
+  flags.windows(2).any(|pair| pair == cap)
+}
+
+// The flags of the `patch` fixture's lib and of its `foo` dependency, which comes from the
+// (vendored) registry once the patch is removed.
+fn vendored(test: &str, options: FlagsOptions) -> (RustcFlags, RustcFlags) {
+  let ws = common::fixture("patch", test);
+  let manifest = fs::read_to_string(ws.join("Cargo.toml")).unwrap();
+  let manifest = manifest.replace("[patch.crates-io]\nfoo = { path = \"local_foo\" }\n", "");
+  fs::write(ws.join("Cargo.toml"), manifest).unwrap();
+
+  let options = FlagsOptions {
+    manifest_path: Some(ws.join("Cargo.toml")),
+    ..options
+  };
+  common::in_dir(&ws, || {
+    (
+      generate(ws.join("src/lib.rs"), &options).unwrap(),
+      generate(ws.join("vendor/foo/src/lib.rs"), &options).unwrap(),
+    )
+  })
+}
+
+#[test]
+fn registry_dependencies_have_their_lints_capped() {
+  let (app, foo) = vendored("registry_deps_vendored", FlagsOptions::default());
+  assert_eq!(foo.env["CARGO_PKG_VERSION"], "1.0.0");
+  assert!(caps_lints(&foo.flags), "{:?}", foo.to_args());
+  assert!(!caps_lints(&app.flags), "{:?}", app.to_args());
+}
+
+#[test]
+fn path_dependencies_keep_their_lints() {
+  let ws = common::fixture("deps", "registry_deps_path");
+  let options = FlagsOptions {
+    manifest_path: Some(ws.join("Cargo.toml")),
+    ..FlagsOptions::default()
+  };
+  let used = common::in_dir(&ws, || generate(ws.join("used/src/lib.rs"), &options).unwrap());
+  assert_eq!(used.env["CARGO_PKG_NAME"], "used");
+  assert!(!caps_lints(&used.flags), "{:?}", used.to_args());
+}
+
+#[test]
+fn dependencies_are_not_migrated() {
+  let options = FlagsOptions {
+    edition_migration: true,
+    ..FlagsOptions::default()
+  };
+  let (app, foo) = vendored("registry_deps_migration", options);
+  let force_warns = |flags: &RustcFlags| common::values(&flags.to_args(), "--force-warn");
+  assert_eq!(force_warns(&app), ["rust-2021-compatibility"]);
+  assert_eq!(force_warns(&foo), Vec::<String>::new());
+}
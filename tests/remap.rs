@@ -0,0 +1,119 @@
+// Remapping machine-specific path prefixes for portable commands.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions};
+use std::{fs, io::Write, path::PathBuf};
+
+fn remaps(test: &str, options: &FlagsOptions) -> Vec<String> {
+  let ws = common::fixture("basic", test);
+  let flags = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), options).unwrap());
+  let prefix = "--remap-path-prefix=";
+  flags
+    .to_args()
+    .into_iter()
+    .filter_map(|arg| arg.strip_prefix(prefix).map(str::to_string))
+    .collect()
+}
+
+#[test]
+fn nothing_is_remapped_by_default() {
+  assert_eq!(remaps("remap_default", &FlagsOptions::default()), Vec::<String>::new());
+}
+
+#[test]
+fn the_sysroot_is_remapped() {
+  let sysroot = PathBuf::from("/opt/toolchain");
+  let options = FlagsOptions {
+    sysroot: Some(sysroot),
+    remap_sysroot: Some(PathBuf::from("/rustc/sysroot")),
+    ..FlagsOptions::default()
+  };
+  assert_eq!(remaps("remap_sysroot", &options), ["/opt/toolchain=/rustc/sysroot"]);
+}
+
+#[test]
+fn the_registry_is_remapped() {
+  let options = FlagsOptions {
+    remap_registry: Some(PathBuf::from("/registry")),
+    ..FlagsOptions::default()
+  };
+  let remaps = remaps("remap_registry", &options);
+  assert_eq!(remaps.len(), 1);
+  let (from, to) = remaps[0].rsplit_once('=').unwrap();
+  assert!(PathBuf::from(from).ends_with("registry/src"), "{}", from);
+  assert_eq!(to, "/registry");
+}
+
+// The remaps and `-Z remap-path-scope` for `basic` with `profile` appended to its manifest.
+fn trimmed(
+  test: &str,
+  profile: &str,
+  options: &FlagsOptions,
+) -> (PathBuf, Vec<String>, Vec<String>) {
+  let ws = common::fixture("basic", test);
+  let mut manifest = fs::OpenOptions::new().append(true).open(ws.join("Cargo.toml")).unwrap();
+  manifest.write_all(profile.as_bytes()).unwrap();
+  let args = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), options).unwrap().to_args());
+  let remaps = args
+    .iter()
+    .filter_map(|arg| arg.strip_prefix("--remap-path-prefix=").map(str::to_string))
+    .collect();
+  let scopes = common::values(&args, "-Z")
+    .into_iter()
+    .filter_map(|value| value.strip_prefix("remap-path-scope=").map(str::to_string))
+    .collect();
+  (ws, remaps, scopes)
+}
+
+#[test]
+fn the_profiles_trim_paths_remap_the_package() {
+  let (ws, remaps, scopes) = trimmed(
+    "remap_trim_paths",
+    "\n[profile.dev]\ntrim-paths = \"diagnostics\"\n",
+    &FlagsOptions::default(),
+  );
+  assert_eq!(scopes, ["diagnostics"]);
+  assert_eq!(remaps[0], format!("{}=.", ws.display()));
+  assert!(remaps[1].contains("lib/rustlib/src/rust=/rustc/"), "{:?}", remaps);
+}
+
+#[test]
+fn trim_paths_are_inherited() {
+  let options = FlagsOptions {
+    profile: Some("ci".into()),
+    ..FlagsOptions::default()
+  };
+  let (_, _, scopes) = trimmed(
+    "remap_trim_paths_inherited",
+    "\n[profile.dev]\ntrim-paths = true\n\n[profile.ci]\ninherits = \"dev\"\n",
+    &options,
+  );
+  assert_eq!(scopes, ["all"]);
+}
+
+#[test]
+fn the_trim_paths_option_overrides_the_profile() {
+  let profile = "\n[profile.dev]\ntrim-paths = [\"diagnostics\", \"object\"]\n";
+  let options = FlagsOptions {
+    trim_paths: Some("none".into()),
+    ..FlagsOptions::default()
+  };
+  let (_, remaps, scopes) = trimmed("remap_trim_paths_none", profile, &options);
+  assert_eq!((remaps, scopes), (Vec::new(), Vec::new()));
+
+  let (_, _, scopes) = trimmed("remap_trim_paths_list", profile, &FlagsOptions::default());
+  assert_eq!(scopes, ["diagnostics,object"]);
+}
+
+#[test]
+fn the_callers_remaps_come_last() {
+  let options = FlagsOptions {
+    remap_sysroot: Some(PathBuf::from("/rustc/sysroot")),
+    remap_path_prefixes: vec![(PathBuf::from("/home/me"), PathBuf::from("~"))],
+    ..FlagsOptions::default()
+  };
+  let remaps = remaps("remap_callers", &options);
+  assert_eq!(remaps.len(), 2);
+  assert_eq!(remaps[1], "/home/me=~");
+}
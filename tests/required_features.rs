@@ -0,0 +1,100 @@
+// Targets cargo leaves out of the unit graph until their `required-features` are enabled.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions, GenerateFlagsError};
+use std::{fs::OpenOptions, io::Write, path::Path};
+
+fn append(manifest: &Path, toml: &str) {
+  let mut manifest = OpenOptions::new().append(true).open(manifest).unwrap();
+  manifest.write_all(toml.as_bytes()).unwrap();
+}
+
+fn hint(file: std::path::PathBuf, options: &FlagsOptions) -> String {
+  let err = generate(file, options).unwrap_err();
+  match err.chain().find_map(|cause| cause.downcast_ref::<GenerateFlagsError>()) {
+    Some(GenerateFlagsError::UnitNotFound {
+      hint: Some(hint), ..
+    }) => hint.clone(),
+    _ => panic!("{:#}", err),
+  }
+}
+
+fn enabling() -> FlagsOptions {
+  FlagsOptions {
+    enable_required_features: true,
+    ..FlagsOptions::default()
+  }
+}
+
+#[test]
+fn the_missing_features_are_named() {
+  let ws = common::fixture("basic", "required_features_named");
+  append(
+    &ws.join("Cargo.toml"),
+    "\n[[bin]]\nname = \"basic\"\npath = \"src/main.rs\"\nrequired-features = [\"bar\"]\n",
+  );
+  let hint = hint(ws.join("src/main.rs"), &FlagsOptions::default());
+  assert!(hint.contains("bin `basic`"), "{}", hint);
+  assert!(hint.contains("bar"), "{}", hint);
+}
+
+#[test]
+fn enabled_features_are_not_named() {
+  let ws = common::fixture("basic", "required_features_active");
+  append(
+    &ws.join("Cargo.toml"),
+    "\n[[bin]]\nname = \"basic\"\npath = \"src/main.rs\"\nrequired-features = [\"foo\", \"bar\"]\n",
+  );
+  // `foo` is a default feature.
+  let hint = hint(ws.join("src/main.rs"), &FlagsOptions::default());
+  assert!(hint.contains("features bar,"), "{}", hint);
+  assert!(!hint.contains("foo"), "{}", hint);
+}
+
+#[test]
+fn required_features_can_be_enabled() {
+  let ws = common::fixture("basic", "required_features_enabled");
+  append(
+    &ws.join("Cargo.toml"),
+    "\n[[bin]]\nname = \"basic\"\npath = \"src/main.rs\"\nrequired-features = [\"bar\"]\n",
+  );
+  let args = generate(ws.join("src/main.rs"), &enabling()).unwrap().to_args();
+  assert_eq!(common::values(&args, "--crate-type"), ["bin"]);
+  assert!(common::values(&args, "--cfg").contains(&"feature=\"bar\"".to_string()));
+}
+
+#[test]
+fn lib_files_ignore_a_sibling_bins_features() {
+  let ws = common::fixture("basic", "required_features_lib");
+  append(
+    &ws.join("Cargo.toml"),
+    "\n[[bin]]\nname = \"basic\"\npath = \"src/main.rs\"\nrequired-features = [\"bar\"]\n",
+  );
+  for file in ["src/lib.rs", "src/util.rs"] {
+    let args = generate(ws.join(file), &enabling()).unwrap().to_args();
+    assert_eq!(common::values(&args, "--crate-type"), ["lib"], "{}", file);
+    assert!(
+      !common::values(&args, "--cfg").contains(&"feature=\"bar\"".to_string()),
+      "{}",
+      file
+    );
+  }
+}
+
+#[test]
+fn examples_only_report_their_own_features() {
+  let ws = common::fixture("dev_deps", "required_features_examples");
+  append(
+    &ws.join("Cargo.toml"),
+    "\n[features]\nx = []\n\n[[example]]\nname = \"demo\"\nrequired-features = [\"x\"]\n",
+  );
+  let args = generate(ws.join("examples/other.rs"), &FlagsOptions::default())
+    .unwrap()
+    .to_args();
+  assert_eq!(common::values(&args, "--crate-name"), ["other"]);
+
+  let hint = hint(ws.join("examples/demo.rs"), &FlagsOptions::default());
+  assert!(hint.contains("example `demo`"), "{}", hint);
+  assert!(hint.contains('x'), "{}", hint);
+}
@@ -0,0 +1,29 @@
+// The features resolved for every package in the graph.
+
+mod common;
+
+use generate_rustc_flags::{resolved_features, CliFeatures};
+
+fn features_of(features: CliFeatures, test: &str) -> Vec<(String, Vec<String>)> {
+  let ws = common::fixture("dep_features", test);
+  let resolved = common::in_dir(&ws, || resolved_features(ws.join("Cargo.toml"), features));
+  let mut resolved = resolved
+    .unwrap()
+    .into_iter()
+    .map(|(id, features)| (id.name().to_string(), features))
+    .collect::<Vec<_>>();
+  resolved.sort();
+  resolved
+}
+
+#[test]
+fn dependency_features_enabled_by_the_parent_are_reported() {
+  assert_eq!(
+    features_of(CliFeatures::new_all(false), "resolved_features"),
+    [
+      ("dep".to_string(), vec!["x".to_string()]),
+      ("dep_features".to_string(), Vec::new()),
+    ]
+  );
+}
+
@@ -0,0 +1,44 @@
+// The JSON messages rust-analyzer reads from an `overrideCommand`.
+
+#![cfg(feature = "rust-analyzer")]
+
+mod common;
+
+use generate_rustc_flags::{generate, rust_analyzer, FallbackMode, FlagsOptions};
+use serde_json::Value;
+
+#[test]
+fn messages_have_cargos_build_script_shape() {
+  let ws = common::fixture("basic", "rust_analyzer");
+  let options = FlagsOptions::default();
+  let flags = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap());
+  let messages = rust_analyzer::messages(&[flags]);
+  assert!(messages.ends_with('\n'));
+  assert_eq!(messages.lines().count(), 1);
+
+  let message = serde_json::from_str::<Value>(&messages).unwrap();
+  assert_eq!(message["reason"], "build-script-executed");
+  assert!(message["package_id"].as_str().unwrap().starts_with("basic 0.1.0 (path+file://"));
+  assert_eq!(message["linked_libs"], Value::Array(Vec::new()));
+  assert_eq!(message["linked_paths"], Value::Array(Vec::new()));
+  assert_eq!(message["cfgs"], serde_json::json!(["feature=\"default\"", "feature=\"foo\""]));
+  assert_eq!(message["out_dir"], Value::Null);
+
+  let env = message["env"].as_array().unwrap();
+  assert!(env.contains(&serde_json::json!(["CARGO_PKG_NAME", "basic"])));
+  let names = env.iter().map(|pair| pair[0].as_str().unwrap()).collect::<Vec<_>>();
+  let mut sorted = names.clone();
+  sorted.sort_unstable();
+  assert_eq!(names, sorted);
+}
+
+#[test]
+fn fallback_flags_have_no_message() {
+  let ws = common::fixture("basic", "rust_analyzer_fallback");
+  let options = FlagsOptions {
+    fallback: FallbackMode::Always,
+    ..FlagsOptions::default()
+  };
+  let flags = generate(ws.join("src/lib.rs"), &options).unwrap();
+  assert_eq!(rust_analyzer::messages(&[flags]), "");
+}
@@ -0,0 +1,49 @@
+// Caching flags by serializing them.
+
+#![cfg(feature = "serde")]
+
+mod common;
+
+use generate_rustc_flags::{generate, generate_test_flags, FlagsOptions, RustcFlags, TestFlags};
+
+#[test]
+fn json_round_trips() {
+  // Externs, search paths and lints cover the flags holding paths and enums.
+  let ws = common::fixture("deps", "serde_round_trip");
+  let options = FlagsOptions {
+    collect_inputs: true,
+    ..FlagsOptions::default()
+  };
+  let flags = common::in_dir(&ws, || generate(ws.join("src/main.rs"), &options).unwrap());
+
+  let json = serde_json::to_string(&flags).unwrap();
+  let back = serde_json::from_str::<RustcFlags>(&json).unwrap();
+  assert_eq!(back.to_args(), flags.to_args());
+  assert_eq!(back.flags, flags.flags);
+  assert_eq!(back.env, flags.env);
+  assert_eq!(back.origin, flags.origin);
+  assert_eq!(back.package_id, flags.package_id);
+  assert_eq!(back.profile, flags.profile);
+  assert_eq!(back.externs, flags.externs);
+  assert_eq!(back.rustc, flags.rustc);
+  assert_eq!(back.wrappers, flags.wrappers);
+  assert_eq!(back.inputs, flags.inputs);
+  assert_eq!(back.crate_root, flags.crate_root);
+  assert_eq!(back.file_overrides, flags.file_overrides);
+  assert_eq!(back.package_env, flags.package_env);
+}
+
+#[test]
+fn the_schema_version_is_serialized() {
+  let ws = common::fixture("basic", "serde_schema_version");
+  let flags = generate(ws.join("src/lib.rs"), &FlagsOptions::default()).unwrap();
+  let json = serde_json::to_value(&flags).unwrap();
+  assert_eq!(json["schema_version"], RustcFlags::SCHEMA_VERSION);
+
+  let test = generate_test_flags(ws.join("src/lib.rs"), &FlagsOptions::default()).unwrap();
+  let json = serde_json::to_string(&test).unwrap();
+  let back = serde_json::from_str::<TestFlags>(&json).unwrap();
+  assert_eq!(back.test_flags.to_args(), test.test_flags.to_args());
+  let doctest_args = |test: &TestFlags| test.doctest_flags.as_ref().map(RustcFlags::to_args);
+  assert_eq!(doctest_args(&back), doctest_args(&test));
+}
@@ -0,0 +1,44 @@
+// Source paths are resolved against the current directory before matching units.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions};
+
+#[test]
+fn relative_paths_match_the_unit() {
+  let ws = common::fixture("basic", "relative_source_path");
+  let options = FlagsOptions::default();
+  let flags = common::in_dir(&ws, || generate("src/util.rs", &options).unwrap());
+  assert_eq!(common::values(&flags.to_args(), "--crate-name"), ["basic"]);
+}
+
+#[test]
+fn missing_files_are_reported() {
+  let ws = common::fixture("basic", "missing_source_path");
+  let options = FlagsOptions::default();
+  let error = common::in_dir(&ws, || generate("src/missing.rs", &options).unwrap_err());
+  assert_eq!(error.to_string(), "Source file src/missing.rs does not exist");
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_match_the_unit_of_the_real_file() {
+  let ws = common::fixture("basic", "symlinked_source_path");
+  std::os::unix::fs::symlink(ws.join("src/util.rs"), ws.join("util.rs")).unwrap();
+  let options = FlagsOptions::default();
+  let flags = common::in_dir(&ws, || generate(ws.join("util.rs"), &options).unwrap());
+  assert_eq!(common::values(&flags.to_args(), "--crate-name"), ["basic"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn broken_symlinks_are_reported() {
+  let ws = common::fixture("basic", "broken_symlink_source_path");
+  std::os::unix::fs::symlink(ws.join("src/missing.rs"), ws.join("src/link.rs")).unwrap();
+  let options = FlagsOptions::default();
+  let error = common::in_dir(&ws, || generate("src/link.rs", &options).unwrap_err());
+  assert_eq!(
+    error.to_string(),
+    "Source file src/link.rs is a symlink to a file that does not exist"
+  );
+}
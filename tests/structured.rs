@@ -0,0 +1,35 @@
+// The structured result renders to the same command line the string API returns.
+
+mod common;
+
+use generate_rustc_flags::{generate, generate_rustc_flags_with, FlagsOptions, RustcFlag};
+
+#[test]
+fn renders_the_string_api_output() {
+  let ws = common::fixture("basic", "structured");
+  let options = FlagsOptions::default();
+  let (flags, args) = common::in_dir(&ws, || {
+    let flags = generate(ws.join("src/lib.rs"), &options).unwrap();
+    (flags, generate_rustc_flags_with(ws.join("src/lib.rs"), &options).unwrap())
+  });
+  assert_eq!(flags.to_args(), args);
+  assert!(flags.flags.contains(&RustcFlag::CrateName("basic".into())));
+  assert!(flags.flags.contains(&RustcFlag::Cfg("feature=\"foo\"".into())));
+  assert_eq!(flags.env["CARGO_PKG_NAME"], "basic");
+}
+
+#[test]
+fn the_crate_root_is_separate() {
+  let ws = common::fixture("basic", "structured_crate_root");
+  let options = FlagsOptions::default();
+  // The root of the target the file belongs to, not the file itself.
+  let flags = common::in_dir(&ws, || generate(ws.join("src/util.rs"), &options).unwrap());
+  assert_eq!(flags.crate_root, ws.join("src/lib.rs"));
+
+  let root = ws.join("src/lib.rs").display().to_string();
+  let with = flags.to_args();
+  let without = flags.to_args_without_crate_root();
+  assert!(with.contains(&root), "{:?}", with);
+  assert!(!without.contains(&root), "{:?}", without);
+  assert_eq!(without.len(), with.len() - 1);
+}
@@ -0,0 +1,47 @@
+// Finding the sysroot when the rustc is a driver that rejects `--print sysroot`. The
+// driver is selected with `RUSTC`, which is process-wide, so everything runs in one test.
+
+#![cfg(unix)]
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions};
+use std::{env, fs, os::unix::fs::PermissionsExt, path::PathBuf, process::Command};
+
+#[test]
+fn falls_back_when_the_driver_rejects_print_sysroot() {
+  let ws = common::fixture("basic", "sysroot_driver");
+  let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+  let output = Command::new(&rustc).args(&["--print", "sysroot"]).output().unwrap();
+  let sysroot = PathBuf::from(String::from_utf8(output.stdout).unwrap().trim());
+
+  // Cargo's own queries (`--print=sysroot` among them) still go through to rustc.
+  let driver = ws.join("driver.sh");
+  fs::write(
+    &driver,
+    format!(
+      "#!/bin/sh\n\
+       if [ \"$1\" = --print ] && [ \"$2\" = sysroot ]; then\n  \
+         echo 'error: unsupported' >&2\n  exit 1\n\
+       fi\n\
+       exec '{}' \"$@\"\n",
+      rustc
+    ),
+  )
+  .unwrap();
+  fs::set_permissions(&driver, fs::Permissions::from_mode(0o755)).unwrap();
+  env::set_var("RUSTC", &driver);
+
+  // The `sysroot` option skips the probe altogether.
+  let options = FlagsOptions {
+    sysroot: Some(sysroot.clone()),
+    ..FlagsOptions::default()
+  };
+  let flags = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap());
+  assert_eq!(common::values(&flags.to_args(), "--sysroot"), [sysroot.display().to_string()]);
+
+  env::set_var("SYSROOT", &sysroot);
+  let options = FlagsOptions::default();
+  let flags = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap());
+  assert_eq!(common::values(&flags.to_args(), "--sysroot"), [sysroot.display().to_string()]);
+}
@@ -0,0 +1,65 @@
+// Where artifacts go, and target directories that can't be created.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions};
+use std::{fs, path::Path};
+
+fn deps_dir(ws: &Path, options: &FlagsOptions) -> Vec<String> {
+  let args = generate(ws.join("src/lib.rs"), options).unwrap().to_args();
+  common::values(&args, "-L")
+}
+
+#[test]
+fn the_target_dir_can_be_moved() {
+  let ws = common::fixture("basic", "target_dir_option");
+  let target_dir = ws.join("elsewhere");
+  let options = FlagsOptions {
+    target_dir: Some(target_dir.clone()),
+    ..FlagsOptions::default()
+  };
+  let expected = format!("dependency={}", target_dir.join("debug/deps").display());
+  assert_eq!(deps_dir(&ws, &options), [expected]);
+  assert!(!ws.join("target").exists());
+}
+
+#[test]
+fn isolated_target_dirs_are_under_the_workspaces() {
+  let ws = common::fixture("basic", "target_dir_isolated");
+  let options = FlagsOptions {
+    isolated_target_dir: Some("tool".into()),
+    ..FlagsOptions::default()
+  };
+  let expected = format!("dependency={}", ws.join("target/tool/debug/deps").display());
+  assert_eq!(deps_dir(&ws, &options), [expected]);
+  assert!(!ws.join("target/debug").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn read_only_target_dirs_are_explained() {
+  use std::os::unix::fs::PermissionsExt;
+
+  let ws = common::fixture("basic", "read_only_target_dir");
+  let read_only = ws.join("read-only");
+  fs::create_dir_all(&read_only).unwrap();
+  fs::set_permissions(&read_only, fs::Permissions::from_mode(0o555)).unwrap();
+  // Permissions don't apply to root, so there's nothing to test.
+  if fs::write(read_only.join("probe"), "").is_ok() {
+    return;
+  }
+
+  fs::create_dir_all(ws.join(".cargo")).unwrap();
+  fs::write(
+    ws.join(".cargo/config.toml"),
+    format!("[build]\ntarget-dir = \"{}\"\n", read_only.join("target").display()),
+  )
+  .unwrap();
+  let options = FlagsOptions::default();
+  let err = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap_err());
+  fs::set_permissions(&read_only, fs::Permissions::from_mode(0o755)).unwrap();
+
+  let message = err.to_string();
+  assert!(message.starts_with("Could not set up the target directory"), "{:#}", err);
+  assert!(message.contains("CARGO_TARGET_DIR"), "{:#}", err);
+}
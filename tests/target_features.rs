@@ -0,0 +1,77 @@
+// Codegen flags like `-C target-cpu` set through rustflags in cargo's config. Without
+// `propagate_rustflags`, they are the only rustflags kept.
+
+mod common;
+
+use generate_rustc_flags::{generate, CompileMode, FlagsOptions, RustcFlag};
+use std::fs;
+
+#[test]
+fn codegen_flags_come_from_rustflags() {
+  let ws = common::fixture("basic", "target_features");
+  fs::create_dir_all(ws.join(".cargo")).unwrap();
+  fs::write(
+    ws.join(".cargo/config.toml"),
+    "[build]\nrustflags = [\"-C\", \"target-feature=+avx2\", \"-Ctarget-cpu=native\", \
+     \"--codegen=target-feature=+fma\"]\n",
+  )
+  .unwrap();
+
+  let options = FlagsOptions {
+    propagate_rustflags: false,
+    ..FlagsOptions::default()
+  };
+  let flags = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap());
+  let codegen = common::values(&flags.to_args(), "-C");
+  assert!(codegen.iter().any(|value| value == "target-cpu=native"), "{:?}", codegen);
+  let features = codegen
+    .iter()
+    .filter(|value| value.starts_with("target-feature="))
+    .collect::<Vec<_>>();
+  assert_eq!(features, ["target-feature=+avx2", "target-feature=+fma"]);
+  // rustc derives `target_feature` cfgs from the flag; passing them as `--cfg` is an error.
+  assert!(!flags.flags.iter().any(|flag| {
+    matches!(flag, RustcFlag::Cfg(cfg) if cfg.starts_with("target_feature"))
+  }));
+}
+
+#[test]
+fn relocation_and_code_models_come_from_rustflags() {
+  let ws = common::fixture("basic", "code_models");
+  fs::create_dir_all(ws.join(".cargo")).unwrap();
+  fs::write(
+    ws.join(".cargo/config.toml"),
+    "[build]\nrustflags = [\"-Crelocation-model=static\", \"-Ccode-model=small\", \
+     \"-Crelocation-model=pic\"]\n",
+  )
+  .unwrap();
+
+  let options = FlagsOptions {
+    mode: CompileMode::Build,
+    propagate_rustflags: false,
+    ..FlagsOptions::default()
+  };
+  let flags = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap());
+  let models = common::values(&flags.to_args(), "-C")
+    .into_iter()
+    .filter(|value| value.starts_with("relocation-model=") || value.starts_with("code-model="))
+    .collect::<Vec<_>>();
+  // As in rustc, the last relocation model wins.
+  assert_eq!(models, ["relocation-model=pic", "code-model=small"]);
+}
+
+#[test]
+fn rustflags_are_appended_as_written() {
+  let ws = common::fixture("basic", "rustflags_appended");
+  fs::create_dir_all(ws.join(".cargo")).unwrap();
+  fs::write(
+    ws.join(".cargo/config.toml"),
+    "[build]\nrustflags = [\"--cfg\", \"from_config\", \"-Crelocation-model=static\"]\n",
+  )
+  .unwrap();
+
+  let options = FlagsOptions::default();
+  let args = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap().to_args());
+  let rustflags = ["--cfg", "from_config", "-Crelocation-model=static"];
+  assert!(args.windows(3).any(|window| window == rustflags), "{:?}", args);
+}
@@ -0,0 +1,91 @@
+// Picking among several targets that compile a source file by their kind.
+
+mod common;
+
+use generate_rustc_flags::{
+  generate, generate_candidates, FlagsBuilder, FlagsOptions, GenerateFlagsError, TargetKind,
+};
+use std::{fs, path::Path};
+
+fn prioritized(priority: &[TargetKind]) -> FlagsOptions {
+  FlagsOptions {
+    target_priority: priority.to_vec(),
+    ..FlagsOptions::default()
+  }
+}
+
+fn crate_types(file: &Path, options: &FlagsOptions) -> Vec<String> {
+  let args = generate(file, options).unwrap().to_args();
+  common::values(&args, "--crate-type")
+}
+
+fn is_ambiguous(file: &Path, options: &FlagsOptions) -> bool {
+  let err = generate(file, options).unwrap_err();
+  err
+    .chain()
+    .any(|cause| matches!(cause.downcast_ref(), Some(GenerateFlagsError::AmbiguousUnit { .. })))
+}
+
+#[test]
+fn libs_come_first_by_default() {
+  let ws = common::fixture("basic", "target_priority_default");
+  assert_eq!(crate_types(&ws.join("src/util.rs"), &FlagsOptions::default()), ["lib"]);
+}
+
+#[test]
+fn the_priority_can_be_changed() {
+  let ws = common::fixture("basic", "target_priority_changed");
+  let file = ws.join("src/util.rs");
+  let bins_first = prioritized(&[TargetKind::Bin, TargetKind::Lib]);
+  assert_eq!(crate_types(&file, &bins_first), ["bin"]);
+
+  let options = FlagsBuilder::new().target_priority(vec![TargetKind::Bin]).build().unwrap();
+  assert_eq!(options.target_priority, [TargetKind::Bin]);
+
+  // Kinds that aren't listed are never picked.
+  assert!(is_ambiguous(&file, &prioritized(&[TargetKind::Example])));
+  assert!(is_ambiguous(&file, &prioritized(&[])));
+}
+
+#[test]
+fn proc_macros_count_as_libs() {
+  let ws = common::fixture("basic", "target_priority_proc_macro");
+  let manifest = fs::read_to_string(ws.join("Cargo.toml")).unwrap();
+  fs::write(ws.join("Cargo.toml"), manifest + "\n[lib]\nproc-macro = true\n").unwrap();
+  assert_eq!(crate_types(&ws.join("src/util.rs"), &FlagsOptions::default()), ["proc-macro"]);
+}
+
+#[test]
+fn two_bins_stay_ambiguous() {
+  let ws = common::fixture("multi_bin", "target_priority_bins");
+  let file = ws.join("src/bin/common/mod.rs");
+  assert!(is_ambiguous(&file, &prioritized(&[TargetKind::Bin])));
+}
+
+#[test]
+fn every_candidate_gets_flags() {
+  let ws = common::fixture("multi_bin", "target_priority_candidates");
+  let candidates =
+    generate_candidates(ws.join("src/bin/common/mod.rs"), &FlagsOptions::default()).unwrap();
+  let mut names = candidates
+    .iter()
+    .map(|(id, flags)| {
+      let crate_name = common::values(&flags.to_args(), "--crate-name");
+      assert_eq!(crate_name, [id.target_name.clone()]);
+      format!("{} {}", id.target_kind, id.target_name)
+    })
+    .collect::<Vec<_>>();
+  names.sort();
+  assert_eq!(names, ["bin a", "bin b"]);
+
+  fs::write(ws.join("elsewhere.rs"), "").unwrap();
+  let err = generate_candidates(ws.join("elsewhere.rs"), &FlagsOptions::default()).unwrap_err();
+  assert!(
+    err.chain().any(|cause| matches!(
+      cause.downcast_ref(),
+      Some(GenerateFlagsError::UnitNotFound { .. })
+    )),
+    "{:#}",
+    err
+  );
+}
@@ -0,0 +1,52 @@
+// Generating flags for a target picked by kind and name.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions, RustcFlag, RustcFlags, TargetSelector};
+
+fn generate_for(test: &str, file: &str, selector: TargetSelector) -> anyhow::Result<RustcFlags> {
+  let ws = common::fixture("dev_deps", test);
+  let options = FlagsOptions {
+    target_selector: Some(selector),
+    ..FlagsOptions::default()
+  };
+  generate(ws.join(file), &options)
+}
+
+#[test]
+fn selected_tests_compile_in_test_mode() {
+  let flags = generate_for("selector_test", "tests/it.rs", TargetSelector::Test("it".into()));
+  let flags = flags.unwrap();
+  assert!(flags.flags.contains(&RustcFlag::CrateName("it".into())));
+  assert!(flags.flags.contains(&RustcFlag::Raw("--test".into())));
+}
+
+#[test]
+fn the_lib_can_be_selected() {
+  let flags = generate_for("selector_lib", "src/lib.rs", TargetSelector::Lib).unwrap();
+  assert!(flags.flags.contains(&RustcFlag::CrateName("dev_deps".into())));
+}
+
+#[test]
+fn files_outside_the_selected_target_are_an_error() {
+  let selector = TargetSelector::Example("demo".into());
+  let err = generate_for("selector_other", "examples/other.rs", selector).unwrap_err();
+  assert!(err.to_string().contains("is not part of the selected target"), "{}", err);
+}
+
+#[test]
+fn shared_files_list_their_targets_until_one_is_selected() {
+  let ws = common::fixture("multi_bin", "selector_multi_bin");
+  let file = ws.join("src/bin/common/mod.rs");
+  let err = generate(&file, &FlagsOptions::default()).unwrap_err();
+  let message = err.to_string();
+  assert!(message.contains("belongs to several targets"), "{}", message);
+  assert!(message.contains("bin `a`") && message.contains("bin `b`"), "{}", message);
+
+  let options = FlagsOptions {
+    target_selector: Some(TargetSelector::Bin("b".into())),
+    ..FlagsOptions::default()
+  };
+  let flags = generate(&file, &options).unwrap();
+  assert!(flags.flags.contains(&RustcFlag::CrateName("b".into())));
+}
@@ -0,0 +1,71 @@
+// The test-harness and doctest flags of a library, from one cargo context.
+
+mod common;
+
+use cargo::core::compiler::CrateType;
+use generate_rustc_flags::{
+  generate_all_rustc_flags, generate_test_flags, CompileMode, FlagsOptions, RustcFlag,
+};
+
+#[test]
+fn test_and_doctest_flags_come_together() {
+  let ws = common::fixture("basic", "test_flags");
+  let options = FlagsOptions::default();
+  let flags = common::in_dir(&ws, || generate_test_flags(ws.join("src/lib.rs"), &options).unwrap());
+
+  let test = &flags.test_flags.flags;
+  assert!(test.contains(&RustcFlag::Raw("--test".into())));
+  assert!(!test.contains(&RustcFlag::Cfg("doctest".into())));
+  assert!(!test.iter().any(|flag| matches!(flag, RustcFlag::CrateType(_))));
+
+  let doctest = &flags.doctest_flags.unwrap().flags;
+  assert!(doctest.contains(&RustcFlag::Cfg("doctest".into())));
+  assert!(doctest.contains(&RustcFlag::CrateName("basic".into())));
+}
+
+#[test]
+fn doctests_extern_the_documented_crate() {
+  let ws = common::fixture("basic", "test_flags_self_extern");
+  let options = FlagsOptions::default();
+  let flags = common::in_dir(&ws, || generate_test_flags(ws.join("src/lib.rs"), &options).unwrap());
+
+  let path = flags
+    .doctest_flags
+    .unwrap()
+    .flags
+    .into_iter()
+    .find_map(|flag| match flag {
+      RustcFlag::Extern { name, path, .. } if name == "basic" => path,
+      _ => None,
+    })
+    .unwrap();
+  assert_eq!(path.parent().unwrap(), ws.join("target/debug/deps"));
+  let file_name = path.file_name().unwrap().to_str().unwrap();
+  assert!(file_name.starts_with("libbasic-") && file_name.ends_with(".rlib"), "{}", file_name);
+}
+
+#[test]
+fn every_lib_has_doctest_flags() {
+  let ws = common::fixture("basic", "test_flags_all_doctests");
+  let options = FlagsOptions {
+    mode: CompileMode::Doctest,
+    ..FlagsOptions::default()
+  };
+  let all = generate_all_rustc_flags(ws.join("Cargo.toml"), &options).unwrap();
+  let kinds = all.keys().map(|id| id.target_kind.as_str()).collect::<Vec<_>>();
+  assert_eq!(kinds, ["lib"]);
+  let doctest = &all.values().next().unwrap().flags;
+  assert!(doctest.contains(&RustcFlag::Cfg("doctest".into())));
+  assert!(doctest
+    .iter()
+    .any(|flag| matches!(flag, RustcFlag::Extern { name, .. } if name == "basic")));
+}
+
+#[test]
+fn proc_macro_doctests_say_so() {
+  let ws = common::fixture("build_deps", "test_flags_proc_macro");
+  let options = FlagsOptions::default();
+  let flags = generate_test_flags(ws.join("mac/src/lib.rs"), &options).unwrap();
+  let doctest = &flags.doctest_flags.unwrap().flags;
+  assert!(doctest.contains(&RustcFlag::CrateType(CrateType::ProcMacro)));
+}
@@ -0,0 +1,50 @@
+// Per-phase timings of flag generation.
+
+mod common;
+
+use generate_rustc_flags::{generate, FallbackMode, FlagsOptions};
+use std::time::Duration;
+
+fn timed() -> FlagsOptions {
+  FlagsOptions {
+    timings: true,
+    ..FlagsOptions::default()
+  }
+}
+
+#[test]
+fn timings_are_only_collected_on_request() {
+  let ws = common::fixture("basic", "timings_default");
+  let flags = generate(ws.join("src/lib.rs"), &FlagsOptions::default()).unwrap();
+  assert!(flags.timings.is_none());
+}
+
+#[test]
+fn the_phases_add_up() {
+  let ws = common::fixture("build_env", "timings_phases");
+  let timings = generate(ws.join("src/lib.rs"), &timed()).unwrap().timings.unwrap();
+  assert!(timings.workspace_load > Duration::ZERO, "{:?}", timings);
+  assert!(timings.resolve > Duration::ZERO, "{:?}", timings);
+  // The build script is compiled and run.
+  assert!(timings.build_scripts > Duration::ZERO, "{:?}", timings);
+  let phases = timings.sysroot
+    + timings.workspace_load
+    + timings.resolve
+    + timings.build_map
+    + timings.build_scripts
+    + timings.externs;
+  assert!(timings.total >= phases, "{:?}", timings);
+}
+
+#[test]
+fn fallback_flags_skip_cargos_phases() {
+  let ws = common::fixture("basic", "timings_fallback");
+  let options = FlagsOptions {
+    fallback: FallbackMode::Always,
+    ..timed()
+  };
+  let timings = generate(ws.join("src/lib.rs"), &options).unwrap().timings.unwrap();
+  assert_eq!(timings.workspace_load, Duration::ZERO);
+  assert_eq!(timings.resolve, Duration::ZERO);
+  assert!(timings.total > Duration::ZERO);
+}
@@ -0,0 +1,77 @@
+// Probing the sysroot of a chosen rustup toolchain.
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions, ToolchainInfo};
+use std::process::Command;
+
+fn sysroot_of(toolchain: &str) -> Option<String> {
+  let output = Command::new("rustup")
+    .args(&["run", toolchain, "rustc", "--print", "sysroot"])
+    .output()
+    .ok()?;
+  output
+    .status
+    .success()
+    .then(|| String::from_utf8(output.stdout).unwrap().trim().to_string())
+}
+
+#[test]
+fn the_chosen_toolchains_sysroot_is_used() {
+  // Only meaningful where rustup manages the toolchains.
+  let active = match Command::new("rustup").args(&["show", "active-toolchain"]).output() {
+    Ok(output) if output.status.success() => String::from_utf8(output.stdout).unwrap(),
+    _ => return,
+  };
+  let toolchain = active.split_whitespace().next().unwrap().to_string();
+  let sysroot = sysroot_of(&toolchain).unwrap();
+
+  let ws = common::fixture("basic", "toolchain_chosen");
+  let options = FlagsOptions {
+    toolchain: Some(toolchain),
+    ..FlagsOptions::default()
+  };
+  let args = generate(ws.join("src/lib.rs"), &options).unwrap().to_args();
+  assert_eq!(common::values(&args, "--sysroot"), [sysroot]);
+}
+
+#[test]
+fn unknown_toolchains_are_an_error() {
+  // These would be used instead.
+  if ["SYSROOT", "RUST_SYSROOT"].iter().any(|var| std::env::var_os(var).is_some()) {
+    return;
+  }
+  let ws = common::fixture("basic", "toolchain_unknown");
+  let options = FlagsOptions {
+    toolchain: Some("no-such-toolchain".into()),
+    ..FlagsOptions::default()
+  };
+  let err = generate(ws.join("src/lib.rs"), &options).unwrap_err();
+  assert!(format!("{:#}", err).contains("sysroot"), "{:#}", err);
+}
+
+#[test]
+fn toolchain_info_matches_the_generated_flags() {
+  let ws = common::fixture("basic", "toolchain_info");
+  let options = FlagsOptions::default();
+  let info = ToolchainInfo::query(&options, ws.join("src/lib.rs")).unwrap();
+  assert_eq!(info.host, common::host_triple());
+  assert_eq!(info.target, info.host);
+  // Queried again, from the cache.
+  assert_eq!(ToolchainInfo::query(&options, &ws).unwrap(), info);
+
+  let args = generate(ws.join("src/lib.rs"), &options).unwrap().to_args();
+  assert_eq!(common::values(&args, "--sysroot"), [info.sysroot.display().to_string()]);
+}
+
+#[test]
+fn toolchain_info_reports_the_chosen_target() {
+  let ws = common::fixture("basic", "toolchain_info_target");
+  let options = FlagsOptions {
+    target: Some("wasm32-unknown-unknown".into()),
+    ..FlagsOptions::default()
+  };
+  let info = ToolchainInfo::query(&options, &ws).unwrap();
+  assert_eq!(info.target, "wasm32-unknown-unknown");
+  assert_eq!(info.host, common::host_triple());
+}
@@ -0,0 +1,34 @@
+// The unit graph cargo resolved, as callers see it.
+
+mod common;
+
+use generate_rustc_flags::{unit_graph, FlagsOptions};
+
+#[test]
+fn edges_point_at_earlier_dependencies() {
+  let ws = common::fixture("versions", "unit_graph");
+  let graph = unit_graph(ws.join("Cargo.toml"), &FlagsOptions::default()).unwrap();
+  let (index, root) = graph
+    .units
+    .iter()
+    .enumerate()
+    .find(|(_, unit)| unit.target_name == "versions")
+    .unwrap();
+  assert_eq!(root.target_kind, "lib");
+  assert_eq!(root.compile_kind, "host");
+
+  let mut deps = root
+    .deps
+    .iter()
+    .map(|edge| {
+      assert!(edge.unit < index, "{:?}", edge);
+      let dep = &graph.units[edge.unit];
+      (edge.extern_name.as_str(), dep.pkg_id.split(' ').take(2).collect::<Vec<_>>())
+    })
+    .collect::<Vec<_>>();
+  deps.sort();
+  assert_eq!(
+    deps,
+    [("shared1", vec!["shared", "1.0.0"]), ("shared2", vec!["shared", "2.0.0"])]
+  );
+}
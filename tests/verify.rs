@@ -0,0 +1,47 @@
+// Diffing the generated flags against the rustc command cargo runs.
+
+mod common;
+
+use generate_rustc_flags::{verify, FallbackMode, FlagsOptions};
+
+#[test]
+fn the_generated_flags_match_cargos() {
+  let ws = common::fixture("basic", "verify_match");
+  let diff = verify::against_cargo(ws.join("src/lib.rs"), &FlagsOptions::default()).unwrap();
+  assert!(diff.cargo_args.contains(&"basic".to_string()), "{:?}", diff.cargo_args);
+  // Whatever cargo version runs, the basics agree.
+  for item in [&diff.missing, &diff.extra].iter().copied().flatten() {
+    assert!(
+      !item.starts_with("--crate-name") && !item.starts_with("--cfg feature="),
+      "{:?}",
+      diff
+    );
+  }
+  assert!(!diff.extra.iter().any(|item| item.ends_with("src/lib.rs")), "{:?}", diff);
+}
+
+#[test]
+fn extra_arguments_are_reported() {
+  let ws = common::fixture("basic", "verify_extra");
+  let options = FlagsOptions {
+    extra_args: vec!["--cfg".into(), "from_caller".into()],
+    ..FlagsOptions::default()
+  };
+  // Run twice, since the unit has to be rebuilt for cargo to print its command.
+  for _ in 0..2 {
+    let diff = verify::against_cargo(ws.join("src/lib.rs"), &options).unwrap();
+    assert!(diff.extra.contains(&"--cfg from_caller".to_string()), "{:?}", diff);
+    assert!(!diff.is_empty());
+  }
+}
+
+#[test]
+fn standalone_files_cant_be_verified() {
+  let ws = common::fixture("basic", "verify_fallback");
+  let options = FlagsOptions {
+    fallback: FallbackMode::Always,
+    ..FlagsOptions::default()
+  };
+  let err = verify::against_cargo(ws.join("src/lib.rs"), &options).unwrap_err();
+  assert!(err.to_string().contains("isn't part of a cargo package"), "{}", err);
+}
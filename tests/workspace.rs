@@ -0,0 +1,20 @@
+// Generating for a workspace the caller has already loaded.
+
+mod common;
+
+use cargo::{core::Workspace, util::config::Config};
+use generate_rustc_flags::{generate, generate_for_workspace, FlagsOptions};
+
+#[test]
+fn a_loaded_workspace_gives_the_same_flags() {
+  let ws = common::fixture("basic", "workspace_loaded");
+  let options = FlagsOptions::default();
+  let (loaded, generated) = common::in_dir(&ws, || {
+    let config = Config::default().unwrap();
+    let workspace = Workspace::new(&ws.join("Cargo.toml"), &config).unwrap();
+    let loaded = generate_for_workspace(&workspace, ws.join("src/lib.rs"), &options).unwrap();
+    (loaded, generate(ws.join("src/lib.rs"), &options).unwrap())
+  });
+  assert_eq!(loaded.to_args(), generated.to_args());
+  assert_eq!(loaded.env, generated.env);
+}
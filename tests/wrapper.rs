@@ -0,0 +1,53 @@
+// Generating flags from the rustc command cargo passes a wrapper.
+
+mod common;
+
+use generate_rustc_flags::{generate, wrapper::Invocation, FlagsOptions};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::iter;
+use std::path::Path;
+
+fn invocation(args: &[String], cwd: &Path, env: &[(&str, &str)]) -> Invocation {
+  let args = iter::once("rustc".to_string()).chain(args.iter().cloned()).map(OsString::from);
+  let env = env.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<BTreeMap<_, _>>();
+  Invocation::new(args, cwd, env).unwrap()
+}
+
+#[test]
+fn generates_for_the_command_features() {
+  let ws = common::fixture("basic", "wrapper_features");
+  let args = ["--crate-name", "basic", "src/lib.rs", "--cfg", "feature=\"bar\""]
+    .iter()
+    .map(|arg| arg.to_string())
+    .collect::<Vec<_>>();
+  let env = [
+    ("CARGO_PKG_NAME", "basic"),
+    ("CARGO_PKG_VERSION", "0.1.0"),
+    ("CARGO_PRIMARY_PACKAGE", "1"),
+  ];
+  let flags = invocation(&args, &ws, &env).generate(FlagsOptions::default()).unwrap();
+  assert_eq!(common::values(&flags.to_args(), "--cfg"), vec!["feature=\"bar\"".to_string()]);
+}
+
+#[test]
+fn merge_adds_what_cargo_lacks() {
+  let ws = common::fixture("basic", "wrapper_merge");
+  let options = FlagsOptions {
+    extra_args: vec!["--cfg=wrapped".into()],
+    ..FlagsOptions::default()
+  };
+  let flags = generate(ws.join("src/lib.rs"), &options).unwrap();
+  // Without the program.
+  let cargo_args = flags.to_args()[1..]
+    .iter()
+    .filter(|arg| *arg != "--cfg=wrapped")
+    .cloned()
+    .collect::<Vec<_>>();
+
+  let invocation = invocation(&cargo_args, &ws, &[]);
+  assert_eq!(invocation.crate_root.as_ref(), Some(&flags.crate_root));
+  let merged = invocation.merge(&flags);
+  assert_eq!(merged.args[..cargo_args.len()], cargo_args[..]);
+  assert_eq!(merged.args[cargo_args.len()..], ["--cfg=wrapped".to_string()]);
+}
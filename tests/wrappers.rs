@@ -0,0 +1,51 @@
+// Running rustc the way cargo would, through the wrappers in its config.
+
+#![cfg(unix)]
+
+mod common;
+
+use generate_rustc_flags::{generate, FlagsOptions};
+use std::{fs, os::unix::fs::PermissionsExt};
+
+#[test]
+fn commands_go_through_the_configured_wrappers() {
+  let ws = common::fixture("basic", "wrappers");
+  // The wrapper notes that it ran, then runs rustc as given.
+  let wrapper = ws.join("wrapper.sh");
+  let log = ws.join("wrapped");
+  fs::write(
+    &wrapper,
+    format!("#!/bin/sh\necho \"$@\" >> '{}'\nexec \"$@\"\n", log.display()),
+  )
+  .unwrap();
+  fs::set_permissions(&wrapper, fs::Permissions::from_mode(0o755)).unwrap();
+  fs::create_dir_all(ws.join(".cargo")).unwrap();
+  fs::write(
+    ws.join(".cargo/config.toml"),
+    format!("[build]\nrustc-workspace-wrapper = '{}'\n", wrapper.display()),
+  )
+  .unwrap();
+
+  let options = FlagsOptions::default();
+  let flags = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap());
+  // A `RUSTC_WRAPPER` from the environment would come first.
+  assert_eq!(flags.wrappers.last(), Some(&wrapper));
+  assert!(!flags.rustc.as_os_str().is_empty());
+
+  fs::remove_file(&log).ok();
+  let output = flags.command().current_dir(&ws).output().unwrap();
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  let logged = fs::read_to_string(&log).unwrap();
+  assert!(logged.contains("--crate-name basic"), "{}", logged);
+}
+
+#[test]
+fn without_wrappers_rustc_runs_directly() {
+  let ws = common::fixture("basic", "wrappers_none");
+  let options = FlagsOptions::default();
+  let flags = common::in_dir(&ws, || generate(ws.join("src/lib.rs"), &options).unwrap());
+  if std::env::var_os("RUSTC_WRAPPER").is_none() {
+    assert!(flags.wrappers.is_empty(), "{:?}", flags.wrappers);
+    assert_eq!(flags.command().get_program(), flags.rustc.as_os_str());
+  }
+}